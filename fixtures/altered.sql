@@ -0,0 +1,15 @@
+-- Built with: sqlite3 fixtures/altered.db < fixtures/altered.sql
+
+-- Defaults which aren't constant, for columns every record stores
+CREATE TABLE ev (
+    id INTEGER PRIMARY KEY,
+    at TEXT DEFAULT CURRENT_TIMESTAMP,
+    day TEXT DEFAULT (date('now'))
+);
+INSERT INTO ev VALUES (1, '2026-01-02 03:04:05', '2026-01-02');
+
+-- Records written before these columns were added don't store them
+ALTER TABLE ev ADD COLUMN kind TEXT DEFAULT 'plain';
+ALTER TABLE ev ADD COLUMN score REAL DEFAULT 1;
+ALTER TABLE ev ADD COLUMN note;
+INSERT INTO ev VALUES (2, '2026-03-04 05:06:07', '2026-03-04', 'fancy', 2.5, 'new');
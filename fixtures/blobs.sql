@@ -0,0 +1,3 @@
+-- Built with: sqlite3 fixtures/blobs.db < fixtures/blobs.sql
+CREATE TABLE b (id INTEGER PRIMARY KEY, x BLOB);
+INSERT INTO b VALUES (1, x'00ff10'), (2, x''), (3, 'text'), (4, x'c328'), (5, NULL);
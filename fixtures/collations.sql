@@ -0,0 +1,11 @@
+-- Built with: sqlite3 fixtures/collations.db < fixtures/collations.sql
+CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT COLLATE NOCASE, email TEXT);
+INSERT INTO users VALUES
+    (1, 'Alice', 'ALICE@example.com'),
+    (2, 'bob', 'Bob@Example.com'),
+    (3, 'ALICE', 'alice@other.com'),
+    (4, 'Carol', 'carol@example.com');
+
+-- An index using its column's collation, and one with its own
+CREATE INDEX users_name ON users (name);
+CREATE INDEX users_email ON users (email COLLATE NOCASE);
@@ -0,0 +1,26 @@
+-- Built with: sqlite3 fixtures/large_schema.db < fixtures/large_schema.sql
+PRAGMA page_size = 512;
+
+-- Enough tables for sqlite_schema to need interior pages
+CREATE TABLE t01 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t02 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t03 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t04 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t05 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t06 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t07 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t08 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t09 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t10 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t11 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t12 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t13 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t14 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t15 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t16 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t17 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t18 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t19 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE TABLE t20 (id INTEGER PRIMARY KEY, name TEXT);
+CREATE INDEX t20_name ON t20 (name);
+INSERT INTO t20 VALUES (1, 'last'), (2, 'table');
@@ -0,0 +1,13 @@
+-- Built with: sqlite3 fixtures/overflow.db < fixtures/overflow.sql
+PRAGMA page_size = 1024;
+
+-- Records too big for a page, whose payloads continue on chains of overflow pages
+CREATE TABLE docs (id INTEGER PRIMARY KEY, title TEXT, body TEXT);
+INSERT INTO docs VALUES
+    (1, 'short', 'fits'),
+    (2, 'long', printf('%.5000c', 'x') || 'end'),
+    (3, 'blob', zeroblob(3000)),
+    (4, 'edge', printf('%.989c', 'y'));
+
+-- Index entries overflow sooner than table records
+CREATE INDEX docs_body ON docs (body);
@@ -0,0 +1,6 @@
+-- Built with: sqlite3 fixtures/partial_index.db < fixtures/partial_index.sql
+
+-- A partial index only holds the rows its WHERE passes
+CREATE TABLE t (a INT, b INT);
+INSERT INTO t VALUES (5, 1), (5, -1), (6, 1);
+CREATE INDEX ip ON t (a) WHERE b > 0;
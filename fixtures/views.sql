@@ -0,0 +1,12 @@
+-- Built with: sqlite3 fixtures/views.db < fixtures/views.sql
+CREATE TABLE people (id INTEGER PRIMARY KEY, name TEXT UNIQUE, team TEXT);
+INSERT INTO people VALUES (1, 'Ann', 'red'), (2, 'Bo', 'blue'), (3, 'Cy', 'red');
+
+-- A view, and a view over it which renames its columns
+CREATE VIEW team_sizes AS SELECT team, count(*) AS size FROM people GROUP BY team;
+CREATE VIEW big_teams (team_name) AS SELECT team FROM team_sizes WHERE size > 1;
+
+CREATE TRIGGER people_renamed AFTER UPDATE OF name ON people
+BEGIN
+    SELECT raise(ABORT, 'names are fixed');
+END;
@@ -0,0 +1,12 @@
+-- Built with: sqlite3 fixtures/without_rowid.db < fixtures/without_rowid.sql
+PRAGMA page_size = 1024;
+
+-- Enough rows for the table's B-tree to have interior pages
+CREATE TABLE words (word TEXT PRIMARY KEY, length INTEGER NOT NULL) WITHOUT ROWID;
+CREATE INDEX words_length ON words (length);
+WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 500)
+INSERT INTO words SELECT printf('word%04d', i), 8 + i % 3 FROM n;
+
+-- The primary key's columns are stored first, in the key's order
+CREATE TABLE pairs (note TEXT, a INTEGER, b TEXT, PRIMARY KEY (b, a DESC)) WITHOUT ROWID;
+INSERT INTO pairs VALUES ('one', 1, 'x'), ('two', 2, 'x'), ('three', 1, 'y');
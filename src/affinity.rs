@@ -0,0 +1,193 @@
+use crate::{
+    expr::{to_integer, to_numeric, to_real},
+    record::Value,
+};
+
+/// A column's type affinity: the type its values are preferably stored as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Affinity {
+    Text,
+    Numeric,
+    Integer,
+    Real,
+    /// Also known as "none": values are stored as-is.
+    Blob,
+}
+
+impl Affinity {
+    /// Determine the affinity of a declared column type, e.g. `VARCHAR(10)` or `BIGINT`.
+    pub fn from_type_name(type_name: &str) -> Self {
+        let type_name = type_name.to_ascii_uppercase();
+        if type_name.contains("INT") {
+            Affinity::Integer
+        } else if ["CHAR", "CLOB", "TEXT"]
+            .iter()
+            .any(|t| type_name.contains(t))
+        {
+            Affinity::Text
+        } else if type_name.contains("BLOB") || type_name.trim().is_empty() {
+            Affinity::Blob
+        } else if ["REAL", "FLOA", "DOUB"]
+            .iter()
+            .any(|t| type_name.contains(t))
+        {
+            Affinity::Real
+        } else {
+            Affinity::Numeric
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Affinity::Numeric | Affinity::Integer | Affinity::Real)
+    }
+
+    /// Apply this affinity to a value, as happens when a value is stored in a column or
+    /// compared with one. Conversions only happen when they are lossless.
+    pub fn apply(&self, value: Value) -> Value {
+        match (self, value) {
+            (Affinity::Text, value @ (Value::Integer(_) | Value::Real(_))) => {
+                Value::Text(value.to_string())
+            }
+            (Affinity::Numeric | Affinity::Integer, Value::Text(s)) => match parse_number(&s) {
+                Some(number) => integral(number),
+                None => Value::Text(s),
+            },
+            (Affinity::Numeric | Affinity::Integer, value @ Value::Real(_)) => integral(value),
+            (Affinity::Real, Value::Text(s)) => match parse_number(&s) {
+                Some(number) => Value::Real(to_real(&number).unwrap()),
+                None => Value::Text(s),
+            },
+            (Affinity::Real, Value::Integer(n)) => Value::Real(n as f64),
+            (_, value) => value,
+        }
+    }
+}
+
+/// Convert a real with no fractional part to an integer, if it fits.
+fn integral(value: Value) -> Value {
+    match value {
+        Value::Real(f)
+            if f.fract() == 0.0 && (-9.223372036854775e18..9.223372036854775e18).contains(&f) =>
+        {
+            Value::Integer(f as i64)
+        }
+        value => value,
+    }
+}
+
+/// Parse text that is entirely a well-formed number, ignoring surrounding whitespace.
+fn parse_number(s: &str) -> Option<Value> {
+    let s = s.trim();
+    if let Ok(n) = s.parse::<i64>() {
+        Some(Value::Integer(n))
+    } else {
+        // Rust also accepts "inf" and "NaN", which SQLite doesn't
+        s.parse::<f64>()
+            .ok()
+            .filter(|f| f.is_finite() && !s.contains(['i', 'I', 'n', 'N']))
+            .map(Value::Real)
+    }
+}
+
+/// `CAST(value AS type)`.
+pub fn cast(value: Value, affinity: Affinity) -> Value {
+    if value.is_null() {
+        return value;
+    }
+    match affinity {
+        Affinity::Text => match value {
+            Value::Blob(bytes) => Value::Text(String::from_utf8_lossy(&bytes).into_owned()),
+            value => Value::Text(value.to_string()),
+        },
+        Affinity::Blob => match value {
+            Value::Text(s) => Value::Blob(s.into_bytes()),
+            value @ Value::Blob(_) => value,
+            value => Value::Blob(value.to_string().into_bytes()),
+        },
+        Affinity::Integer => match value {
+            Value::Real(f) => Value::Integer(f as i64),
+            value => Value::Integer(integer_prefix(&value.to_string())),
+        },
+        Affinity::Real => Value::Real(to_real(&value).unwrap_or(0.0)),
+        Affinity::Numeric => match value {
+            value @ (Value::Integer(_) | Value::Real(_)) => value,
+            value => {
+                let text = value.to_string();
+                match to_numeric(&Value::Text(text.clone())) {
+                    Value::Real(f) if text.trim().parse::<f64>().is_ok() => {
+                        integral(Value::Real(f))
+                    }
+                    numeric => numeric,
+                }
+            }
+        },
+    }
+}
+
+/// The integer at the start of `s`, ignoring leading whitespace, or 0 if there is none.
+fn integer_prefix(s: &str) -> i64 {
+    let s = s.trim_start();
+    let end = s
+        .char_indices()
+        .find(|(i, c)| !(c.is_ascii_digit() || (*i == 0 && (*c == '-' || *c == '+'))))
+        .map(|(i, _)| i)
+        .unwrap_or(s.len());
+    match s[..end].parse::<i64>() {
+        Ok(n) => n,
+        // Too big to fit, so saturate
+        Err(_) if end > 1 => to_integer(&Value::Text(s[..end].to_owned())).unwrap_or(0),
+        Err(_) => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cast, Affinity};
+    use crate::record::Value;
+
+    #[test]
+    fn type_names() {
+        assert_eq!(Affinity::from_type_name("BIGINT"), Affinity::Integer);
+        assert_eq!(Affinity::from_type_name("varchar(10)"), Affinity::Text);
+        assert_eq!(Affinity::from_type_name(""), Affinity::Blob);
+        assert_eq!(Affinity::from_type_name("DOUBLE PRECISION"), Affinity::Real);
+        assert_eq!(Affinity::from_type_name("DECIMAL(10,2)"), Affinity::Numeric);
+        // "FLOATING POINT" contains "INT"
+        assert_eq!(
+            Affinity::from_type_name("FLOATING POINT"),
+            Affinity::Integer
+        );
+    }
+
+    #[test]
+    fn apply() {
+        let text = |s: &str| Value::Text(s.into());
+        assert_eq!(Affinity::Integer.apply(text("30")), Value::Integer(30));
+        assert_eq!(Affinity::Numeric.apply(text(" 3.0e1 ")), Value::Integer(30));
+        assert_eq!(Affinity::Numeric.apply(text("1.5")), Value::Real(1.5));
+        assert_eq!(Affinity::Integer.apply(text("30abc")), text("30abc"));
+        assert_eq!(Affinity::Real.apply(Value::Integer(1)), Value::Real(1.0));
+        assert_eq!(Affinity::Text.apply(Value::Integer(30)), text("30"));
+        assert_eq!(Affinity::Blob.apply(text("30")), text("30"));
+    }
+
+    #[test]
+    fn casts() {
+        let text = |s: &str| Value::Text(s.into());
+        assert_eq!(cast(text(" 12abc"), Affinity::Integer), Value::Integer(12));
+        assert_eq!(cast(text("1e3"), Affinity::Integer), Value::Integer(1));
+        assert_eq!(
+            cast(Value::Real(-1.9), Affinity::Integer),
+            Value::Integer(-1)
+        );
+        assert_eq!(
+            cast(Value::Real(1e30), Affinity::Integer),
+            Value::Integer(i64::MAX)
+        );
+        assert_eq!(cast(text("1e3"), Affinity::Numeric), Value::Integer(1000));
+        assert_eq!(cast(Value::Real(3.0), Affinity::Numeric), Value::Real(3.0));
+        assert_eq!(cast(text("abc"), Affinity::Real), Value::Real(0.0));
+        assert_eq!(cast(Value::Real(2.0), Affinity::Text), text("2.0"));
+        assert!(cast(Value::Null, Affinity::Integer).is_null());
+    }
+}
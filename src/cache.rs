@@ -0,0 +1,127 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+/// The cache size SQLite uses when the header doesn't set one: 2000 KiB.
+pub const DEFAULT_CACHE_SIZE: i64 = -2000;
+
+/// A least-recently-used cache of raw pages, keyed by page number.
+#[derive(Debug)]
+pub struct PageCache {
+    /// The `cache_size` setting: a number of pages if positive, or of KiB if negative.
+    size: i64,
+    page_size: usize,
+    /// The most pages the cache holds.
+    capacity: usize,
+    /// Each cached page, along with when it was last used.
+    pages: HashMap<usize, (u64, Arc<[u8]>)>,
+    /// The cached page numbers, by when they were last used.
+    recency: BTreeMap<u64, usize>,
+    /// Incremented on every use, to order uses.
+    clock: u64,
+    /// The number of page reads served from the cache.
+    pub hits: usize,
+    /// The number of page reads which had to go to the file.
+    pub misses: usize,
+}
+
+impl PageCache {
+    pub fn new(size: i64, page_size: usize) -> Self {
+        PageCache {
+            size,
+            page_size,
+            capacity: Self::capacity_for_size(size, page_size),
+            pages: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// The number of pages a `cache_size` setting allows.
+    fn capacity_for_size(size: i64, page_size: usize) -> usize {
+        if size >= 0 {
+            size as usize
+        } else {
+            (size.unsigned_abs() as usize * 1024) / page_size
+        }
+    }
+
+    pub fn size(&self) -> i64 {
+        self.size
+    }
+
+    /// Change the `cache_size` setting, evicting the least recently used pages if the cache
+    /// is over its new capacity.
+    pub fn set_size(&mut self, size: i64) {
+        self.size = size;
+        self.capacity = Self::capacity_for_size(size, self.page_size);
+        self.evict();
+    }
+
+    /// Get a page if it's cached, counting a hit if it is.
+    pub fn get(&mut self, page_index: usize) -> Option<Arc<[u8]>> {
+        self.clock += 1;
+        let (last_used, page) = self.pages.get_mut(&page_index)?;
+        self.hits += 1;
+        self.recency.remove(last_used);
+        self.recency.insert(self.clock, page_index);
+        *last_used = self.clock;
+        Some(page.clone())
+    }
+
+    /// Cache a page which had to be read from storage, counting a miss.
+    pub fn insert(&mut self, page_index: usize, page: Vec<u8>) -> Arc<[u8]> {
+        self.clock += 1;
+        self.misses += 1;
+        let page: Arc<[u8]> = page.into();
+        if self.capacity > 0 {
+            self.pages.insert(page_index, (self.clock, page.clone()));
+            self.recency.insert(self.clock, page_index);
+            self.evict();
+        }
+        page
+    }
+
+    /// Drop the least recently used pages until the cache is within its capacity.
+    fn evict(&mut self) {
+        while self.pages.len() > self.capacity {
+            let Some((_, page_index)) = self.recency.pop_first() else {
+                break;
+            };
+            self.pages.remove(&page_index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PageCache;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = PageCache::new(2, 4096);
+        cache.insert(1, vec![1]);
+        cache.insert(2, vec![2]);
+        // Page 1 is now more recently used than page 2
+        assert_eq!(cache.get(1).as_deref(), Some(&[1][..]));
+        cache.insert(3, vec![3]);
+        assert!(cache.get(2).is_none());
+        assert!(cache.get(1).is_some());
+        assert!(cache.get(3).is_some());
+        assert_eq!((cache.hits, cache.misses), (3, 3));
+
+        cache.set_size(0);
+        assert!(cache.get(1).is_none());
+        cache.insert(1, vec![1]);
+        assert!(cache.get(1).is_none());
+    }
+
+    #[test]
+    fn capacity_for_size() {
+        assert_eq!(PageCache::new(100, 4096).capacity, 100);
+        assert_eq!(PageCache::new(-2000, 4096).capacity, 500);
+    }
+}
@@ -1,112 +1,159 @@
-use nom::{number::complete::be_u32, IResult};
+use std::collections::HashMap;
 
-use crate::{
-    page::BTreePageType,
-    record::{Record, RecordType},
-    varint::varint,
-};
+use nom::{bytes::complete::take, error::ErrorKind, number::complete::be_u32, IResult};
+
+use crate::{page::BTreePageType, record::RecordView, varint::varint};
 
-#[allow(dead_code)]
 #[derive(Debug)]
-pub enum Cell {
-    TableLeaf(Record),
+pub enum Cell<'a> {
+    TableLeaf(RecordView<'a>),
     TableInterior {
         left_child_pointer: u32,
         key: i64,
     },
-    IndexLeaf(Record),
+    IndexLeaf(RecordView<'a>),
     IndexInterior {
         left_child_pointer: u32,
-        record: Record,
+        record: RecordView<'a>,
     },
 }
 
-impl Cell {
-    pub fn parse<'input>(
-        input: &'input [u8],
+/// A cell's payload which is too big for its page, so continues on overflow pages.
+#[derive(Debug, Clone, Copy)]
+pub struct Overflow<'a> {
+    /// The start of the payload, which is kept in the cell.
+    pub local: &'a [u8],
+    /// The size of the whole payload.
+    pub payload_size: usize,
+    /// The first of the linked list of overflow pages holding the rest of the payload.
+    pub first_page: u32,
+}
+
+/// The whole payloads of a page's overflowing cells, by their first overflow page.
+pub type OverflowPayloads = HashMap<u32, Vec<u8>>;
+
+/// A cell's payload, or as much of it as is in the cell.
+enum Payload<'a> {
+    Local(&'a [u8]),
+    Overflowing(Overflow<'a>),
+}
+
+impl<'a> Cell<'a> {
+    /// Parse a cell. The payloads of cells which overflow are taken from `overflow_payloads`,
+    /// which must have been read from their overflow pages (see [`Cell::overflow`]).
+    pub fn parse(
+        input: &'a [u8],
         ty: BTreePageType,
         usable_page_size: usize,
-        column_names: &[&str],
-        column_indices: &[usize],
-    ) -> IResult<&'input [u8], Self> {
-        let (input, left_child_pointer) = if matches!(ty, BTreePageType::IndexInterior) {
+        rowid_alias: Option<usize>,
+        overflow_payloads: &'a OverflowPayloads,
+    ) -> IResult<&'a [u8], Self> {
+        if matches!(ty, BTreePageType::TableInterior) {
             let (input, left_child_pointer) = be_u32(input)?;
-            (input, Some(left_child_pointer))
-        } else {
-            (input, None)
-        };
-
-        // Check for overflow
-        let input = if matches!(
-            ty,
-            BTreePageType::TableLeaf | BTreePageType::IndexInterior | BTreePageType::IndexLeaf
-        ) {
-            let (input, payload_size) = varint(input)?;
-            let payload_size = payload_size as usize;
-
-            let x = match ty {
-                BTreePageType::TableLeaf => usable_page_size - 35,
-                _ => ((usable_page_size - 12) * 64 / 255) - 23,
-            };
-            let m = ((usable_page_size - 12) * 32 / 255) - 23;
-            let k = m + ((payload_size - m) % (usable_page_size - 4));
+            let (input, key) = varint(input)?;
+            return Ok((
+                input,
+                Cell::TableInterior {
+                    left_child_pointer,
+                    key,
+                },
+            ));
+        }
 
-            if payload_size > x {
-                // Overflow
-                let overflow_size = if k <= x {
-                    payload_size - k
-                } else {
-                    payload_size - m
-                };
-                todo!("overflow of size {:?}", overflow_size);
-            }
+        let (input, (left_child_pointer, row_id, payload)) =
+            parse_payload(input, ty, usable_page_size)?;
+        let payload = match payload {
+            Payload::Local(payload) => payload,
+            Payload::Overflowing(overflow) => match overflow_payloads.get(&overflow.first_page) {
+                Some(payload) => payload,
+                None => {
+                    return Err(nom::Err::Failure(nom::error::Error::new(
+                        input,
+                        ErrorKind::TooLarge,
+                    )))
+                }
+            },
+        };
+        let (_, record) = RecordView::parse(payload, row_id, rowid_alias)?;
 
-            input
-        } else {
-            input
+        let cell = match (ty, left_child_pointer) {
+            (BTreePageType::TableLeaf, _) => Cell::TableLeaf(record),
+            (BTreePageType::IndexInterior, Some(left_child_pointer)) => Cell::IndexInterior {
+                left_child_pointer,
+                record,
+            },
+            _ => Cell::IndexLeaf(record),
         };
+        Ok((input, cell))
+    }
 
-        match ty {
-            BTreePageType::TableInterior => {
-                let (input, left_child_pointer) = be_u32(input)?;
-                let (input, key) = varint(input)?;
-                Ok((
-                    input,
-                    Cell::TableInterior {
-                        left_child_pointer,
-                        key,
-                    },
-                ))
-            }
-            BTreePageType::TableLeaf => {
-                let (input, record) =
-                    Record::parse(input, column_names, column_indices, RecordType::Table)?;
-                Ok((input, Cell::TableLeaf(record)))
-            }
-            BTreePageType::IndexInterior => {
-                let left_child_pointer = left_child_pointer.unwrap();
-                let (input, record) =
-                    Record::parse(input, column_names, column_indices, RecordType::Index)?;
-                Ok((
-                    input,
-                    Cell::IndexInterior {
-                        left_child_pointer,
-                        record,
-                    },
-                ))
-            }
-            BTreePageType::IndexLeaf => {
-                let (input, record) =
-                    Record::parse(input, column_names, column_indices, RecordType::Index)?;
-                Ok((input, Cell::IndexLeaf(record)))
-            }
+    /// Find where a cell's payload continues, if it doesn't fit in the page.
+    pub fn overflow(
+        input: &'a [u8],
+        ty: BTreePageType,
+        usable_page_size: usize,
+    ) -> IResult<&'a [u8], Option<Overflow<'a>>> {
+        if matches!(ty, BTreePageType::TableInterior) {
+            return Ok((input, None));
+        }
+        let (input, (_, _, payload)) = parse_payload(input, ty, usable_page_size)?;
+        match payload {
+            Payload::Local(_) => Ok((input, None)),
+            Payload::Overflowing(overflow) => Ok((input, Some(overflow))),
         }
     }
+}
 
-    pub fn as_record(&self) -> Option<&Record> {
-        match self {
-            Cell::TableLeaf(record) => Some(record),
-            _ => None,
-        }
+/// Parse the start of a cell which has a payload: the left child pointer of index interior
+/// cells, the rowid of table leaf cells, and the payload.
+#[allow(clippy::type_complexity)]
+fn parse_payload(
+    input: &[u8],
+    ty: BTreePageType,
+    usable_page_size: usize,
+) -> IResult<&[u8], (Option<u32>, Option<i64>, Payload<'_>)> {
+    let (input, left_child_pointer) = if matches!(ty, BTreePageType::IndexInterior) {
+        let (input, left_child_pointer) = be_u32(input)?;
+        (input, Some(left_child_pointer))
+    } else {
+        (input, None)
+    };
+    let (input, payload_size) = varint(input)?;
+    let payload_size = payload_size as usize;
+    let (input, row_id) = if matches!(ty, BTreePageType::TableLeaf) {
+        let (input, row_id) = varint(input)?;
+        (input, Some(row_id))
+    } else {
+        (input, None)
+    };
+
+    // The most payload a cell keeps in the page, and the least it keeps if it overflows
+    let max_local = match ty {
+        BTreePageType::TableLeaf => usable_page_size - 35,
+        _ => ((usable_page_size - 12) * 64 / 255) - 23,
+    };
+    let min_local = ((usable_page_size - 12) * 32 / 255) - 23;
+    if payload_size <= max_local {
+        let (input, payload) = take(payload_size)(input)?;
+        return Ok((input, (left_child_pointer, row_id, Payload::Local(payload))));
     }
+
+    // As much is kept as leaves the rest filling whole overflow pages, if that fits
+    let local_size = min_local + (payload_size - min_local) % (usable_page_size - 4);
+    let local_size = if local_size <= max_local {
+        local_size
+    } else {
+        min_local
+    };
+    let (input, local) = take(local_size)(input)?;
+    let (input, first_page) = be_u32(input)?;
+    let overflow = Overflow {
+        local,
+        payload_size,
+        first_page,
+    };
+    Ok((
+        input,
+        (left_child_pointer, row_id, Payload::Overflowing(overflow)),
+    ))
 }
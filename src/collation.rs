@@ -0,0 +1,134 @@
+use std::{cmp::Ordering, collections::HashMap, sync::Arc};
+
+use crate::record::{Value, ValueRef};
+
+/// A function comparing two strings, registered as a custom collation.
+pub type CollationFn = Arc<dyn Fn(&str, &str) -> Ordering + Send + Sync>;
+
+/// A way of comparing text values.
+#[derive(Clone)]
+pub enum Collation {
+    /// Compare bytes, the default.
+    Binary,
+    /// Fold ASCII letters to lower case before comparing.
+    NoCase,
+    /// Ignore trailing spaces.
+    RTrim,
+    Custom(CollationFn),
+}
+
+impl std::fmt::Debug for Collation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Collation::Binary => write!(f, "Binary"),
+            Collation::NoCase => write!(f, "NoCase"),
+            Collation::RTrim => write!(f, "RTrim"),
+            Collation::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+impl Collation {
+    pub fn compare_text(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            Collation::Binary => a.cmp(b),
+            Collation::NoCase => a
+                .bytes()
+                .map(|c| c.to_ascii_lowercase())
+                .cmp(b.bytes().map(|c| c.to_ascii_lowercase())),
+            Collation::RTrim => a.trim_end_matches(' ').cmp(b.trim_end_matches(' ')),
+            Collation::Custom(compare) => compare(a, b),
+        }
+    }
+
+    /// Compare two values in SQLite's sort order, using this collation if both are text.
+    pub fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        match (a, b) {
+            (Value::Text(a), Value::Text(b)) => self.compare_text(a, b),
+            _ => a.compare(b),
+        }
+    }
+
+    /// Compare a value still in its record with another value, like `compare`. Text is
+    /// compared without copying it out of the record.
+    pub fn compare_ref(&self, a: ValueRef, b: &Value) -> Ordering {
+        match (a, b) {
+            (ValueRef::Text(a), Value::Text(b)) => self.compare_text(a, b),
+            (ValueRef::Text(_), Value::Blob(_)) => Ordering::Less,
+            (ValueRef::Text(_), _) => Ordering::Greater,
+            (a, b) => self.compare(&a.to_value(), b),
+        }
+    }
+}
+
+/// The collations that can be named in `COLLATE` clauses: the built-in ones plus any
+/// registered by the user.
+#[derive(Clone, Default)]
+pub struct Collations {
+    custom: HashMap<String, CollationFn>,
+}
+
+impl Collations {
+    /// Register a custom collation, replacing any existing collation with the same name.
+    pub fn register<F>(&mut self, name: &str, compare: F)
+    where
+        F: Fn(&str, &str) -> Ordering + Send + Sync + 'static,
+    {
+        self.custom
+            .insert(name.to_ascii_lowercase(), Arc::new(compare));
+    }
+
+    /// Find a collation by name, ignoring case.
+    pub fn get(&self, name: &str) -> anyhow::Result<Collation> {
+        if let Some(compare) = self.custom.get(&name.to_ascii_lowercase()) {
+            return Ok(Collation::Custom(compare.clone()));
+        }
+        match name.to_ascii_lowercase().as_str() {
+            "binary" => Ok(Collation::Binary),
+            "nocase" => Ok(Collation::NoCase),
+            "rtrim" => Ok(Collation::RTrim),
+            _ => anyhow::bail!("no such collation sequence: {}", name),
+        }
+    }
+
+    /// Find a collation by name, defaulting to `BINARY` if there isn't one.
+    pub fn resolve(&self, name: Option<&str>) -> anyhow::Result<Collation> {
+        name.map_or(Ok(Collation::Binary), |name| self.get(name))
+    }
+}
+
+/// Do two collation names refer to the same collation? `None` means `BINARY`.
+pub fn same_collation(a: Option<&str>, b: Option<&str>) -> bool {
+    a.unwrap_or("binary")
+        .eq_ignore_ascii_case(b.unwrap_or("binary"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Collation, Collations};
+    use std::cmp::Ordering;
+
+    #[test]
+    fn builtin_collations() {
+        let collations = Collations::default();
+        let nocase = collations.get("NoCase").unwrap();
+        assert_eq!(nocase.compare_text("Alice", "aLICE"), Ordering::Equal);
+        assert_eq!(nocase.compare_text("a", "B"), Ordering::Less);
+        assert_eq!(Collation::Binary.compare_text("a", "B"), Ordering::Greater);
+        let rtrim = collations.get("rtrim").unwrap();
+        assert_eq!(rtrim.compare_text("a  ", "a"), Ordering::Equal);
+        assert_eq!(rtrim.compare_text(" a", "a"), Ordering::Less);
+        assert_eq!(
+            collations.get("nope").unwrap_err().to_string(),
+            "no such collation sequence: nope"
+        );
+    }
+
+    #[test]
+    fn custom_collations() {
+        let mut collations = Collations::default();
+        collations.register("length", |a, b| a.len().cmp(&b.len()));
+        let length = collations.get("LENGTH").unwrap();
+        assert_eq!(length.compare_text("zz", "aaa"), Ordering::Less);
+    }
+}
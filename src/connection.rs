@@ -0,0 +1,398 @@
+use std::{cmp::Ordering, sync::Arc};
+
+use crate::{
+    database::{Database, Schema, Stats},
+    pager::Pager,
+    query::{self, Query},
+    record::Value,
+};
+
+/// An open database, which statements are run against.
+pub struct Connection {
+    db: Database,
+}
+
+impl Connection {
+    /// Open a database file.
+    pub fn open(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        Ok(Connection {
+            db: Database::open(path)?,
+        })
+    }
+
+    /// Open a database whose pages are read from `pager`.
+    pub fn with_pager(pager: impl Pager + 'static) -> anyhow::Result<Self> {
+        Ok(Connection {
+            db: Database::new(pager)?,
+        })
+    }
+
+    /// Parse a statement, ready to be run.
+    pub fn prepare(&mut self, sql: &str) -> anyhow::Result<Statement<'_>> {
+        let (query, parameters) = Query::parse_with_parameters(sql)?;
+        Ok(Statement {
+            db: &mut self.db,
+            query,
+            parameters,
+            bound: None,
+        })
+    }
+
+    /// The tables, indexes, views and triggers in the database.
+    pub fn schema(&self) -> &Schema {
+        &self.db.schema
+    }
+
+    /// The underlying database, for its header and I/O statistics.
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    /// The pages read, records examined and values decoded by every statement run so far.
+    /// Compare snapshots with [`Stats::since`] to see what a single statement did.
+    pub fn stats(&self) -> Stats {
+        self.db.stats()
+    }
+
+    /// Register a custom collation, which `COLLATE` clauses can then name.
+    pub fn create_collation<F>(&mut self, name: &str, compare: F)
+    where
+        F: Fn(&str, &str) -> Ordering + Send + Sync + 'static,
+    {
+        self.db.collations.register(name, compare);
+    }
+}
+
+/// A parsed statement, which may have parameters for values to be bound to when it's run.
+pub struct Statement<'conn> {
+    db: &'conn mut Database,
+    query: Query,
+    /// The name of each parameter by number, or `None` for numbered parameters.
+    parameters: Vec<Option<String>>,
+    /// The query with the values last bound to its parameters.
+    bound: Option<Query>,
+}
+
+impl Statement<'_> {
+    /// The number of parameters, which is the highest parameter number.
+    pub fn parameter_count(&self) -> usize {
+        self.parameters.len()
+    }
+
+    /// The number of the parameter with the given name, including its prefix (e.g. `:name`).
+    pub fn parameter_index(&self, name: &str) -> Option<usize> {
+        self.parameters
+            .iter()
+            .position(|p| p.as_deref() == Some(name))
+            .map(|i| i + 1)
+    }
+
+    /// Run the statement with values bound to its parameters by number, returning its result
+    /// rows as they're read.
+    pub fn query(&mut self, params: &[Value]) -> anyhow::Result<Rows<'_>> {
+        if params.len() != self.parameters.len() {
+            anyhow::bail!(
+                "expected {} parameters, but {} given",
+                self.parameters.len(),
+                params.len()
+            );
+        }
+        self.run(params)
+    }
+
+    /// Run the statement with values bound to its parameters by name, returning its result
+    /// rows as they're read. Parameters without a value are NULL.
+    pub fn query_named(&mut self, params: &[(&str, Value)]) -> anyhow::Result<Rows<'_>> {
+        let mut values = vec![Value::Null; self.parameters.len()];
+        for (name, value) in params.iter() {
+            let index = self
+                .parameter_index(name)
+                .ok_or_else(|| anyhow::format_err!("no such parameter: {}", name))?;
+            values[index - 1] = value.clone();
+        }
+        self.run(&values)
+    }
+
+    fn run(&mut self, values: &[Value]) -> anyhow::Result<Rows<'_>> {
+        let query = if self.parameters.is_empty() {
+            &self.query
+        } else {
+            self.bound.insert(self.query.bind(values)?)
+        };
+        let rows = query.rows(self.db)?;
+        let column_names = rows.scope.columns.iter().map(|c| c.name.clone()).collect();
+        Ok(Rows { column_names, rows })
+    }
+
+    /// Run the statement to completion, discarding any result rows.
+    pub fn execute(&mut self, params: &[Value]) -> anyhow::Result<()> {
+        for row in self.query(params)? {
+            row?;
+        }
+        Ok(())
+    }
+}
+
+/// The result rows of a statement, which are computed as they're pulled.
+pub struct Rows<'stmt> {
+    column_names: Arc<[String]>,
+    rows: query::Rows<'stmt>,
+}
+
+impl<'stmt> Rows<'stmt> {
+    pub fn column_names(&self) -> &[String] {
+        &self.column_names
+    }
+
+    /// Deserialize each row into an owned `T`, as with [`Row::deserialize`].
+    #[cfg(feature = "serde")]
+    pub fn deserialize<T>(self) -> impl Iterator<Item = anyhow::Result<T>> + 'stmt
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.map(|row| row?.deserialize())
+    }
+}
+
+impl Iterator for Rows<'_> {
+    type Item = anyhow::Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let values = self.rows.next()?;
+        Some(values.map(|values| Row {
+            column_names: self.column_names.clone(),
+            values,
+        }))
+    }
+}
+
+/// A result row.
+#[derive(Debug, Clone)]
+pub struct Row {
+    column_names: Arc<[String]>,
+    values: Vec<Value>,
+}
+
+impl Row {
+    /// Get a column's value, converted to `T`.
+    pub fn get<T: FromValue>(&self, idx: usize) -> anyhow::Result<T> {
+        let value = self.values.get(idx).ok_or_else(|| {
+            anyhow::format_err!(
+                "column index {} out of range for {}",
+                idx,
+                self.values.len()
+            )
+        })?;
+        T::from_value(value)
+    }
+
+    pub fn column_names(&self) -> &[String] {
+        &self.column_names
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    pub fn into_values(self) -> Vec<Value> {
+        self.values
+    }
+
+    /// Deserialize the row into `T`: structs and maps by column name, and tuples and
+    /// sequences by column position. NULL deserializes as `None`.
+    #[cfg(feature = "serde")]
+    pub fn deserialize<'a, T: serde::Deserialize<'a>>(&'a self) -> anyhow::Result<T> {
+        Ok(T::deserialize(crate::de::RowDeserializer {
+            column_names: &self.column_names,
+            values: &self.values,
+        })?)
+    }
+}
+
+/// A type a column's value can be converted to.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> anyhow::Result<Self>;
+}
+
+fn invalid_type(value: &Value, type_name: &str) -> anyhow::Error {
+    anyhow::format_err!(
+        "cannot convert {} value to {}",
+        value.type_name(),
+        type_name
+    )
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        Ok(value.clone())
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        value.as_integer().ok_or_else(|| invalid_type(value, "i64"))
+    }
+}
+
+impl FromValue for i32 {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        let n = i64::from_value(value)?;
+        i32::try_from(n).map_err(|_| anyhow::format_err!("integer {} out of range for i32", n))
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        Ok(i64::from_value(value)? != 0)
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        match value {
+            Value::Integer(n) => Ok(*n as f64),
+            Value::Real(f) => Ok(*f),
+            _ => Err(invalid_type(value, "f64")),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        match value {
+            Value::Text(s) => Ok(s.clone()),
+            Value::Blob(bytes) => {
+                String::from_utf8(bytes.clone()).map_err(|_| invalid_type(value, "String"))
+            }
+            _ => Err(invalid_type(value, "String")),
+        }
+    }
+}
+
+impl FromValue for Vec<u8> {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        match value {
+            Value::Text(s) => Ok(s.clone().into_bytes()),
+            Value::Blob(bytes) => Ok(bytes.clone()),
+            _ => Err(invalid_type(value, "Vec<u8>")),
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> anyhow::Result<Self> {
+        match value {
+            Value::Null => Ok(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Connection;
+    use crate::record::Value;
+
+    #[test]
+    fn query_typed_rows() {
+        let mut conn = Connection::open(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        assert_eq!(
+            conn.schema().table_names(),
+            vec!["apples", "oranges", "sqlite_sequence"]
+        );
+
+        let mut statement = conn
+            .prepare("SELECT id, name, NULL AS missing FROM apples WHERE id = 2")
+            .unwrap();
+        let mut rows = statement.query(&[]).unwrap();
+        assert_eq!(rows.column_names(), ["id", "name", "missing"]);
+        let row = rows.next().unwrap().unwrap();
+        assert_eq!(row.get::<i64>(0).unwrap(), 2);
+        assert_eq!(row.get::<String>(1).unwrap(), "Fuji");
+        assert_eq!(row.get::<Option<String>>(2).unwrap(), None);
+        assert!(row.get::<i64>(1).is_err());
+        assert!(row.get::<i64>(3).is_err());
+        assert!(rows.next().is_none());
+        drop(rows);
+
+        // Expressions without an alias are named by their source text
+        let mut statement = conn
+            .prepare("SELECT id + 1, count(*) AS n FROM apples")
+            .unwrap();
+        let rows = statement.query(&[]).unwrap();
+        assert_eq!(rows.column_names(), ["id + 1", "n"]);
+    }
+
+    #[test]
+    fn bind_parameters() {
+        let mut conn = Connection::open(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        let names = |rows: super::Rows| {
+            rows.map(|row| row.unwrap().get::<String>(0).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let mut statement = conn
+            .prepare("SELECT name FROM apples WHERE id > ?1 AND color <> ? ORDER BY id")
+            .unwrap();
+        assert_eq!(statement.parameter_count(), 2);
+        let rows = statement
+            .query(&[Value::Integer(1), Value::Text("Red".into())])
+            .unwrap();
+        assert_eq!(names(rows), vec!["Honeycrisp", "Golden Delicious"]);
+        assert!(statement.query(&[Value::Integer(1)]).is_err());
+
+        // Text which looks like SQL is only ever a value
+        let mut statement = conn
+            .prepare("SELECT name FROM apples WHERE name = :name OR name = $name")
+            .unwrap();
+        let rows = statement
+            .query_named(&[(":name", Value::Text("Fuji' OR '1' = '1".into()))])
+            .unwrap();
+        assert!(names(rows).is_empty());
+        let rows = statement
+            .query_named(&[(":name", Value::Text("Fuji".into()))])
+            .unwrap();
+        assert_eq!(names(rows), vec!["Fuji"]);
+        assert!(statement.query_named(&[("@name", Value::Null)]).is_err());
+    }
+
+    #[test]
+    fn connections_move_between_threads() {
+        let conn = Connection::open(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        let row = std::thread::spawn(move || {
+            let mut conn = conn;
+            let mut statement = conn
+                .prepare("SELECT name FROM apples WHERE id = 2")
+                .unwrap();
+            let mut rows = statement.query(&[]).unwrap();
+            // Result rows can be handed to another thread as they're read
+            std::thread::scope(|s| s.spawn(|| rows.next().unwrap().unwrap()).join().unwrap())
+        })
+        .join()
+        .unwrap();
+        assert_eq!(row.get::<String>(0).unwrap(), "Fuji");
+    }
+
+    #[test]
+    fn stats_per_statement() {
+        let mut conn = Connection::open(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        let apples = conn.schema().table("apples").unwrap().root_page;
+        let run = |conn: &mut Connection| {
+            let before = conn.stats();
+            let mut statement = conn.prepare("SELECT name FROM apples").unwrap();
+            statement.execute(&[]).unwrap();
+            conn.stats().since(&before)
+        };
+
+        let stats = run(&mut conn);
+        assert_eq!(
+            stats.pages_read.into_iter().collect::<Vec<_>>(),
+            [(apples, 1)]
+        );
+        assert_eq!(stats.rows_examined, 4);
+        assert!(stats.bytes_decoded >= "Granny SmithFujiHoneycrispGolden Delicious".len());
+        assert_eq!((stats.cache_hits, stats.cache_misses), (0, 1));
+        let stats = run(&mut conn);
+        assert_eq!((stats.cache_hits, stats.cache_misses), (1, 0));
+    }
+}
@@ -1,16 +1,81 @@
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{BTreeMap, HashSet},
+    ops::Deref,
+    sync::Arc,
+};
+
 use crate::{
-    cell::Cell,
-    header::{Header, HEADER_SIZE},
+    cache::{PageCache, DEFAULT_CACHE_SIZE},
+    cell::{Cell, Overflow, OverflowPayloads},
+    collation::{same_collation, Collation, Collations},
+    error::CorruptError,
+    expr::{glob, like, Expr},
+    header::{Header, TextEncoding, HEADER_SIZE},
     page::Page,
-    query::Query,
-    record::{Record, Value},
+    pager::{MmapPager, Pager},
+    query::{
+        conjuncts, ColumnDefinition, IndexedColumn, Query, SelectQuery, TableConstraint,
+        TriggerEvent, TriggerTiming,
+    },
+    record::{Record, RecordView, Value},
 };
 
 pub struct Database {
     pub header: Header,
     pub schema: Schema,
-    pub table_pages_parsed: usize,
-    pub index_pages_parsed: usize,
+    /// The number of pages read from each B-tree, by its root page.
+    pub pages_read: BTreeMap<usize, usize>,
+    /// The number of records B-tree walks have come across.
+    pub rows_examined: usize,
+    /// The number of bytes of values decoded from records.
+    pub bytes_decoded: usize,
+    /// The number of overflow pages read for records too big for their B-tree pages.
+    pub overflow_pages_read: usize,
+    /// Recently read pages, shared by all B-tree walks.
+    pub page_cache: PageCache,
+    /// Where pages are read from.
+    pub pager: Box<dyn Pager>,
+    /// The collations queries can use, including any custom ones registered.
+    pub collations: Collations,
+}
+
+/// A snapshot of the work done reading a database, as `.stats` reports it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    /// The number of pages read from each B-tree, by its root page.
+    pub pages_read: BTreeMap<usize, usize>,
+    /// The number of page reads served from the page cache.
+    pub cache_hits: usize,
+    /// The number of page reads which had to go to the file.
+    pub cache_misses: usize,
+    /// The number of records B-tree walks came across, whether or not they were wanted.
+    pub rows_examined: usize,
+    /// The number of bytes of values decoded from records.
+    pub bytes_decoded: usize,
+    /// The number of overflow pages followed to read records too big for their B-tree pages.
+    pub overflow_pages_read: usize,
+}
+
+impl Stats {
+    /// The work done since an earlier snapshot, such as for a single statement.
+    pub fn since(&self, earlier: &Stats) -> Stats {
+        let pages_read = self
+            .pages_read
+            .iter()
+            .map(|(root, n)| (*root, n - earlier.pages_read.get(root).unwrap_or(&0)))
+            .filter(|(_, n)| *n > 0)
+            .collect();
+        Stats {
+            pages_read,
+            cache_hits: self.cache_hits - earlier.cache_hits,
+            cache_misses: self.cache_misses - earlier.cache_misses,
+            rows_examined: self.rows_examined - earlier.rows_examined,
+            bytes_decoded: self.bytes_decoded - earlier.bytes_decoded,
+            overflow_pages_read: self.overflow_pages_read - earlier.overflow_pages_read,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -18,13 +83,13 @@ pub struct Schema {
     pub objects: Vec<ObjectSchema>,
 }
 
-#[allow(dead_code)]
+#[allow(dead_code, clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum ObjectSchema {
     Table(TableSchema),
     Index(IndexSchema),
-    View,
-    Trigger,
+    View(ViewSchema),
+    Trigger(TriggerSchema),
 }
 
 impl ObjectSchema {
@@ -41,6 +106,49 @@ impl ObjectSchema {
             _ => None,
         }
     }
+
+    pub fn as_view(&self) -> Option<&ViewSchema> {
+        match self {
+            ObjectSchema::View(view) => Some(view),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            ObjectSchema::Table(table) => &table.name,
+            ObjectSchema::Index(index) => &index.name,
+            ObjectSchema::View(view) => &view.name,
+            ObjectSchema::Trigger(trigger) => &trigger.name,
+        }
+    }
+
+    /// The table the object belongs to, which for tables and views is themselves.
+    pub fn table_name(&self) -> &str {
+        match self {
+            ObjectSchema::Table(table) => &table.name,
+            ObjectSchema::Index(index) => &index.table_name,
+            ObjectSchema::View(view) => &view.name,
+            ObjectSchema::Trigger(trigger) => &trigger.table_name,
+        }
+    }
+
+    /// The statement which created the object, or `None` for automatic indexes.
+    pub fn sql(&self) -> Option<&str> {
+        match self {
+            ObjectSchema::Table(table) => Some(&table.sql),
+            ObjectSchema::Index(index) => index.sql.as_deref(),
+            ObjectSchema::View(view) => Some(&view.sql),
+            ObjectSchema::Trigger(trigger) => Some(&trigger.sql),
+        }
+    }
+
+    /// Is this one of SQLite's own objects, such as `sqlite_sequence`?
+    pub fn is_internal(&self) -> bool {
+        self.name()
+            .get(..7)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("sqlite_"))
+    }
 }
 
 #[derive(Debug)]
@@ -48,7 +156,9 @@ pub struct TableSchema {
     pub name: String,
     pub root_page: usize,
     pub sql: String,
-    pub column_names: Vec<String>,
+    pub columns: Vec<ColumnDefinition>,
+    pub constraints: Vec<TableConstraint>,
+    pub without_rowid: bool,
 }
 
 #[derive(Debug)]
@@ -56,261 +166,771 @@ pub struct IndexSchema {
     pub name: String,
     pub table_name: String,
     pub root_page: usize,
+    /// `None` for the indexes SQLite creates automatically for `PRIMARY KEY` and `UNIQUE`
+    /// constraints.
+    pub sql: Option<String>,
+    pub unique: bool,
+    pub columns: Vec<IndexedColumn>,
+    /// The `WHERE` clause of a partial index, which only holds the rows it passes.
+    pub filter: Option<Expr>,
+}
+
+#[derive(Debug)]
+pub struct ViewSchema {
+    pub name: String,
     pub sql: String,
-    pub column_name: String,
+    /// The column names given after the view's name, if any.
+    pub columns: Vec<String>,
+    pub select: SelectQuery,
 }
 
-impl Database {
-    /// Parse the first page of the database file, containing the header and a schema.
-    pub fn parse_header_and_schema<R>(mut file: R) -> anyhow::Result<Self>
-    where
-        R: std::io::Read,
-    {
-        let mut header_buf = [0; HEADER_SIZE];
-        file.read_exact(&mut header_buf)?;
-        let (rest, header) = Header::parse(&header_buf).expect("failed to parse header");
-        assert!(rest.is_empty());
-
-        let mut first_page_data = vec![0; header.page_size - HEADER_SIZE];
-        file.read_exact(&mut first_page_data)?;
-        let (_, first_page) = Page::parse(
-            &first_page_data,
-            true,
-            &["type", "name", "tbl_name", "rootpage", "sql"],
-            &[0, 1, 2, 3, 4],
-            header.page_size - header.end_page_reserved_bytes,
-        )
-        .expect("failed to parse first page");
-
-        let mut objects = Vec::new();
-        for object_cell in first_page.cells.iter() {
-            let object_record = object_cell.as_record().unwrap();
-            let object = match object_record.values[0].as_text().unwrap() {
-                "table" => {
-                    let create_query_str = object_record.values[4].as_text().unwrap();
-                    let create_query = Query::parse(create_query_str)?;
-                    let column_names = create_query.as_create().unwrap().column_names.clone();
-
-                    ObjectSchema::Table(TableSchema {
-                        name: object_record.values[1].as_text().unwrap().to_owned(),
-                        root_page: object_record.values[3].as_integer().unwrap() as usize,
-                        sql: create_query_str.to_owned(),
-                        column_names,
-                    })
-                }
-                "index" => {
-                    let create_query_str = object_record.values[4].as_text().unwrap();
-                    let create_query = Query::parse(create_query_str)?;
-                    let column_name = create_query.as_create().unwrap().column_names[0].clone();
-
-                    ObjectSchema::Index(IndexSchema {
-                        name: object_record.values[1].as_text().unwrap().to_owned(),
-                        table_name: object_record.values[2].as_text().unwrap().to_owned(),
-                        root_page: object_record.values[3].as_integer().unwrap() as usize,
-                        sql: create_query_str.to_owned(),
-                        column_name,
-                    })
+#[derive(Debug)]
+pub struct TriggerSchema {
+    pub name: String,
+    pub table_name: String,
+    pub sql: String,
+    pub timing: TriggerTiming,
+    pub event: TriggerEvent,
+    pub for_each_row: bool,
+    pub when: Option<Expr>,
+}
+
+impl TableSchema {
+    pub fn column_names(&self) -> Vec<&str> {
+        self.columns.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    /// The index of the `INTEGER PRIMARY KEY` column, which is an alias for the rowid.
+    pub fn rowid_alias(&self) -> Option<usize> {
+        if self.without_rowid {
+            return None;
+        }
+        let is_integer = |i: &usize| self.columns[*i].type_name.eq_ignore_ascii_case("integer");
+        // `INTEGER PRIMARY KEY DESC` on the column itself is a quirk which isn't an alias
+        let column_primary_key = self
+            .columns
+            .iter()
+            .position(|c| c.primary_key.as_ref().is_some_and(|pk| !pk.descending));
+        let table_primary_key = self.constraints.iter().find_map(|c| match c {
+            TableConstraint::PrimaryKey(columns) if columns.len() == 1 => {
+                let name = columns[0].column_name()?;
+                self.columns
+                    .iter()
+                    .position(|c| c.name.eq_ignore_ascii_case(name))
+            }
+            _ => None,
+        });
+        column_primary_key.or(table_primary_key).filter(is_integer)
+    }
+
+    /// The indices of the primary key columns, in key order.
+    pub fn primary_key(&self) -> Vec<usize> {
+        if let Some(i) = self.columns.iter().position(|c| c.primary_key.is_some()) {
+            return vec![i];
+        }
+        self.constraints
+            .iter()
+            .find_map(|c| match c {
+                TableConstraint::PrimaryKey(columns) => Some(
+                    columns
+                        .iter()
+                        .filter_map(|c| c.column_name())
+                        .filter_map(|name| {
+                            self.columns
+                                .iter()
+                                .position(|c| c.name.eq_ignore_ascii_case(name))
+                        })
+                        .collect(),
+                ),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// The first column of a `PRIMARY KEY` table constraint.
+    fn primary_key_constraint_column(&self) -> Option<&IndexedColumn> {
+        self.constraints.iter().find_map(|c| match c {
+            TableConstraint::PrimaryKey(columns) => columns.first(),
+            _ => None,
+        })
+    }
+
+    /// Whether the table's B-tree is in ascending order of its first primary key column, so
+    /// can be searched by it.
+    pub fn primary_key_searchable(&self) -> bool {
+        let Some(first) = self.primary_key().first().map(|i| &self.columns[*i]) else {
+            return false;
+        };
+        first.primary_key.as_ref().map_or(true, |pk| !pk.descending)
+            && self
+                .primary_key_constraint_column()
+                .map_or(true, |c| !c.descending)
+    }
+
+    /// The collation the table's B-tree is ordered by, for its first primary key column.
+    pub fn primary_key_collation(&self) -> Option<String> {
+        let first = &self.columns[*self.primary_key().first()?];
+        self.primary_key_constraint_column()
+            .and_then(|c| c.collation.clone())
+            .or_else(|| first.collation.clone())
+    }
+
+    /// The columns of the indexes SQLite creates for the table's `PRIMARY KEY` and `UNIQUE`
+    /// constraints, in the order they're numbered in `sqlite_autoindex_<table>_<N>`. A
+    /// `WITHOUT ROWID` table's primary key is numbered, though it has no index of its own.
+    pub fn autoindex_columns(&self) -> Vec<Vec<IndexedColumn>> {
+        let indexed_column = |name: &str, descending: bool| IndexedColumn {
+            expr: Expr::Column {
+                table: None,
+                name: name.to_owned(),
+            },
+            collation: None,
+            descending,
+        };
+        let rowid_alias = self.rowid_alias();
+
+        let mut indexes = Vec::new();
+        for (i, column) in self.columns.iter().enumerate() {
+            if let Some(pk) = column
+                .primary_key
+                .as_ref()
+                .filter(|_| rowid_alias != Some(i))
+            {
+                indexes.push(vec![indexed_column(&column.name, pk.descending)]);
+            }
+            if column.unique {
+                indexes.push(vec![indexed_column(&column.name, false)]);
+            }
+        }
+        for constraint in self.constraints.iter() {
+            match constraint {
+                TableConstraint::PrimaryKey(_) if rowid_alias.is_some() => {}
+                TableConstraint::PrimaryKey(columns) | TableConstraint::Unique(columns) => {
+                    indexes.push(columns.clone())
                 }
-                _ => todo!("non-table/index object"),
-            };
+                _ => {}
+            }
+        }
 
-            objects.push(object);
+        // Constraints on the same columns as an earlier one share its index
+        let key = |columns: &[IndexedColumn]| {
+            columns
+                .iter()
+                .map(|c| c.column_name().map(|n| n.to_ascii_lowercase()))
+                .collect::<Vec<_>>()
+        };
+        let mut unique_indexes: Vec<Vec<IndexedColumn>> = Vec::new();
+        for index in indexes.into_iter() {
+            if !unique_indexes.iter().any(|u| key(u) == key(&index)) {
+                unique_indexes.push(index);
+            }
         }
+        unique_indexes
+    }
 
-        Ok(Database {
+    /// The index of the column stored at each position of the table's records. Virtual
+    /// generated columns aren't stored, and `WITHOUT ROWID` tables store their primary key
+    /// columns first.
+    pub fn storage_order(&self) -> Vec<usize> {
+        let is_stored = |i: &usize| {
+            self.columns[*i]
+                .generated
+                .as_ref()
+                .map_or(true, |g| g.stored)
+        };
+        let mut order = Vec::new();
+        if self.without_rowid {
+            order.extend(self.primary_key());
+        }
+        for i in (0..self.columns.len()).filter(is_stored) {
+            if !order.contains(&i) {
+                order.push(i);
+            }
+        }
+        order
+    }
+}
+
+impl Database {
+    /// The work done reading the database since it was opened.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            pages_read: self.pages_read.clone(),
+            cache_hits: self.page_cache.hits,
+            cache_misses: self.page_cache.misses,
+            rows_examined: self.rows_examined,
+            bytes_decoded: self.bytes_decoded,
+            overflow_pages_read: self.overflow_pages_read,
+        }
+    }
+
+    /// Open a database file, which is read through a memory map if `PRAGMA mmap_size` is set.
+    pub fn open(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path).map_err(|err| {
+            anyhow::Error::new(err)
+                .context(format!("unable to open database \"{}\"", path.display()))
+        })?;
+        Self::new(MmapPager::new(file))
+    }
+
+    /// Parse the database header, and the schema from the `sqlite_schema` table, whose B-tree
+    /// is rooted at page 1.
+    pub fn new(pager: impl Pager + 'static) -> anyhow::Result<Self> {
+        let mut pager: Box<dyn Pager> = Box::new(pager);
+        // A file too short to have a header isn't a database either
+        let header = match pager.read_page(1, HEADER_SIZE) {
+            Ok(header_buf) => match Header::parse(&header_buf) {
+                Ok((_, header)) => header,
+                Err(_) => return Err(CorruptError::NotADatabase.into()),
+            },
+            Err(err) if err.is::<CorruptError>() => return Err(CorruptError::NotADatabase.into()),
+            Err(err) => return Err(err),
+        };
+        if header.text_encoding != TextEncoding::Utf8 {
+            anyhow::bail!(
+                "{} databases aren't supported",
+                header.text_encoding.name().to_uppercase()
+            );
+        }
+
+        let cache_size = match header.default_page_cache_size {
+            0 => DEFAULT_CACHE_SIZE,
+            n => n as i64,
+        };
+        let page_cache = PageCache::new(cache_size, header.page_size);
+        let mut db = Database {
             header,
-            schema: Schema { objects },
-            table_pages_parsed: 1,
-            index_pages_parsed: 0,
-        })
+            schema: Schema {
+                objects: Vec::new(),
+            },
+            pages_read: BTreeMap::new(),
+            rows_examined: 0,
+            bytes_decoded: 0,
+            overflow_pages_read: 0,
+            page_cache,
+            pager,
+            collations: Collations::default(),
+        };
+
+        let schema_records = db.get_full_table(1, None, &[0, 1, 2, 3, 4])?;
+        for object_record in schema_records.iter() {
+            let object = db.schema.parse_object(object_record)?;
+            db.schema.objects.push(object);
+        }
+
+        Ok(db)
+    }
+
+    /// Read a page, from the page cache if it's there.
+    fn read_page(&mut self, page_index: usize) -> anyhow::Result<PageData<'_>> {
+        if page_index == 0 {
+            return Err(CorruptError::Malformed("page 0".to_owned()).into());
+        }
+        if let Some(page) = self.page_cache.get(page_index) {
+            return Ok(PageData::Cached(page));
+        }
+        // Pages the pager lends out are already in memory, so aren't cached
+        match self.pager.read_page(page_index, self.header.page_size)? {
+            Cow::Borrowed(page) => Ok(PageData::Lent(page)),
+            Cow::Owned(page) => Ok(PageData::Cached(self.page_cache.insert(page_index, page))),
+        }
+    }
+
+    /// The bytes of each page which hold content, rather than bytes reserved by extensions.
+    fn usable_page_size(&self) -> usize {
+        self.header.page_size - self.header.end_page_reserved_bytes
     }
 
-    pub fn get_full_table<R>(
+    /// Read and parse a page, from the page cache if it's there, and pass it to `f`. The
+    /// page's records borrow from the page buffer, so are only decoded as `f` needs them. Page
+    /// 1 starts with the database header, which is skipped.
+    fn with_page<T>(
         &mut self,
-        mut file: R,
         page_index: usize,
-        column_names: &[&str],
-        column_indices: &[usize],
-    ) -> anyhow::Result<Vec<Record>>
-    where
-        R: std::io::Read + std::io::Seek,
-    {
-        assert!(page_index > 1);
-
-        let mut records: Vec<Record> = Vec::new();
-        let mut pages_to_read: Vec<usize> = vec![page_index];
-        let mut page_buffer = vec![0; self.header.page_size];
-        while let Some(page_index) = pages_to_read.pop() {
-            file.seek(std::io::SeekFrom::Start(
-                (self.header.page_size * (page_index - 1)) as u64,
-            ))?;
-            file.read_exact(&mut page_buffer)?;
-            let page = Page::parse(
-                &page_buffer,
-                false,
-                column_names,
-                column_indices,
-                self.header.page_size - self.header.end_page_reserved_bytes,
-            )
-            .expect("failed to parse page")
-            .1;
-            self.table_pages_parsed += 1;
-
-            if let Some(rightmost_pointer) = page.rightmost_pointer {
-                pages_to_read.push(rightmost_pointer);
-            }
+        rowid_alias: Option<usize>,
+        f: impl FnOnce(Page<'_>) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let malformed = || CorruptError::Malformed(format!("page {}", page_index));
+        let usable_page_size = self.usable_page_size();
+        let is_first_page = page_index == 1;
+        let content_start = if is_first_page { HEADER_SIZE } else { 0 };
 
-            for cell in page.cells {
-                match cell {
-                    Cell::TableLeaf(record) => records.push(record),
-                    Cell::TableInterior {
-                        left_child_pointer, ..
-                    } => pages_to_read.push(left_child_pointer as usize),
-                    _ => unreachable!(),
-                }
+        // Records too big for the page continue on overflow pages, which are read first. The
+        // page can't stay borrowed from the pager while they are.
+        let page = self.read_page(page_index)?;
+        let (_, overflows) =
+            Page::overflows(&page[content_start..], is_first_page, usable_page_size)
+                .map_err(|_| malformed())?;
+        let overflows = overflows
+            .into_iter()
+            .map(|o| (o.local.to_vec(), o.payload_size, o.first_page))
+            .collect::<Vec<_>>();
+        let mut overflow_payloads = OverflowPayloads::new();
+        let page = if overflows.is_empty() {
+            page
+        } else {
+            let page = page.into_shared();
+            for (local, payload_size, first_page) in overflows {
+                let overflow = Overflow {
+                    local: &local,
+                    payload_size,
+                    first_page,
+                };
+                let payload = self.read_overflow(&overflow)?;
+                overflow_payloads.insert(first_page, payload);
             }
+            PageData::Cached(page)
+        };
+
+        let (_, page) = Page::parse(
+            &page[content_start..],
+            is_first_page,
+            rowid_alias,
+            usable_page_size,
+            &overflow_payloads,
+        )
+        .map_err(|_| malformed())?;
+        f(page)
+    }
+
+    /// Read the whole of a payload which continues on overflow pages.
+    fn read_overflow(&mut self, overflow: &Overflow) -> anyhow::Result<Vec<u8>> {
+        let content_size = self.usable_page_size() - 4;
+        let mut payload = Vec::with_capacity(overflow.payload_size);
+        payload.extend_from_slice(overflow.local);
+        let mut next_page = overflow.first_page as usize;
+        // Each overflow page starts with the number of the next one, then holds as much of
+        // the payload as fits
+        while payload.len() < overflow.payload_size {
+            let page = self.read_page(next_page)?;
+            let size = content_size.min(overflow.payload_size - payload.len());
+            let (Some(next), Some(content)) = (page.get(..4), page.get(4..4 + size)) else {
+                return Err(CorruptError::Malformed(format!("page {}", next_page)).into());
+            };
+            payload.extend_from_slice(content);
+            next_page = u32::from_be_bytes(next.try_into().unwrap()) as usize;
+            self.overflow_pages_read += 1;
         }
+        Ok(payload)
+    }
 
-        Ok(records)
+    /// A cursor over the records of a table B-tree (or the index B-tree of a `WITHOUT ROWID`
+    /// table), in key order.
+    pub fn cursor(
+        &mut self,
+        page_index: usize,
+        rowid_alias: Option<usize>,
+        column_indices: &[usize],
+    ) -> Cursor<'_> {
+        Cursor {
+            db: self,
+            root_page: page_index,
+            to_visit: vec![Visit::Page(page_index)],
+            visited: HashSet::new(),
+            rowid_alias,
+            column_indices: column_indices.to_vec(),
+            row_ids: None,
+            filter: None,
+        }
     }
 
-    pub fn search_index<R>(
+    pub fn get_full_table(
         &mut self,
-        mut file: R,
         page_index: usize,
-        key: Value,
-    ) -> anyhow::Result<Vec<i64>>
-    where
-        R: std::io::Read + std::io::Seek,
-    {
-        assert!(page_index > 1);
+        rowid_alias: Option<usize>,
+        column_indices: &[usize],
+    ) -> anyhow::Result<Vec<Record>> {
+        self.cursor(page_index, rowid_alias, column_indices)
+            .collect()
+    }
 
-        let column_name = self
+    /// Find the rowids of the entries in an index whose first column equals `key`, when
+    /// compared using the collation the index is ordered by.
+    pub fn search_index(
+        &mut self,
+        page_index: usize,
+        key: Value,
+        collation: &Collation,
+    ) -> anyhow::Result<Vec<i64>> {
+        let column_count = self
             .schema
             .objects
             .iter()
-            .find(|o| {
-                matches!(o, ObjectSchema::Index(_)) && o.as_index().unwrap().root_page == page_index
-            })
-            .map(|o| o.as_index().unwrap().column_name.clone())
+            .filter_map(|o| o.as_index())
+            .find(|i| i.root_page == page_index)
+            .map(|i| i.columns.len())
             .unwrap();
 
-        let mut row_ids = Vec::new();
-        let mut pages_to_read: Vec<usize> = vec![page_index];
-        let mut page_buffer = vec![0; self.header.page_size];
-        while let Some(page_index) = pages_to_read.pop() {
-            file.seek(std::io::SeekFrom::Start(
-                (self.header.page_size * (page_index - 1)) as u64,
-            ))?;
-            file.read_exact(&mut page_buffer)?;
-            let page = Page::parse(
-                &page_buffer,
-                false,
-                &[&column_name, "row_id"],
-                &[0, 1],
-                self.header.page_size - self.header.end_page_reserved_bytes,
-            )
-            .expect("failed to parse page")
-            .1;
-            self.index_pages_parsed += 1;
-
-            // TODO: Only parse `rightmost_pointer` page if the entries we're interested in
-            // will appear there
-
-            if let Some(rightmost_pointer) = page.rightmost_pointer {
-                pages_to_read.push(rightmost_pointer);
+        // The rowid comes after the indexed columns
+        let records = self.seek(page_index, &key, &[0, column_count], collation)?;
+        Ok(records
+            .iter()
+            .filter_map(|r| r.values.last().and_then(|v| v.as_integer()))
+            .collect())
+    }
+
+    /// Find the records in an index B-tree (an index, or a `WITHOUT ROWID` table) whose first
+    /// column equals `key`, only reading the pages they could be in. The B-tree's first column
+    /// must be ordered by `collation`.
+    pub fn seek(
+        &mut self,
+        page_index: usize,
+        key: &Value,
+        column_indices: &[usize],
+        collation: &Collation,
+    ) -> anyhow::Result<Vec<Record>> {
+        let root_page = page_index;
+        let mut records = Vec::new();
+        let mut to_visit = vec![Visit::Page(page_index)];
+        let mut visited = HashSet::new();
+        while let Some(visit) = to_visit.pop() {
+            let page_index = match visit {
+                Visit::Page(page_index) => page_index,
+                Visit::Record(record) => {
+                    records.push(record);
+                    continue;
+                }
+            };
+            if !visited.insert(page_index) {
+                return Err(CorruptError::Malformed(format!("page {}", page_index)).into());
             }
 
-            for cell in page.cells {
-                match cell {
-                    Cell::IndexLeaf(record) => {
-                        if record.values[0] == key {
-                            row_ids.push(record.values[1].as_integer().unwrap());
+            let mut bytes_decoded = 0;
+            let children = self.with_page(page_index, None, |page| {
+                // Each child page holds the entries between the keys of the cells either side
+                // of it, which may be equal to the key when it's repeated. Only the key column
+                // is decoded until a record matches.
+                let mut children = Vec::new();
+                let mut previous_ordering: Option<Ordering> = None;
+                let could_follow =
+                    |previous: Option<Ordering>| previous.map_or(true, |o| o.is_le());
+                for cell in page.cells {
+                    match cell {
+                        Cell::IndexLeaf(view) => {
+                            if collation.compare_ref(view.column(0), key).is_eq() {
+                                bytes_decoded += view.size_of(column_indices);
+                                children.push(Visit::Record(view.to_record(column_indices)));
+                            }
                         }
-                    }
-                    Cell::IndexInterior {
-                        left_child_pointer,
-                        record,
-                    } => {
-                        if record.values[0] >= key {
-                            pages_to_read.push(left_child_pointer as usize);
+                        Cell::IndexInterior {
+                            left_child_pointer,
+                            record: view,
+                        } => {
+                            let ordering = collation.compare_ref(view.column(0), key);
+                            if ordering.is_ge() && could_follow(previous_ordering) {
+                                children.push(Visit::Page(left_child_pointer as usize));
+                            }
+                            if ordering.is_eq() {
+                                bytes_decoded += view.size_of(column_indices);
+                                children.push(Visit::Record(view.to_record(column_indices)));
+                            }
+                            previous_ordering = Some(ordering);
                         }
-                        if record.values[0] == key {
-                            row_ids.push(record.values[1].as_integer().unwrap());
+                        // Only index B-trees can be searched
+                        _ => {
+                            return Err(
+                                CorruptError::Malformed(format!("page {}", page_index)).into()
+                            )
                         }
                     }
-                    _ => unreachable!(),
                 }
-            }
+                if could_follow(previous_ordering) {
+                    children.extend(page.rightmost_pointer.map(Visit::Page));
+                }
+                Ok(children)
+            })?;
+            *self.pages_read.entry(root_page).or_default() += 1;
+            self.rows_examined += children
+                .iter()
+                .filter(|c| matches!(c, Visit::Record(_)))
+                .count();
+            self.bytes_decoded += bytes_decoded;
+            to_visit.extend(children.into_iter().rev());
         }
 
-        Ok(row_ids)
+        Ok(records)
     }
 
-    pub fn get_by_row_ids<R>(
+    /// A cursor over the records of a table with the given rowids, which only reads the pages
+    /// they could be in.
+    pub fn cursor_by_row_ids(
         &mut self,
-        mut file: R,
         page_index: usize,
         row_ids: &[i64],
-        column_names: &[&str],
+        rowid_alias: Option<usize>,
         column_indices: &[usize],
-    ) -> anyhow::Result<Vec<Record>>
-    where
-        R: std::io::Read + std::io::Seek,
-    {
-        assert!(page_index > 1);
-
-        let mut records: Vec<Record> = Vec::new();
-        let mut pages_to_read: Vec<usize> = vec![page_index];
-        let mut page_buffer = vec![0; self.header.page_size];
-        while let Some(page_index) = pages_to_read.pop() {
-            file.seek(std::io::SeekFrom::Start(
-                (self.header.page_size * (page_index - 1)) as u64,
-            ))?;
-            file.read_exact(&mut page_buffer)?;
-            let page = Page::parse(
-                &page_buffer,
-                false,
-                column_names,
-                column_indices,
-                self.header.page_size - self.header.end_page_reserved_bytes,
-            )
-            .expect("failed to parse page")
-            .1;
-            self.table_pages_parsed += 1;
-
-            let id_column_index = column_names.iter().position(|c| *c == "id").unwrap();
-
-            // TODO: When to follow rightmost pointer?
-            if let Some(rightmost_pointer) = page.rightmost_pointer {
-                pages_to_read.push(rightmost_pointer);
+    ) -> Cursor<'_> {
+        let mut row_ids = row_ids.to_vec();
+        row_ids.sort_unstable();
+        let mut cursor = self.cursor(page_index, rowid_alias, column_indices);
+        cursor.row_ids = Some(row_ids);
+        cursor
+    }
+
+    pub fn get_by_row_ids(
+        &mut self,
+        page_index: usize,
+        row_ids: &[i64],
+        rowid_alias: Option<usize>,
+        column_indices: &[usize],
+    ) -> anyhow::Result<Vec<Record>> {
+        self.cursor_by_row_ids(page_index, row_ids, rowid_alias, column_indices)
+            .collect()
+    }
+}
+
+/// A page's bytes, which are either shared with the page cache or lent out by the pager.
+enum PageData<'a> {
+    Cached(Arc<[u8]>),
+    Lent(&'a [u8]),
+}
+
+impl PageData<'_> {
+    /// Share the page's bytes, copying them if they're lent out by the pager.
+    fn into_shared(self) -> Arc<[u8]> {
+        match self {
+            PageData::Cached(page) => page,
+            PageData::Lent(page) => page.into(),
+        }
+    }
+}
+
+impl Deref for PageData<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            PageData::Cached(page) => page,
+            PageData::Lent(page) => page,
+        }
+    }
+}
+
+/// A page or record still to be visited in an in-order traversal of a B-tree.
+enum Visit {
+    Page(usize),
+    Record(Record),
+}
+
+/// An iterator over the records of a B-tree, which reads each page when it's first needed.
+pub struct Cursor<'a> {
+    db: &'a mut Database,
+    root_page: usize,
+    /// The pages and records still to visit, with the next on top.
+    to_visit: Vec<Visit>,
+    /// The pages visited so far. A page which is its own descendant would otherwise be
+    /// visited forever.
+    visited: HashSet<usize>,
+    rowid_alias: Option<usize>,
+    column_indices: Vec<usize>,
+    /// If set, the sorted rowids of the only records to return.
+    row_ids: Option<Vec<i64>>,
+    /// If set, records are only returned if this returns true for them.
+    filter: Option<RecordFilter<'a>>,
+}
+
+/// A test of a record, made before its values are decoded.
+pub type RecordFilter<'a> = Box<dyn Fn(&RecordView) -> anyhow::Result<bool> + Send + 'a>;
+
+impl<'a> Cursor<'a> {
+    /// Only return records which pass `filter`, which is given each record before any of its
+    /// values are decoded.
+    pub fn with_filter(mut self, filter: RecordFilter<'a>) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Read a page, and queue up its records and the child pages which need visiting.
+    fn visit_page(&mut self, page_index: usize) -> anyhow::Result<()> {
+        if !self.visited.insert(page_index) {
+            return Err(CorruptError::Malformed(format!("page {}", page_index)).into());
+        }
+        let (row_ids, filter, column_indices) = (&self.row_ids, &self.filter, &self.column_indices);
+
+        // Is there a wanted rowid in the range (after, up_to]?
+        let wanted = |after: Option<i64>, up_to: Option<i64>| match row_ids {
+            Some(row_ids) => {
+                let start = after.map_or(0, |a| row_ids.partition_point(|id| *id <= a));
+                row_ids
+                    .get(start)
+                    .is_some_and(|id| up_to.map_or(true, |u| *id <= u))
             }
+            None => true,
+        };
+        // Records are only decoded once they've passed the filter
+        let (mut rows_examined, mut bytes_decoded) = (0, 0);
+        let mut record = |view: RecordView| -> anyhow::Result<Option<Visit>> {
+            rows_examined += 1;
+            if let Some(filter) = filter {
+                if !filter(&view)? {
+                    return Ok(None);
+                }
+            }
+            bytes_decoded += view.size_of(column_indices);
+            Ok(Some(Visit::Record(view.to_record(column_indices))))
+        };
 
+        let children = self.db.with_page(page_index, self.rowid_alias, |page| {
+            // `WITHOUT ROWID` tables are index B-trees, whose interior cells hold records too
+            let mut children = Vec::new();
+            let mut previous_key = None;
             for cell in page.cells {
                 match cell {
-                    Cell::TableLeaf(record) => {
-                        if row_ids.contains(&record.values[id_column_index].as_integer().unwrap()) {
-                            records.push(record)
+                    Cell::TableLeaf(view) => {
+                        let row_id = view.row_id.unwrap();
+                        if row_ids
+                            .as_ref()
+                            .map_or(true, |ids| ids.binary_search(&row_id).is_ok())
+                        {
+                            children.extend(record(view)?);
                         }
                     }
+                    Cell::IndexLeaf(view) => children.extend(record(view)?),
                     Cell::TableInterior {
                         left_child_pointer,
                         key,
                     } => {
-                        if row_ids.iter().any(|id| *id < key) {
-                            pages_to_read.push(left_child_pointer as usize)
+                        if wanted(previous_key, Some(key)) {
+                            children.push(Visit::Page(left_child_pointer as usize));
                         }
+                        previous_key = Some(key);
+                    }
+                    Cell::IndexInterior {
+                        left_child_pointer,
+                        record: view,
+                    } => {
+                        children.push(Visit::Page(left_child_pointer as usize));
+                        children.extend(record(view)?);
                     }
-                    _ => unreachable!(),
                 }
             }
-        }
+            if let Some(rightmost) = page.rightmost_pointer {
+                if wanted(previous_key, None) {
+                    children.push(Visit::Page(rightmost));
+                }
+            }
+            Ok(children)
+        })?;
+        *self.db.pages_read.entry(self.root_page).or_default() += 1;
+        self.db.rows_examined += rows_examined;
+        self.db.bytes_decoded += bytes_decoded;
 
-        Ok(records)
+        // Visit children left to right, so records come out in key order
+        self.to_visit.extend(children.into_iter().rev());
+        Ok(())
+    }
+}
+
+impl Iterator for Cursor<'_> {
+    type Item = anyhow::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.to_visit.pop()? {
+                Visit::Record(record) => return Some(Ok(record)),
+                Visit::Page(page_index) => {
+                    if let Err(e) = self.visit_page(page_index) {
+                        self.to_visit.clear();
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
     }
 }
 
 impl Schema {
+    /// Parse a row of `sqlite_schema`. Auto-indexes are described using the table's schema,
+    /// which comes before them.
+    fn parse_object(&self, record: &Record) -> anyhow::Result<ObjectSchema> {
+        let text = |i: usize| record.values.get(i).and_then(|v| v.as_text());
+        let (Some(object_type), Some(name), Some(table_name)) = (text(0), text(1), text(2)) else {
+            return Err(CorruptError::MalformedSchema {
+                name: text(1).unwrap_or("?").to_owned(),
+                reason: None,
+            }
+            .into());
+        };
+        let root_page = record
+            .values
+            .get(3)
+            .and_then(|v| v.as_integer())
+            .unwrap_or(0) as usize;
+        let malformed = || CorruptError::MalformedSchema {
+            name: name.to_owned(),
+            reason: None,
+        };
+
+        let Some(sql) = text(4) else {
+            // Only auto-indexes have no SQL
+            let number = name
+                .strip_prefix("sqlite_autoindex_")
+                .and_then(|n| n.rsplit_once('_'))
+                .and_then(|(_, n)| n.parse::<usize>().ok())
+                .filter(|_| object_type == "index")
+                .ok_or_else(malformed)?;
+            let columns = self
+                .table(table_name)
+                .and_then(|t| {
+                    t.autoindex_columns()
+                        .into_iter()
+                        .nth(number.wrapping_sub(1))
+                })
+                .ok_or_else(malformed)?;
+            return Ok(ObjectSchema::Index(IndexSchema {
+                name: name.to_owned(),
+                table_name: table_name.to_owned(),
+                root_page,
+                sql: None,
+                unique: true,
+                columns,
+                filter: None,
+            }));
+        };
+
+        let query = Query::parse(sql).map_err(|e| CorruptError::MalformedSchema {
+            name: name.to_owned(),
+            reason: Some(e.to_string()),
+        })?;
+        let object = match (object_type, query) {
+            ("table", Query::CreateTable(create)) => ObjectSchema::Table(TableSchema {
+                name: create.name,
+                root_page,
+                sql: sql.to_owned(),
+                columns: create.columns,
+                constraints: create.constraints,
+                without_rowid: create.without_rowid,
+            }),
+            ("index", Query::CreateIndex(create)) => ObjectSchema::Index(IndexSchema {
+                name: create.name,
+                table_name: create.table_name,
+                root_page,
+                sql: Some(sql.to_owned()),
+                unique: create.unique,
+                columns: create.columns,
+                filter: create.filter,
+            }),
+            ("view", Query::CreateView(create)) => ObjectSchema::View(ViewSchema {
+                name: create.name,
+                sql: sql.to_owned(),
+                columns: create.columns,
+                select: create.select,
+            }),
+            ("trigger", Query::CreateTrigger(create)) => ObjectSchema::Trigger(TriggerSchema {
+                name: create.name,
+                table_name: create.table_name,
+                sql: sql.to_owned(),
+                timing: create.timing,
+                event: create.event,
+                for_each_row: create.for_each_row,
+                when: create.when,
+            }),
+            _ => return Err(malformed().into()),
+        };
+        Ok(object)
+    }
+
     pub fn table_count(&self) -> usize {
         self.objects
             .iter()
@@ -318,27 +938,348 @@ impl Schema {
             .count()
     }
 
+    /// The number of indexes, including automatic indexes.
+    pub fn index_count(&self) -> usize {
+        self.objects
+            .iter()
+            .filter(|o| matches!(o, ObjectSchema::Index(_)))
+            .count()
+    }
+
+    pub fn trigger_count(&self) -> usize {
+        self.objects
+            .iter()
+            .filter(|o| matches!(o, ObjectSchema::Trigger(_)))
+            .count()
+    }
+
+    pub fn view_count(&self) -> usize {
+        self.objects
+            .iter()
+            .filter(|o| matches!(o, ObjectSchema::View(_)))
+            .count()
+    }
+
+    /// The total length in characters of the statements which created the objects.
+    pub fn sql_size(&self) -> usize {
+        self.objects
+            .iter()
+            .filter_map(|o| o.sql())
+            .map(|sql| sql.chars().count())
+            .sum()
+    }
+
+    /// The names of the tables and views, as listed by `.tables`.
     pub fn table_names(&self) -> Vec<&str> {
         let mut tables = self
             .objects
             .iter()
-            .filter(|o| matches!(o, ObjectSchema::Table(_)))
-            .map(|o| o.as_table().unwrap().name.as_str())
+            .filter_map(|o| match o {
+                ObjectSchema::Table(table) => Some(table.name.as_str()),
+                ObjectSchema::View(view) => Some(view.name.as_str()),
+                _ => None,
+            })
             .collect::<Vec<&str>>();
         tables.sort();
         tables
     }
 
-    pub fn table_root_page(&self, table_name: &str) -> anyhow::Result<usize> {
-        Ok(self
-            .objects
+    /// The objects on tables matching a pattern, as listed by `.schema`. The pattern is a
+    /// `GLOB` pattern against the lowercased table name if it has any `GLOB` wildcards, and a
+    /// `LIKE` pattern otherwise.
+    pub fn objects_on_tables(&self, pattern: &str) -> Vec<&ObjectSchema> {
+        let is_glob = pattern.contains(['*', '?', '[']);
+        self.objects
             .iter()
-            .find(|o| {
-                matches!(o, ObjectSchema::Table(_)) && o.as_table().unwrap().name == table_name
+            .filter(|o| {
+                let table_name = o.table_name().to_lowercase();
+                if is_glob {
+                    glob(pattern, &table_name)
+                } else {
+                    like(pattern, &table_name)
+                }
             })
-            .expect("table not found")
-            .as_table()
+            .collect()
+    }
+
+    /// The objects whose own name or whose table's name matches a `LIKE` pattern, as
+    /// `.dump` selects them.
+    pub fn objects_like(&self, pattern: &str) -> Vec<&ObjectSchema> {
+        self.objects
+            .iter()
+            .filter(|o| like(pattern, o.name()) || like(pattern, o.table_name()))
+            .collect()
+    }
+
+    /// The names of the indexes on tables whose names match a `LIKE` pattern, or on every
+    /// table, as listed by `.indexes`.
+    pub fn index_names(&self, table_pattern: Option<&str>) -> Vec<&str> {
+        let mut indexes = self
+            .objects
+            .iter()
+            .filter_map(|o| o.as_index())
+            .filter(|i| table_pattern.map_or(true, |pattern| like(pattern, &i.table_name)))
+            .map(|i| i.name.as_str())
+            .collect::<Vec<&str>>();
+        indexes.sort();
+        indexes
+    }
+
+    /// The name of the table or index whose B-tree is rooted at a page. Page 1 holds
+    /// `sqlite_schema`.
+    pub fn btree_name(&self, root_page: usize) -> Option<&str> {
+        if root_page == 1 {
+            return Some("sqlite_schema");
+        }
+        self.objects.iter().find_map(|o| match o {
+            ObjectSchema::Table(table) if table.root_page == root_page => Some(table.name.as_str()),
+            ObjectSchema::Index(index) if index.root_page == root_page => Some(index.name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Find a table by name, ignoring case as SQLite does.
+    pub fn table(&self, table_name: &str) -> Option<&TableSchema> {
+        self.objects
+            .iter()
+            .filter_map(|o| o.as_table())
+            .find(|t| t.name.eq_ignore_ascii_case(table_name))
+    }
+
+    /// Find a view by name, ignoring case.
+    pub fn view(&self, view_name: &str) -> Option<&ViewSchema> {
+        self.objects
+            .iter()
+            .filter_map(|o| o.as_view())
+            .find(|v| v.name.eq_ignore_ascii_case(view_name))
+    }
+
+    /// Find an index on the given column of a table, ordered by the given collation, for a query
+    /// whose filter is the `AND` of `terms`. Partial indexes are only used if the query's
+    /// filter implies theirs, as they're missing the other rows.
+    pub fn index_on(
+        &self,
+        table_name: &str,
+        column_name: &str,
+        collation: Option<&str>,
+        terms: &[&Expr],
+    ) -> Option<&IndexSchema> {
+        // Index columns use the table column's collation unless they give their own
+        let column_collation = self.table(table_name).and_then(|t| {
+            t.columns
+                .iter()
+                .find(|c| c.name.eq_ignore_ascii_case(column_name))
+                .and_then(|c| c.collation.as_deref())
+        });
+        self.objects.iter().filter_map(|o| o.as_index()).find(|i| {
+            // Only indexes in ascending order can be searched
+            let first = &i.columns[0];
+            i.table_name.eq_ignore_ascii_case(table_name)
+                && first
+                    .column_name()
+                    .is_some_and(|c| c.eq_ignore_ascii_case(column_name))
+                && same_collation(first.collation.as_deref().or(column_collation), collation)
+                && !first.descending
+                && i.filter
+                    .as_ref()
+                    .map_or(true, |filter| implies(terms, filter))
+        })
+    }
+}
+
+/// Does a filter made of the `AND` of `terms` imply `filter`? It does if each of `filter`'s
+/// terms is one of them, whatever table its columns are qualified with.
+fn implies(terms: &[&Expr], filter: &Expr) -> bool {
+    let unqualified = |expr: &Expr| {
+        expr.replace(&mut |e| match e {
+            Expr::Column { name, .. } => Some(Expr::Column {
+                table: None,
+                name: name.to_ascii_lowercase(),
+            }),
+            _ => None,
+        })
+    };
+    let terms = terms.iter().map(|t| unqualified(t)).collect::<Vec<_>>();
+    conjuncts(filter)
+        .into_iter()
+        .all(|term| terms.contains(&unqualified(term)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Database, TableSchema};
+    use crate::{
+        collation::Collation, error::CorruptError, pager::MemoryPager, query::Query, record::Value,
+    };
+
+    fn table(sql: &str) -> TableSchema {
+        let query = Query::parse(sql).unwrap();
+        let create = query.as_create_table().unwrap();
+        TableSchema {
+            name: create.name.clone(),
+            root_page: 2,
+            sql: sql.to_owned(),
+            columns: create.columns.clone(),
+            constraints: create.constraints.clone(),
+            without_rowid: create.without_rowid,
+        }
+    }
+
+    #[test]
+    fn rowid_alias() {
+        assert_eq!(
+            table("CREATE TABLE t (a, id INTEGER PRIMARY KEY)").rowid_alias(),
+            Some(1)
+        );
+        assert_eq!(
+            table("CREATE TABLE t (a, id INTEGER, PRIMARY KEY (id))").rowid_alias(),
+            Some(1)
+        );
+        assert_eq!(
+            table("CREATE TABLE t (id INT PRIMARY KEY)").rowid_alias(),
+            None
+        );
+        assert_eq!(
+            table("CREATE TABLE t (id INTEGER PRIMARY KEY DESC)").rowid_alias(),
+            None
+        );
+        assert_eq!(
+            table("CREATE TABLE t (id INTEGER PRIMARY KEY) WITHOUT ROWID").rowid_alias(),
+            None
+        );
+    }
+
+    #[test]
+    fn storage_order() {
+        let t = table("CREATE TABLE t (a, b AS (a + 1), c, d AS (a * 2) STORED)");
+        assert_eq!(t.storage_order(), vec![0, 2, 3]);
+        let t = table("CREATE TABLE t (a, b, c, PRIMARY KEY (c, a)) WITHOUT ROWID");
+        assert_eq!(t.primary_key(), vec![2, 0]);
+        assert_eq!(t.storage_order(), vec![2, 0, 1]);
+        assert!(t.primary_key_searchable());
+    }
+
+    #[test]
+    fn autoindex_columns() {
+        let names = |sql: &str| {
+            table(sql)
+                .autoindex_columns()
+                .iter()
+                .map(|columns| {
+                    columns
+                        .iter()
+                        .map(|c| c.column_name().unwrap().to_owned())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names("CREATE TABLE t (a PRIMARY KEY, b UNIQUE, c, UNIQUE (c, b), UNIQUE (b))"),
+            vec![vec!["a"], vec!["b"], vec!["c", "b"]]
+        );
+        assert_eq!(
+            names("CREATE TABLE t (id INTEGER PRIMARY KEY, b UNIQUE)"),
+            vec![vec!["b"]]
+        );
+        assert_eq!(
+            names("CREATE TABLE t (id INTEGER PRIMARY KEY DESC, b)"),
+            vec![vec!["id"]]
+        );
+    }
+    #[test]
+    fn objects_on_tables() {
+        let db = Database::open(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        let names = |pattern: &str| {
+            db.schema
+                .objects_on_tables(pattern)
+                .iter()
+                .map(|o| o.name().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names("APPLES"), vec!["apples"]);
+        assert_eq!(names("%es"), vec!["apples", "oranges"]);
+        assert_eq!(names("[ao]*"), vec!["apples", "oranges"]);
+        assert!(names("A*").is_empty());
+        assert_eq!(db.schema.objects_like("%SEQ%")[0].name(), "sqlite_sequence");
+        assert!(db.schema.objects[1].is_internal());
+        assert!(db.schema.index_names(None).is_empty());
+    }
+
+    #[test]
+    fn multi_page_schema() {
+        let db = Database::open(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/fixtures/large_schema.db"
+        ))
+        .unwrap();
+        // Every page of sqlite_schema's B-tree is read: its root and the leaves below it
+        assert_eq!(db.stats().pages_read[&1], 5);
+        assert_eq!(db.schema.table_count(), 20);
+        assert_eq!(db.schema.table_names().first(), Some(&"t01"));
+        assert_eq!(db.schema.index_names(Some("t20")), vec!["t20_name"]);
+        assert!(db.schema.table("t20").unwrap().root_page > 1);
+    }
+
+    #[test]
+    fn corrupt_databases() {
+        let corruption = |data: Vec<u8>| {
+            let err = Database::new(MemoryPager::new(data)).err().unwrap();
+            err.downcast::<CorruptError>().unwrap()
+        };
+        assert!(matches!(
+            corruption(b"not a database".repeat(100)),
+            CorruptError::NotADatabase
+        ));
+
+        // The schema's page is there, but the table it points to isn't
+        let sample = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        let mut db = Database::new(MemoryPager::new(sample[..4096].to_vec())).unwrap();
+        let root_page = db.schema.table("apples").unwrap().root_page;
+        let err = db.with_page(root_page, None, |_| Ok(())).err().unwrap();
+        assert!(matches!(
+            err.downcast::<CorruptError>().unwrap(),
+            CorruptError::Malformed(_)
+        ));
+
+        let fixture = |name: &str| {
+            std::fs::read(format!(
+                "{}/fixtures/{}.db",
+                env!("CARGO_MANIFEST_DIR"),
+                name
+            ))
             .unwrap()
-            .root_page)
+        };
+        let replace = |data: &mut Vec<u8>, from: &[u8], to: &[u8]| {
+            let start = data.windows(from.len()).position(|w| w == from).unwrap();
+            data[start..start + to.len()].copy_from_slice(to);
+        };
+
+        // SQL in the schema which doesn't parse
+        let mut sample = sample.clone();
+        replace(&mut sample, b"CREATE TABLE apples", b"CREATE TABLX apples");
+        assert!(matches!(
+            corruption(sample),
+            CorruptError::MalformedSchema { name, reason: Some(_) } if name == "apples"
+        ));
+
+        // An interior page of the schema which is its own rightmost child
+        let mut large_schema = fixture("large_schema");
+        large_schema[108..112].copy_from_slice(&1u32.to_be_bytes());
+        assert!(matches!(
+            corruption(large_schema),
+            CorruptError::Malformed(page) if page == "page 1"
+        ));
+
+        // An index whose root page is the table's
+        let mut partial_index = fixture("partial_index");
+        replace(&mut partial_index, b"indexipt\x03", b"indexipt\x02");
+        let mut db = Database::new(MemoryPager::new(partial_index)).unwrap();
+        let err = db
+            .search_index(2, Value::Integer(5), &Collation::Binary)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<CorruptError>().unwrap(),
+            CorruptError::Malformed(_)
+        ));
     }
 }
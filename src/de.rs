@@ -0,0 +1,332 @@
+use serde::de::{
+    self,
+    value::{SeqDeserializer, StrDeserializer},
+    DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+};
+
+use crate::record::Value;
+
+/// An error deserializing a row.
+#[derive(Debug)]
+pub struct Error(String);
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+/// Deserializes a row: structs and maps by column name, and tuples and sequences by column
+/// position.
+pub struct RowDeserializer<'a> {
+    pub column_names: &'a [String],
+    pub values: &'a [Value],
+}
+
+impl<'de> Deserializer<'de> for RowDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_map(Columns {
+            columns: self.column_names.iter().zip(self.values.iter()),
+            value: None,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(Values(self.values.iter()))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        if len != self.values.len() {
+            return Err(de::Error::invalid_length(self.values.len(), &visitor));
+        }
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct enum identifier ignored_any
+    }
+}
+
+/// The columns of a row, as a map from column name to value.
+struct Columns<'de, I> {
+    columns: I,
+    /// The value of the column whose name was last returned.
+    value: Option<&'de Value>,
+}
+
+impl<'de, I> MapAccess<'de> for Columns<'de, I>
+where
+    I: Iterator<Item = (&'de String, &'de Value)>,
+{
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let Some((name, value)) = self.columns.next() else {
+            return Ok(None);
+        };
+        self.value = Some(value);
+        let name: StrDeserializer<Error> = name.as_str().into_deserializer();
+        seed.deserialize(name).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| <Error as de::Error>::custom("value requested before key"))?;
+        seed.deserialize(ValueDeserializer(value))
+    }
+}
+
+/// The values of a row, in column order.
+struct Values<I>(I);
+
+impl<'de, I> SeqAccess<'de> for Values<I>
+where
+    I: Iterator<Item = &'de Value>,
+{
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        self.0
+            .next()
+            .map(|value| seed.deserialize(ValueDeserializer(value)))
+            .transpose()
+    }
+}
+
+/// Deserializes a single value: NULL as `None` or `()`, and each other type as the matching
+/// Rust type. Text and blobs can be borrowed from the row.
+pub struct ValueDeserializer<'a>(pub &'a Value);
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::Null => visitor.visit_unit(),
+            Value::Integer(n) => visitor.visit_i64(*n),
+            Value::Real(f) => visitor.visit_f64(*f),
+            Value::Text(s) => visitor.visit_borrowed_str(s),
+            Value::Blob(s) => visitor.visit_borrowed_bytes(s),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    /// Booleans are stored as the integers 0 and 1.
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::Integer(n) => visitor.visit_bool(*n != 0),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    /// Integers can be read as reals, as they can in SQL.
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::Integer(n) => visitor.visit_f64(*n as f64),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_f64(visitor)
+    }
+
+    /// Blobs can be read as sequences of bytes, such as `Vec<u8>`.
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::Blob(b) => visitor.visit_seq(SeqDeserializer::new(b.iter().copied())),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::Blob(b) => visitor.visit_byte_buf(b.clone()),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    /// Text can be read as a unit variant of an enum, by its name.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.0 {
+            Value::Text(s) => {
+                let variant: StrDeserializer<Error> = s.as_str().into_deserializer();
+                visitor.visit_enum(variant)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 char str string bytes unit unit_struct tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use crate::Connection;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Apple<'a> {
+        id: i64,
+        name: String,
+        color: &'a str,
+        ripe: Option<bool>,
+        weight: f64,
+    }
+
+    #[test]
+    fn deserialize_rows() {
+        let mut conn = Connection::open(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        let mut statement = conn
+            .prepare("SELECT color, NULL AS ripe, id * 2 AS weight, name, id FROM apples LIMIT 1")
+            .unwrap();
+        let row = statement.query(&[]).unwrap().next().unwrap().unwrap();
+        assert_eq!(
+            row.deserialize::<Apple>().unwrap(),
+            Apple {
+                id: 1,
+                name: "Granny Smith".into(),
+                color: "Light Green",
+                ripe: None,
+                weight: 2.0,
+            }
+        );
+        assert!(row.deserialize::<(String, i64)>().is_err());
+
+        let mut statement = conn
+            .prepare("SELECT id, name FROM apples WHERE id < 3")
+            .unwrap();
+        let apples = statement
+            .query(&[])
+            .unwrap()
+            .deserialize::<(i64, String)>()
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(apples, vec![(1, "Granny Smith".into()), (2, "Fuji".into())]);
+
+        // Columns without an alias are named by their source text
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Scaled {
+            id: i64,
+            #[serde(rename = "id * 10")]
+            scaled: i64,
+            #[serde(rename = "upper(name)")]
+            upper: String,
+        }
+        let mut statement = conn
+            .prepare("SELECT id, id * 10, upper(name) FROM apples WHERE id = 2")
+            .unwrap();
+        let row = statement.query(&[]).unwrap().next().unwrap().unwrap();
+        assert_eq!(
+            row.deserialize::<Scaled>().unwrap(),
+            Scaled {
+                id: 2,
+                scaled: 20,
+                upper: "FUJI".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_blobs_and_enums() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum Size {
+            Small,
+            Large,
+        }
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Thing {
+            data: Vec<u8>,
+            size: Size,
+        }
+
+        let mut conn = Connection::open(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        let mut statement = conn
+            .prepare("SELECT x'01ff' AS data, 'Large' AS size")
+            .unwrap();
+        let row = statement.query(&[]).unwrap().next().unwrap().unwrap();
+        assert_eq!(
+            row.deserialize::<Thing>().unwrap(),
+            Thing {
+                data: vec![1, 255],
+                size: Size::Large,
+            }
+        );
+
+        let mut statement = conn.prepare("SELECT 'Medium' AS size").unwrap();
+        let row = statement.query(&[]).unwrap().next().unwrap().unwrap();
+        assert!(row.deserialize::<(Size,)>().is_err());
+    }
+}
@@ -25,3 +25,23 @@ impl std::fmt::Display for InvalidValueError {
         write!(f, "{:?}", self)
     }
 }
+
+/// The error for a file which isn't an SQLite database, or whose pages don't make sense.
+#[derive(Debug, Error)]
+pub enum CorruptError {
+    #[error("file is not a database")]
+    NotADatabase,
+    /// A page which couldn't be read or parsed, with a description of where.
+    #[error("database disk image is malformed ({0})")]
+    Malformed(String),
+    /// A row of `sqlite_schema` which doesn't describe an object, with the object's name ("?" if
+    /// it has none) and what's wrong with its SQL, if that's the problem.
+    #[error(
+        "malformed database schema ({name}){}",
+        .reason.as_ref().map(|r| format!(" - {}", r)).unwrap_or_default()
+    )]
+    MalformedSchema {
+        name: String,
+        reason: Option<String>,
+    },
+}
@@ -0,0 +1,1023 @@
+use crate::{
+    affinity::{cast, Affinity},
+    collation::{Collation, Collations},
+    function, json,
+    record::Value,
+    window::{self, Window},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    /// A parameter, numbered from 1, along with its name if it's named. Parameters are NULL
+    /// unless a value is bound to them.
+    Parameter {
+        index: usize,
+        name: Option<String>,
+    },
+    Column {
+        table: Option<String>,
+        name: String,
+    },
+    Unary {
+        op: UnaryOperator,
+        expr: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOperator,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    /// A function call. `count(*)` is represented with no arguments.
+    Function {
+        name: String,
+        args: Vec<Expr>,
+        distinct: bool,
+    },
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        negated: bool,
+    },
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+    /// `CASE [operand] WHEN ... THEN ... [ELSE ...] END`. With an operand, each `WHEN`
+    /// expression is compared against it; without, each is evaluated as a condition.
+    Case {
+        operand: Option<Box<Expr>>,
+        branches: Vec<(Expr, Expr)>,
+        else_expr: Option<Box<Expr>>,
+    },
+    Cast {
+        expr: Box<Expr>,
+        /// The type name, as written.
+        type_name: String,
+    },
+    /// `expr COLLATE name`, which sets the collation used when the expression is compared or
+    /// sorted.
+    Collate {
+        expr: Box<Expr>,
+        collation: String,
+    },
+    /// A window function call, `function(...) OVER (...)`. The function is always an
+    /// `Expr::Function`.
+    Window {
+        function: Box<Expr>,
+        window: Box<Window>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOperator {
+    Negate,
+    Plus,
+    Not,
+    BitNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOperator {
+    Or,
+    And,
+    Equals,
+    NotEquals,
+    Is,
+    IsNot,
+    LessThan,
+    LessThanOrEquals,
+    GreaterThan,
+    GreaterThanOrEquals,
+    Like,
+    Glob,
+    BitAnd,
+    BitOr,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Concat,
+    /// The JSON `->` operator.
+    Extract,
+    /// The JSON `->>` operator.
+    ExtractValue,
+}
+
+/// The columns visible to an expression, e.g. the columns of all the tables in a `FROM` clause.
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+    pub columns: Vec<ScopeColumn>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScopeColumn {
+    /// The table name or alias this column belongs to.
+    pub table: Option<String>,
+    pub name: String,
+    pub affinity: Affinity,
+    /// The column's declared collation, if any.
+    pub collation: Option<String>,
+    /// Hidden columns are only included in results when named explicitly.
+    pub hidden: bool,
+}
+
+impl Scope {
+    pub fn new<S: AsRef<str>>(table: Option<&str>, names: &[S]) -> Self {
+        Scope {
+            columns: names
+                .iter()
+                .map(|name| ScopeColumn {
+                    table: table.map(|t| t.to_owned()),
+                    name: name.as_ref().to_owned(),
+                    affinity: Affinity::Blob,
+                    collation: None,
+                    hidden: false,
+                })
+                .collect(),
+        }
+    }
+
+    /// Find the index of a column, optionally qualified by a table name.
+    pub fn resolve(&self, table: Option<&str>, name: &str) -> anyhow::Result<usize> {
+        self.columns
+            .iter()
+            .position(|c| {
+                c.name.eq_ignore_ascii_case(name)
+                    && match table {
+                        Some(table) => c
+                            .table
+                            .as_deref()
+                            .is_some_and(|t| t.eq_ignore_ascii_case(table)),
+                        None => true,
+                    }
+            })
+            .ok_or_else(|| match table {
+                Some(table) => anyhow::format_err!("no such column: {}.{}", table, name),
+                None => anyhow::format_err!("no such column: {}", name),
+            })
+    }
+
+    pub fn extend(&mut self, other: &Scope) {
+        self.columns.extend(other.columns.iter().cloned());
+    }
+}
+
+/// The row an expression is evaluated against, plus the rows of its group when evaluating an
+/// aggregate query.
+pub struct EvalContext<'a> {
+    pub scope: &'a Scope,
+    pub row: &'a [Value],
+    pub group: Option<&'a [Vec<Value>]>,
+    pub collations: &'a Collations,
+}
+
+impl<'a> EvalContext<'a> {
+    pub fn new(scope: &'a Scope, row: &'a [Value], collations: &'a Collations) -> Self {
+        EvalContext {
+            scope,
+            row,
+            group: None,
+            collations,
+        }
+    }
+
+    /// The collation used to compare the operands of a comparison.
+    fn comparison_collation(&self, left: &Expr, right: &Expr) -> anyhow::Result<Collation> {
+        let name = comparison_collation(left, right, self.scope);
+        self.collations.resolve(name.as_deref())
+    }
+
+    /// The collation of each argument of an aggregate function.
+    pub fn argument_collations(&self, args: &[Expr]) -> anyhow::Result<Vec<Collation>> {
+        args.iter()
+            .map(|a| self.collations.resolve(a.collation(self.scope).as_deref()))
+            .collect()
+    }
+}
+
+impl Expr {
+    pub fn eval(&self, ctx: &EvalContext) -> anyhow::Result<Value> {
+        match self {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Parameter { .. } => Ok(Value::Null),
+            Expr::Column { table, name } => {
+                let index = ctx.scope.resolve(table.as_deref(), name)?;
+                Ok(ctx.row[index].clone())
+            }
+            Expr::Unary { op, expr } => {
+                let value = expr.eval(ctx)?;
+                Ok(match op {
+                    UnaryOperator::Plus => value,
+                    UnaryOperator::Negate => match to_numeric(&value) {
+                        Value::Integer(n) => n
+                            .checked_neg()
+                            .map(Value::Integer)
+                            .unwrap_or(Value::Real(-(n as f64))),
+                        Value::Real(f) => Value::Real(-f),
+                        _ => Value::Null,
+                    },
+                    UnaryOperator::Not => match truthiness(&value) {
+                        Some(b) => Value::Integer(!b as i64),
+                        None => Value::Null,
+                    },
+                    UnaryOperator::BitNot => match to_integer(&value) {
+                        Some(n) => Value::Integer(!n),
+                        None => Value::Null,
+                    },
+                })
+            }
+            Expr::Binary { op, left, right } => {
+                match op {
+                    BinaryOperator::And => {
+                        let left = truthiness(&left.eval(ctx)?);
+                        if left == Some(false) {
+                            return Ok(Value::Integer(0));
+                        }
+                        let right = truthiness(&right.eval(ctx)?);
+                        return Ok(match (left, right) {
+                            (_, Some(false)) => Value::Integer(0),
+                            (Some(true), Some(true)) => Value::Integer(1),
+                            _ => Value::Null,
+                        });
+                    }
+                    BinaryOperator::Or => {
+                        let left = truthiness(&left.eval(ctx)?);
+                        if left == Some(true) {
+                            return Ok(Value::Integer(1));
+                        }
+                        let right = truthiness(&right.eval(ctx)?);
+                        return Ok(match (left, right) {
+                            (_, Some(true)) => Value::Integer(1),
+                            (Some(false), Some(false)) => Value::Integer(0),
+                            _ => Value::Null,
+                        });
+                    }
+                    _ => {}
+                }
+                let left_value = left.eval(ctx)?;
+                let right_value = right.eval(ctx)?;
+                if op.is_comparison() {
+                    let collation = ctx.comparison_collation(left, right)?;
+                    let (left_value, right_value) =
+                        comparison_affinity(left, left_value, right, right_value, ctx.scope);
+                    return binary(*op, &left_value, &right_value, &collation);
+                }
+                binary(*op, &left_value, &right_value, &Collation::Binary)
+            }
+            Expr::Function {
+                name,
+                args,
+                distinct,
+            } => {
+                if window::is_window_only(name) {
+                    anyhow::bail!("misuse of window function {}()", name);
+                }
+                if function::is_aggregate(name, args.len()) {
+                    let Some(group) = ctx.group else {
+                        anyhow::bail!("misuse of aggregate function {}()", name);
+                    };
+                    let mut arg_values = Vec::with_capacity(group.len());
+                    for row in group.iter() {
+                        let row_ctx = EvalContext::new(ctx.scope, row, ctx.collations);
+                        arg_values.push(
+                            args.iter()
+                                .map(|a| a.eval(&row_ctx))
+                                .collect::<anyhow::Result<Vec<_>>>()?,
+                        );
+                    }
+                    let json_args = args.iter().map(Expr::is_json).collect::<Vec<_>>();
+                    let collations = ctx.argument_collations(args)?;
+                    return function::call_aggregate(
+                        name,
+                        arg_values,
+                        *distinct,
+                        &json_args,
+                        &collations,
+                    );
+                }
+
+                let arg_values = args
+                    .iter()
+                    .map(|a| a.eval(ctx))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                let json_args = args.iter().map(Expr::is_json).collect::<Vec<_>>();
+                function::call_scalar(name, &arg_values, &json_args)
+            }
+            Expr::IsNull { expr, negated } => {
+                let is_null = expr.eval(ctx)?.is_null();
+                Ok(Value::Integer((is_null != *negated) as i64))
+            }
+            Expr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => {
+                let value = expr.eval(ctx)?;
+                let (low_left, low_right) =
+                    comparison_affinity(expr, value.clone(), low, low.eval(ctx)?, ctx.scope);
+                let low = binary(
+                    BinaryOperator::GreaterThanOrEquals,
+                    &low_left,
+                    &low_right,
+                    &ctx.comparison_collation(expr, low)?,
+                )?;
+                let (high_left, high_right) =
+                    comparison_affinity(expr, value, high, high.eval(ctx)?, ctx.scope);
+                let high = binary(
+                    BinaryOperator::LessThanOrEquals,
+                    &high_left,
+                    &high_right,
+                    &ctx.comparison_collation(expr, high)?,
+                )?;
+                let result = match (truthiness(&low), truthiness(&high)) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                };
+                Ok(match result {
+                    Some(b) => Value::Integer((b != *negated) as i64),
+                    None => Value::Null,
+                })
+            }
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let value = expr.eval(ctx)?;
+                if value.is_null() {
+                    return Ok(Value::Null);
+                }
+                let mut saw_null = false;
+                for item in list.iter() {
+                    let collation = ctx.comparison_collation(expr, item)?;
+                    let (value, item_value) =
+                        comparison_affinity(expr, value.clone(), item, item.eval(ctx)?, ctx.scope);
+                    if item_value.is_null() {
+                        saw_null = true;
+                    } else if collation.compare(&value, &item_value).is_eq() {
+                        return Ok(Value::Integer(!*negated as i64));
+                    }
+                }
+                Ok(if saw_null {
+                    Value::Null
+                } else {
+                    Value::Integer(*negated as i64)
+                })
+            }
+            Expr::Case {
+                operand,
+                branches,
+                else_expr,
+            } => {
+                let operand_value = operand.as_ref().map(|o| o.eval(ctx)).transpose()?;
+                for (when, then) in branches.iter() {
+                    let matched = match (operand, &operand_value) {
+                        (Some(operand), Some(operand_value)) => {
+                            let collation = ctx.comparison_collation(operand, when)?;
+                            let (left, right) = comparison_affinity(
+                                operand,
+                                operand_value.clone(),
+                                when,
+                                when.eval(ctx)?,
+                                ctx.scope,
+                            );
+                            binary(BinaryOperator::Equals, &left, &right, &collation)?
+                        }
+                        _ => when.eval(ctx)?,
+                    };
+                    if truthiness(&matched) == Some(true) {
+                        return then.eval(ctx);
+                    }
+                }
+                match else_expr {
+                    Some(else_expr) => else_expr.eval(ctx),
+                    None => Ok(Value::Null),
+                }
+            }
+            Expr::Cast { expr, type_name } => {
+                Ok(cast(expr.eval(ctx)?, Affinity::from_type_name(type_name)))
+            }
+            Expr::Collate { expr, .. } => expr.eval(ctx),
+            // Window functions are computed over the whole result set before projection and
+            // replaced with their results, so any left are somewhere they can't be used
+            Expr::Window { function, .. } => match function.as_ref() {
+                Expr::Function { name, .. } => {
+                    anyhow::bail!("misuse of window function {}()", name)
+                }
+                _ => unreachable!(),
+            },
+        }
+    }
+
+    /// The affinity of this expression's result, which is used to convert the other operand
+    /// of a comparison. Only column references and `CAST`s have an affinity.
+    pub fn affinity(&self, scope: &Scope) -> Affinity {
+        match self {
+            Expr::Column { table, name } => scope
+                .resolve(table.as_deref(), name)
+                .map(|i| scope.columns[i].affinity)
+                .unwrap_or(Affinity::Blob),
+            Expr::Cast { type_name, .. } => Affinity::from_type_name(type_name),
+            Expr::Collate { expr, .. } => expr.affinity(scope),
+            _ => Affinity::Blob,
+        }
+    }
+
+    /// The name of the collation used when this expression is compared or sorted: one given
+    /// by a `COLLATE` clause, or else the collation of the column it refers to. `None` means
+    /// `BINARY`.
+    pub fn collation(&self, scope: &Scope) -> Option<String> {
+        self.explicit_collation()
+            .or_else(|| self.column_collation(scope))
+    }
+
+    /// The collation from the leftmost `COLLATE` clause in this expression.
+    fn explicit_collation(&self) -> Option<String> {
+        let mut collation = None;
+        self.walk(&mut |e| {
+            if let Expr::Collate {
+                collation: name, ..
+            } = e
+            {
+                collation.get_or_insert_with(|| name.clone());
+            }
+        });
+        collation
+    }
+
+    /// The declared collation of the column this expression refers to.
+    fn column_collation(&self, scope: &Scope) -> Option<String> {
+        match self {
+            Expr::Column { table, name } => scope
+                .resolve(table.as_deref(), name)
+                .ok()
+                .and_then(|i| scope.columns[i].collation.clone()),
+            Expr::Cast { expr, .. }
+            | Expr::Unary {
+                op: UnaryOperator::Plus,
+                expr,
+            } => expr.column_collation(scope),
+            _ => None,
+        }
+    }
+
+    /// Is this expression's result a value with the JSON subtype? Such values are embedded
+    /// as-is, rather than as strings, when passed to JSON functions.
+    pub fn is_json(&self) -> bool {
+        match self {
+            Expr::Function { name, .. } => json::returns_json(name),
+            Expr::Window { function, .. } => function.is_json(),
+            Expr::Binary {
+                op: BinaryOperator::Extract,
+                ..
+            } => true,
+            _ => false,
+        }
+    }
+
+    /// Does this expression contain an aggregate function call?
+    pub fn contains_aggregate(&self) -> bool {
+        let mut found = false;
+        self.walk(&mut |expr| {
+            if let Expr::Function { name, args, .. } = expr {
+                found |= function::is_aggregate(name, args.len());
+            }
+        });
+        found
+    }
+
+    /// Copy this expression, replacing each sub-expression for which `f` returns a
+    /// replacement. Replacements aren't themselves searched.
+    pub fn replace(&self, f: &mut dyn FnMut(&Expr) -> Option<Expr>) -> Expr {
+        if let Some(replacement) = f(self) {
+            return replacement;
+        }
+        match self {
+            Expr::Literal(_) | Expr::Column { .. } | Expr::Parameter { .. } => self.clone(),
+            Expr::Unary { op, expr } => Expr::Unary {
+                op: *op,
+                expr: Box::new(expr.replace(f)),
+            },
+            Expr::Binary { op, left, right } => Expr::Binary {
+                op: *op,
+                left: Box::new(left.replace(f)),
+                right: Box::new(right.replace(f)),
+            },
+            Expr::Function {
+                name,
+                args,
+                distinct,
+            } => Expr::Function {
+                name: name.clone(),
+                args: args.iter().map(|a| a.replace(f)).collect(),
+                distinct: *distinct,
+            },
+            Expr::IsNull { expr, negated } => Expr::IsNull {
+                expr: Box::new(expr.replace(f)),
+                negated: *negated,
+            },
+            Expr::Between {
+                expr,
+                low,
+                high,
+                negated,
+            } => Expr::Between {
+                expr: Box::new(expr.replace(f)),
+                low: Box::new(low.replace(f)),
+                high: Box::new(high.replace(f)),
+                negated: *negated,
+            },
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => Expr::InList {
+                expr: Box::new(expr.replace(f)),
+                list: list.iter().map(|e| e.replace(f)).collect(),
+                negated: *negated,
+            },
+            Expr::Case {
+                operand,
+                branches,
+                else_expr,
+            } => Expr::Case {
+                operand: operand.as_ref().map(|o| Box::new(o.replace(f))),
+                branches: branches
+                    .iter()
+                    .map(|(when, then)| (when.replace(f), then.replace(f)))
+                    .collect(),
+                else_expr: else_expr.as_ref().map(|e| Box::new(e.replace(f))),
+            },
+            Expr::Cast { expr, type_name } => Expr::Cast {
+                expr: Box::new(expr.replace(f)),
+                type_name: type_name.clone(),
+            },
+            Expr::Collate { expr, collation } => Expr::Collate {
+                expr: Box::new(expr.replace(f)),
+                collation: collation.clone(),
+            },
+            // As with `walk`, the window function itself is skipped
+            Expr::Window { function, window } => {
+                let function = match function.as_ref() {
+                    Expr::Function {
+                        name,
+                        args,
+                        distinct,
+                    } => Expr::Function {
+                        name: name.clone(),
+                        args: args.iter().map(|a| a.replace(f)).collect(),
+                        distinct: *distinct,
+                    },
+                    function => function.clone(),
+                };
+                Expr::Window {
+                    function: Box::new(function),
+                    window: Box::new(window.replace(f)),
+                }
+            }
+        }
+    }
+
+    /// Call `f` on this expression and every expression nested inside it.
+    pub fn walk(&self, f: &mut dyn FnMut(&Expr)) {
+        f(self);
+        match self {
+            Expr::Literal(_) | Expr::Column { .. } | Expr::Parameter { .. } => {}
+            Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } => expr.walk(f),
+            Expr::Binary { left, right, .. } => {
+                left.walk(f);
+                right.walk(f);
+            }
+            Expr::Function { args, .. } => args.iter().for_each(|a| a.walk(f)),
+            Expr::Between {
+                expr, low, high, ..
+            } => {
+                expr.walk(f);
+                low.walk(f);
+                high.walk(f);
+            }
+            Expr::InList { expr, list, .. } => {
+                expr.walk(f);
+                list.iter().for_each(|e| e.walk(f));
+            }
+            Expr::Case {
+                operand,
+                branches,
+                else_expr,
+            } => {
+                if let Some(operand) = operand {
+                    operand.walk(f);
+                }
+                for (when, then) in branches.iter() {
+                    when.walk(f);
+                    then.walk(f);
+                }
+                if let Some(else_expr) = else_expr {
+                    else_expr.walk(f);
+                }
+            }
+            Expr::Cast { expr, .. } | Expr::Collate { expr, .. } => expr.walk(f),
+            // The window function itself isn't visited, so that e.g. `sum(x) OVER ()` isn't
+            // mistaken for an aggregate
+            Expr::Window { function, window } => {
+                if let Expr::Function { args, .. } = function.as_ref() {
+                    args.iter().for_each(|a| a.walk(f));
+                }
+                window.walk(f);
+            }
+        }
+    }
+}
+
+impl BinaryOperator {
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinaryOperator::Equals
+                | BinaryOperator::NotEquals
+                | BinaryOperator::Is
+                | BinaryOperator::IsNot
+                | BinaryOperator::LessThan
+                | BinaryOperator::LessThanOrEquals
+                | BinaryOperator::GreaterThan
+                | BinaryOperator::GreaterThanOrEquals
+        )
+    }
+}
+
+/// Convert the operands of a comparison according to their affinities: if one side is
+/// numeric, the other side gets numeric affinity; otherwise if one side is text and the other
+/// has no affinity, the other side gets text affinity.
+pub fn comparison_affinity(
+    left: &Expr,
+    left_value: Value,
+    right: &Expr,
+    right_value: Value,
+    scope: &Scope,
+) -> (Value, Value) {
+    let left_affinity = left.affinity(scope);
+    let right_affinity = right.affinity(scope);
+
+    if left_affinity.is_numeric() && !right_affinity.is_numeric() {
+        (left_value, Affinity::Numeric.apply(right_value))
+    } else if right_affinity.is_numeric() && !left_affinity.is_numeric() {
+        (Affinity::Numeric.apply(left_value), right_value)
+    } else if left_affinity == Affinity::Text && right_affinity == Affinity::Blob {
+        (left_value, Affinity::Text.apply(right_value))
+    } else if right_affinity == Affinity::Text && left_affinity == Affinity::Blob {
+        (Affinity::Text.apply(left_value), right_value)
+    } else {
+        (left_value, right_value)
+    }
+}
+
+/// The name of the collation used to compare two expressions: from a `COLLATE` clause on the
+/// left, then the right, then the left operand's column, then the right's.
+pub fn comparison_collation(left: &Expr, right: &Expr, scope: &Scope) -> Option<String> {
+    left.explicit_collation()
+        .or_else(|| right.explicit_collation())
+        .or_else(|| left.column_collation(scope))
+        .or_else(|| right.column_collation(scope))
+}
+
+/// Evaluate a binary operator, other than the short-circuiting `AND` and `OR`. Comparisons of
+/// text use `collation`.
+pub fn binary(
+    op: BinaryOperator,
+    left: &Value,
+    right: &Value,
+    collation: &Collation,
+) -> anyhow::Result<Value> {
+    use std::cmp::Ordering;
+
+    match op {
+        BinaryOperator::Is => return Ok(Value::Integer(is_same(left, right, collation) as i64)),
+        BinaryOperator::IsNot => {
+            return Ok(Value::Integer(!is_same(left, right, collation) as i64))
+        }
+        BinaryOperator::Extract => return json::extract_operator(left, right, false),
+        BinaryOperator::ExtractValue => return json::extract_operator(left, right, true),
+        _ => {}
+    }
+
+    if left.is_null() || right.is_null() {
+        return Ok(Value::Null);
+    }
+
+    let compare =
+        |f: fn(Ordering) -> bool| Ok(Value::Integer(f(collation.compare(left, right)) as i64));
+
+    match op {
+        BinaryOperator::Equals => compare(|o| o == Ordering::Equal),
+        BinaryOperator::NotEquals => compare(|o| o != Ordering::Equal),
+        BinaryOperator::LessThan => compare(|o| o == Ordering::Less),
+        BinaryOperator::LessThanOrEquals => compare(|o| o != Ordering::Greater),
+        BinaryOperator::GreaterThan => compare(|o| o == Ordering::Greater),
+        BinaryOperator::GreaterThanOrEquals => compare(|o| o != Ordering::Less),
+        BinaryOperator::Like => Ok(Value::Integer(
+            like(&right.to_string(), &left.to_string()) as i64
+        )),
+        BinaryOperator::Glob => Ok(Value::Integer(
+            glob(&right.to_string(), &left.to_string()) as i64
+        )),
+        BinaryOperator::Concat => Ok(Value::Text(format!("{}{}", left, right))),
+        BinaryOperator::BitAnd
+        | BinaryOperator::BitOr
+        | BinaryOperator::ShiftLeft
+        | BinaryOperator::ShiftRight => {
+            let (l, r) = (
+                to_integer(left).unwrap_or(0),
+                to_integer(right).unwrap_or(0),
+            );
+            Ok(Value::Integer(match op {
+                BinaryOperator::BitAnd => l & r,
+                BinaryOperator::BitOr => l | r,
+                BinaryOperator::ShiftLeft => shift(l, r),
+                _ => shift(l, r.saturating_neg()),
+            }))
+        }
+        BinaryOperator::Add
+        | BinaryOperator::Subtract
+        | BinaryOperator::Multiply
+        | BinaryOperator::Divide
+        | BinaryOperator::Remainder => arithmetic(op, &to_numeric(left), &to_numeric(right)),
+        BinaryOperator::And
+        | BinaryOperator::Or
+        | BinaryOperator::Is
+        | BinaryOperator::IsNot
+        | BinaryOperator::Extract
+        | BinaryOperator::ExtractValue => unreachable!(),
+    }
+}
+
+fn arithmetic(op: BinaryOperator, left: &Value, right: &Value) -> anyhow::Result<Value> {
+    if let (Value::Integer(l), Value::Integer(r)) = (left, right) {
+        let result = match op {
+            BinaryOperator::Add => l.checked_add(*r),
+            BinaryOperator::Subtract => l.checked_sub(*r),
+            BinaryOperator::Multiply => l.checked_mul(*r),
+            BinaryOperator::Divide => {
+                if *r == 0 {
+                    return Ok(Value::Null);
+                }
+                l.checked_div(*r)
+            }
+            BinaryOperator::Remainder => {
+                if *r == 0 {
+                    return Ok(Value::Null);
+                }
+                Some(l.wrapping_rem(*r))
+            }
+            _ => unreachable!(),
+        };
+        if let Some(n) = result {
+            return Ok(Value::Integer(n));
+        }
+    }
+
+    let (Some(l), Some(r)) = (to_real(left), to_real(right)) else {
+        return Ok(Value::Null);
+    };
+    Ok(match op {
+        BinaryOperator::Add => Value::Real(l + r),
+        BinaryOperator::Subtract => Value::Real(l - r),
+        BinaryOperator::Multiply => Value::Real(l * r),
+        BinaryOperator::Divide if r == 0.0 => Value::Null,
+        BinaryOperator::Divide => Value::Real(l / r),
+        BinaryOperator::Remainder => {
+            let (l, r) = (l as i64, r as i64);
+            if r == 0 {
+                Value::Null
+            } else {
+                Value::Real(l.wrapping_rem(r) as f64)
+            }
+        }
+        _ => unreachable!(),
+    })
+}
+
+fn shift(value: i64, amount: i64) -> i64 {
+    if amount >= 64 {
+        0
+    } else if amount >= 0 {
+        value << amount
+    } else if amount <= -64 {
+        if value < 0 {
+            -1
+        } else {
+            0
+        }
+    } else {
+        value >> -amount
+    }
+}
+
+/// `IS` comparison, where NULLs are equal to each other.
+fn is_same(left: &Value, right: &Value, collation: &Collation) -> bool {
+    match (left, right) {
+        (Value::Null, Value::Null) => true,
+        (Value::Null, _) | (_, Value::Null) => false,
+        _ => collation.compare(left, right).is_eq(),
+    }
+}
+
+/// Interpret a value as a boolean, or `None` for NULL.
+pub fn truthiness(value: &Value) -> Option<bool> {
+    match to_numeric(value) {
+        Value::Integer(n) => Some(n != 0),
+        Value::Real(f) => Some(f != 0.0),
+        _ => None,
+    }
+}
+
+/// Convert a value to a number for arithmetic, using the longest numeric prefix of text.
+pub fn to_numeric(value: &Value) -> Value {
+    match value {
+        Value::Null | Value::Integer(_) | Value::Real(_) => value.clone(),
+        Value::Text(_) | Value::Blob(_) => {
+            let text = value.to_string();
+            let s = text.trim();
+            if let Ok(n) = s.parse::<i64>() {
+                return Value::Integer(n);
+            }
+            let prefix = numeric_prefix(s);
+            if let Ok(n) = prefix.parse::<i64>() {
+                Value::Integer(n)
+            } else if let Ok(f) = prefix.parse::<f64>() {
+                Value::Real(f)
+            } else {
+                Value::Integer(0)
+            }
+        }
+    }
+}
+
+/// The longest prefix of `s` that looks like a number.
+fn numeric_prefix(s: &str) -> &str {
+    let bytes = s.as_bytes();
+    let mut end = 0;
+    if matches!(bytes.first(), Some(b'+' | b'-')) {
+        end += 1;
+    }
+    while bytes.get(end).is_some_and(|b| b.is_ascii_digit()) {
+        end += 1;
+    }
+    if bytes.get(end) == Some(&b'.') {
+        end += 1;
+        while bytes.get(end).is_some_and(|b| b.is_ascii_digit()) {
+            end += 1;
+        }
+    }
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let mut exponent_end = end + 1;
+        if matches!(bytes.get(exponent_end), Some(b'+' | b'-')) {
+            exponent_end += 1;
+        }
+        if bytes.get(exponent_end).is_some_and(|b| b.is_ascii_digit()) {
+            while bytes.get(exponent_end).is_some_and(|b| b.is_ascii_digit()) {
+                exponent_end += 1;
+            }
+            end = exponent_end;
+        }
+    }
+    &s[..end]
+}
+
+pub fn to_integer(value: &Value) -> Option<i64> {
+    match to_numeric(value) {
+        Value::Integer(n) => Some(n),
+        Value::Real(f) => Some(f as i64),
+        _ => None,
+    }
+}
+
+pub fn to_real(value: &Value) -> Option<f64> {
+    match to_numeric(value) {
+        Value::Integer(n) => Some(n as f64),
+        Value::Real(f) => Some(f),
+        _ => None,
+    }
+}
+
+/// SQL `LIKE` matching: `%` matches any sequence, `_` any single character, and ASCII letters
+/// match case-insensitively.
+pub fn like(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    wildcard_match(&pattern, &text, '%', '_', |p, t| p.eq_ignore_ascii_case(&t))
+}
+
+/// SQL `GLOB` matching: `*` matches any sequence, `?` any single character, and `[...]`
+/// character classes are supported. Matching is case-sensitive.
+pub fn glob(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    glob_match(&pattern, &text)
+}
+
+fn wildcard_match(
+    pattern: &[char],
+    text: &[char],
+    any: char,
+    one: char,
+    eq: fn(char, char) -> bool,
+) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(c) if *c == any => {
+            (0..=text.len()).any(|i| wildcard_match(&pattern[1..], &text[i..], any, one, eq))
+        }
+        Some(c) if *c == one => {
+            !text.is_empty() && wildcard_match(&pattern[1..], &text[1..], any, one, eq)
+        }
+        Some(c) => {
+            !text.is_empty()
+                && eq(*c, text[0])
+                && wildcard_match(&pattern[1..], &text[1..], any, one, eq)
+        }
+    }
+}
+
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') => (0..=text.len()).any(|i| glob_match(&pattern[1..], &text[i..])),
+        Some('?') => !text.is_empty() && glob_match(&pattern[1..], &text[1..]),
+        Some('[') => {
+            let Some(end) = pattern
+                .iter()
+                .skip(2)
+                .position(|c| *c == ']')
+                .map(|p| p + 2)
+            else {
+                return !text.is_empty() && text[0] == '[' && glob_match(&pattern[1..], &text[1..]);
+            };
+            let Some(c) = text.first() else {
+                return false;
+            };
+            let mut class = &pattern[1..end];
+            let inverted = class.first() == Some(&'^');
+            if inverted {
+                class = &class[1..];
+            }
+            let mut matched = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    matched |= class[i] <= *c && *c <= class[i + 2];
+                    i += 3;
+                } else {
+                    matched |= class[i] == *c;
+                    i += 1;
+                }
+            }
+            matched != inverted && glob_match(&pattern[end + 1..], &text[1..])
+        }
+        Some(c) => !text.is_empty() && *c == text[0] && glob_match(&pattern[1..], &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob, like, to_numeric};
+    use crate::record::Value;
+
+    #[test]
+    fn like_patterns() {
+        assert!(like("a%", "Apple"));
+        assert!(like("%PL_", "apple"));
+        assert!(!like("a_", "apple"));
+        assert!(like("%", ""));
+    }
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob("a*", "apple"));
+        assert!(!glob("A*", "apple"));
+        assert!(glob("?pple", "apple"));
+        assert!(glob("[a-c]pple", "bpple"));
+        assert!(glob("[^a-c]pple", "dpple"));
+    }
+
+    #[test]
+    fn numeric_conversion() {
+        assert_eq!(to_numeric(&Value::Text("12".into())), Value::Integer(12));
+        assert_eq!(
+            to_numeric(&Value::Text(" 1.5e1x".into())),
+            Value::Real(15.0)
+        );
+        assert_eq!(to_numeric(&Value::Text("abc".into())), Value::Integer(0));
+    }
+}
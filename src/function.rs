@@ -0,0 +1,588 @@
+use std::{cmp::Ordering, collections::VecDeque};
+
+use crate::{
+    collation::Collation,
+    expr::{to_integer, to_numeric, to_real},
+    json,
+    query::DistinctRows,
+    record::Value,
+};
+
+/// Is this a call to an aggregate function? `min()` and `max()` are only aggregates with a
+/// single argument.
+pub fn is_aggregate(name: &str, arg_count: usize) -> bool {
+    match name {
+        "count" | "sum" | "total" | "avg" | "group_concat" | "json_group_array"
+        | "json_group_object" => true,
+        "min" | "max" => arg_count == 1,
+        _ => false,
+    }
+}
+
+/// Call an aggregate function, given its argument values for every row in the group and the
+/// collation of each argument, which `DISTINCT`, `min()` and `max()` compare text with.
+pub fn call_aggregate(
+    name: &str,
+    mut rows: Vec<Vec<Value>>,
+    distinct: bool,
+    json_args: &[bool],
+    collations: &[Collation],
+) -> anyhow::Result<Value> {
+    if distinct {
+        let mut seen = DistinctRows::new(collations.to_vec());
+        rows.retain(|row| seen.insert(row));
+    }
+    let collation = collations.first().unwrap_or(&Collation::Binary);
+
+    let first_args = || rows.iter().map(|r| &r[0]);
+    let non_null = || first_args().filter(|v| !v.is_null());
+
+    match name {
+        "count" => Ok(Value::Integer(
+            if rows.first().map_or(true, |r| r.is_empty()) {
+                rows.len()
+            } else {
+                non_null().count()
+            } as i64,
+        )),
+        "sum" => {
+            let mut any = false;
+            let mut integer_total = Some(0i64);
+            let mut real_total = 0.0;
+            for value in non_null() {
+                any = true;
+                match to_numeric(value) {
+                    Value::Integer(n) => {
+                        if let Some(total) = integer_total {
+                            integer_total = Some(
+                                total
+                                    .checked_add(n)
+                                    .ok_or_else(|| anyhow::format_err!("integer overflow"))?,
+                            );
+                        }
+                        real_total += n as f64;
+                    }
+                    numeric => {
+                        integer_total = None;
+                        real_total += to_real(&numeric).unwrap_or(0.0);
+                    }
+                }
+            }
+            Ok(match (any, integer_total) {
+                (false, _) => Value::Null,
+                (true, Some(n)) => Value::Integer(n),
+                (true, None) => Value::Real(real_total),
+            })
+        }
+        "total" => Ok(Value::Real(non_null().filter_map(to_real).sum())),
+        "avg" => {
+            let count = non_null().count();
+            if count == 0 {
+                return Ok(Value::Null);
+            }
+            Ok(Value::Real(
+                non_null().filter_map(to_real).sum::<f64>() / count as f64,
+            ))
+        }
+        "min" => Ok(non_null()
+            .min_by(|a, b| collation.compare(a, b))
+            .cloned()
+            .unwrap_or(Value::Null)),
+        "max" => Ok(non_null()
+            .rev()
+            .max_by(|a, b| collation.compare(a, b))
+            .cloned()
+            .unwrap_or(Value::Null)),
+        "group_concat" => {
+            let mut result: Option<String> = None;
+            for row in rows.iter() {
+                if row[0].is_null() {
+                    continue;
+                }
+                let separator = row.get(1).map(|s| s.to_string()).unwrap_or(",".into());
+                match result.as_mut() {
+                    Some(result) => {
+                        result.push_str(&separator);
+                        result.push_str(&row[0].to_string());
+                    }
+                    None => result = Some(row[0].to_string()),
+                }
+            }
+            Ok(result.map(Value::Text).unwrap_or(Value::Null))
+        }
+        "json_group_array" => {
+            let values = first_args().cloned().collect::<Vec<_>>();
+            json::group_array(&values, json_args.first().copied().unwrap_or(false))
+        }
+        "json_group_object" => {
+            if json_args.len() != 2 {
+                anyhow::bail!("wrong number of arguments to function json_group_object()");
+            }
+            let pairs = rows
+                .iter()
+                .map(|r| (r[0].clone(), r[1].clone()))
+                .collect::<Vec<_>>();
+            json::group_object(&pairs, json_args[1])
+        }
+        _ => anyhow::bail!("no such function: {}", name),
+    }
+}
+
+/// An aggregate function over a window frame as it moves through a partition. Rows are added
+/// to and removed from its state as they enter and leave the frame, rather than the function
+/// being called on each frame's rows.
+pub struct FrameAggregate<'a> {
+    name: &'a str,
+    /// The argument values of each row in the partition.
+    rows: Vec<Vec<Value>>,
+    distinct: bool,
+    json_args: Vec<bool>,
+    collations: Vec<Collation>,
+    /// The range of positions the state is for.
+    start: usize,
+    end: usize,
+    /// The number of rows whose first argument isn't NULL, or of all rows for `count(*)`.
+    count: usize,
+    integer_total: i128,
+    /// The number of values which aren't integers, and so make `sum()` a real.
+    non_integers: usize,
+    /// The total as a real, and the error in it, as values are added and removed with
+    /// Kahan-Babuska-Neumaier summation.
+    real_total: f64,
+    real_error: f64,
+    /// The positions of the values which are `min()` or `max()`, first, or which could be once
+    /// the rows before them leave the frame.
+    extremes: VecDeque<usize>,
+}
+
+impl<'a> FrameAggregate<'a> {
+    pub fn new(
+        name: &'a str,
+        rows: Vec<Vec<Value>>,
+        distinct: bool,
+        json_args: Vec<bool>,
+        collations: Vec<Collation>,
+    ) -> Self {
+        FrameAggregate {
+            name,
+            rows,
+            distinct,
+            json_args,
+            collations,
+            start: 0,
+            end: 0,
+            count: 0,
+            integer_total: 0,
+            non_integers: 0,
+            real_total: 0.0,
+            real_error: 0.0,
+            extremes: VecDeque::new(),
+        }
+    }
+
+    /// The function's value over the rows from `start` up to `end`.
+    pub fn value(&mut self, start: usize, end: usize) -> anyhow::Result<Value> {
+        // These need every row in the frame anyway
+        if self.distinct
+            || matches!(
+                self.name,
+                "group_concat" | "json_group_array" | "json_group_object"
+            )
+        {
+            return call_aggregate(
+                self.name,
+                self.rows[start..end].to_vec(),
+                self.distinct,
+                &self.json_args,
+                &self.collations,
+            );
+        }
+
+        // Frames usually only move forward, and otherwise are started again
+        if start < self.start || end < self.end || start >= self.end {
+            self.clear(start);
+        }
+        while self.end < end {
+            self.add(self.end);
+            self.end += 1;
+        }
+        while self.start < start {
+            self.remove(self.start);
+            self.start += 1;
+        }
+
+        let real_total = if self.non_integers == 0 {
+            self.integer_total as f64
+        } else {
+            self.real_total + self.real_error
+        };
+        Ok(match self.name {
+            "count" => Value::Integer(self.count as i64),
+            "sum" if self.count == 0 => Value::Null,
+            "sum" if self.non_integers == 0 => Value::Integer(
+                i64::try_from(self.integer_total)
+                    .map_err(|_| anyhow::format_err!("integer overflow"))?,
+            ),
+            "sum" => Value::Real(real_total),
+            "total" => Value::Real(if self.count == 0 { 0.0 } else { real_total }),
+            "avg" if self.count == 0 => Value::Null,
+            "avg" => Value::Real(real_total / self.count as f64),
+            "min" | "max" => self
+                .extremes
+                .front()
+                .map_or(Value::Null, |p| self.rows[*p][0].clone()),
+            _ => anyhow::bail!("no such function: {}", self.name),
+        })
+    }
+
+    fn clear(&mut self, start: usize) {
+        self.start = start;
+        self.end = start;
+        self.count = 0;
+        self.integer_total = 0;
+        self.non_integers = 0;
+        self.real_total = 0.0;
+        self.real_error = 0.0;
+        self.extremes.clear();
+    }
+
+    fn add(&mut self, position: usize) {
+        let Some(value) = self.rows[position].first() else {
+            self.count += 1;
+            return;
+        };
+        if value.is_null() {
+            return;
+        }
+        self.count += 1;
+        match to_numeric(value) {
+            Value::Integer(n) => self.integer_total += n as i128,
+            _ => self.non_integers += 1,
+        }
+        let real = to_real(value).unwrap_or(0.0);
+        add_real(&mut self.real_total, &mut self.real_error, real);
+        if !matches!(self.name, "min" | "max") {
+            return;
+        }
+
+        // Values no better than the new one can no longer be the result
+        let worse = if self.name == "min" {
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        };
+        while let Some(last) = self.extremes.back() {
+            let collation = self.collations.first().unwrap_or(&Collation::Binary);
+            if collation.compare(&self.rows[*last][0], value) != worse {
+                break;
+            }
+            self.extremes.pop_back();
+        }
+        self.extremes.push_back(position);
+    }
+
+    fn remove(&mut self, position: usize) {
+        let Some(value) = self.rows[position].first() else {
+            self.count -= 1;
+            return;
+        };
+        if value.is_null() {
+            return;
+        }
+        self.count -= 1;
+        match to_numeric(value) {
+            Value::Integer(n) => self.integer_total -= n as i128,
+            _ => self.non_integers -= 1,
+        }
+        let real = to_real(value).unwrap_or(0.0);
+        add_real(&mut self.real_total, &mut self.real_error, -real);
+        if self.extremes.front() == Some(&position) {
+            self.extremes.pop_front();
+        }
+    }
+}
+
+/// Add `value` to a total, adding the rounding error to `error` (Kahan-Babuska-Neumaier
+/// summation).
+fn add_real(total: &mut f64, error: &mut f64, value: f64) {
+    let sum = *total + value;
+    *error += if total.abs() >= value.abs() {
+        (*total - sum) + value
+    } else {
+        (value - sum) + *total
+    };
+    *total = sum;
+}
+
+/// Call a scalar (non-aggregate) function.
+pub fn call_scalar(name: &str, args: &[Value], json_args: &[bool]) -> anyhow::Result<Value> {
+    if let Some(result) = json::call(name, args, json_args) {
+        return result;
+    }
+
+    let arg_count_error =
+        || anyhow::format_err!("wrong number of arguments to function {}()", name);
+    let check_arg_count = |range: std::ops::RangeInclusive<usize>| {
+        if range.contains(&args.len()) {
+            Ok(())
+        } else {
+            Err(arg_count_error())
+        }
+    };
+
+    match name {
+        "coalesce" | "ifnull" => {
+            if name == "ifnull" {
+                check_arg_count(2..=2)?;
+            } else if args.len() < 2 {
+                return Err(arg_count_error());
+            }
+            Ok(args
+                .iter()
+                .find(|v| !v.is_null())
+                .cloned()
+                .unwrap_or(Value::Null))
+        }
+        "nullif" => {
+            check_arg_count(2..=2)?;
+            Ok(if args[0] == args[1] {
+                Value::Null
+            } else {
+                args[0].clone()
+            })
+        }
+        "iif" => {
+            check_arg_count(3..=3)?;
+            Ok(if crate::expr::truthiness(&args[0]) == Some(true) {
+                args[1].clone()
+            } else {
+                args[2].clone()
+            })
+        }
+        "min" | "max" => {
+            if args.iter().any(|v| v.is_null()) {
+                return Ok(Value::Null);
+            }
+            let result = if name == "min" {
+                args.iter().min_by(|a, b| a.compare(b))
+            } else {
+                args.iter().rev().max_by(|a, b| a.compare(b))
+            };
+            result.cloned().ok_or_else(arg_count_error)
+        }
+        "typeof" => {
+            check_arg_count(1..=1)?;
+            Ok(Value::Text(args[0].type_name().to_owned()))
+        }
+        "length" => {
+            check_arg_count(1..=1)?;
+            Ok(match &args[0] {
+                Value::Null => Value::Null,
+                Value::Blob(s) => Value::Integer(s.len() as i64),
+                value => Value::Integer(value.to_string().chars().count() as i64),
+            })
+        }
+        "lower" | "upper" => {
+            check_arg_count(1..=1)?;
+            Ok(match &args[0] {
+                Value::Null => Value::Null,
+                value if name == "lower" => Value::Text(value.to_string().to_lowercase()),
+                value => Value::Text(value.to_string().to_uppercase()),
+            })
+        }
+        "abs" => {
+            check_arg_count(1..=1)?;
+            Ok(match to_numeric(&args[0]) {
+                Value::Integer(n) => Value::Integer(
+                    n.checked_abs()
+                        .ok_or_else(|| anyhow::format_err!("integer overflow"))?,
+                ),
+                Value::Real(f) => Value::Real(f.abs()),
+                _ => Value::Null,
+            })
+        }
+        "round" => {
+            check_arg_count(1..=2)?;
+            let Some(value) = to_real(&args[0]) else {
+                return Ok(Value::Null);
+            };
+            let digits = args.get(1).and_then(to_integer).unwrap_or(0).clamp(0, 30);
+            let factor = 10f64.powi(digits as i32);
+            Ok(Value::Real((value * factor).round() / factor))
+        }
+        "substr" | "substring" => {
+            check_arg_count(2..=3)?;
+            if args.iter().any(|v| v.is_null()) {
+                return Ok(Value::Null);
+            }
+            let chars = args[0].to_string().chars().collect::<Vec<_>>();
+            let len = chars.len() as i64;
+            let mut start = to_integer(&args[1]).unwrap_or(0);
+            let mut count = args.get(2).and_then(to_integer).unwrap_or(len + 1);
+            if start < 0 {
+                start += len + 1;
+                if start < 1 {
+                    count += start - 1;
+                    start = 1;
+                }
+            } else if start == 0 {
+                start = 1;
+                count -= 1;
+            }
+            if count < 0 {
+                // A negative length takes characters before the start position
+                let end = start - 1;
+                let begin = (end + count).max(0);
+                count = end - begin;
+                start = begin + 1;
+            }
+            let begin = (start - 1).clamp(0, len) as usize;
+            let end = (start - 1 + count.max(0)).clamp(0, len) as usize;
+            Ok(Value::Text(chars[begin..end].iter().collect()))
+        }
+        "trim" | "ltrim" | "rtrim" => {
+            check_arg_count(1..=2)?;
+            if args.iter().any(|v| v.is_null()) {
+                return Ok(Value::Null);
+            }
+            let text = args[0].to_string();
+            let characters = args.get(1).map(|v| v.to_string()).unwrap_or(" ".into());
+            let is_trimmed = |c: char| characters.contains(c);
+            Ok(Value::Text(
+                match name {
+                    "trim" => text.trim_matches(is_trimmed),
+                    "ltrim" => text.trim_start_matches(is_trimmed),
+                    _ => text.trim_end_matches(is_trimmed),
+                }
+                .to_owned(),
+            ))
+        }
+        "replace" => {
+            check_arg_count(3..=3)?;
+            if args.iter().any(|v| v.is_null()) {
+                return Ok(Value::Null);
+            }
+            let pattern = args[1].to_string();
+            if pattern.is_empty() {
+                return Ok(Value::Text(args[0].to_string()));
+            }
+            Ok(Value::Text(
+                args[0].to_string().replace(&pattern, &args[2].to_string()),
+            ))
+        }
+        "instr" => {
+            check_arg_count(2..=2)?;
+            if args.iter().any(|v| v.is_null()) {
+                return Ok(Value::Null);
+            }
+            let haystack = args[0].to_string();
+            let needle = args[1].to_string();
+            Ok(Value::Integer(
+                haystack
+                    .find(&needle)
+                    .map(|i| haystack[..i].chars().count() as i64 + 1)
+                    .unwrap_or(0),
+            ))
+        }
+        "current_date" | "current_time" | "current_timestamp" => {
+            check_arg_count(0..=0)?;
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs() as i64);
+            let (date, time) = utc_date_time(now);
+            Ok(Value::Text(match name {
+                "current_date" => date,
+                "current_time" => time,
+                _ => format!("{} {}", date, time),
+            }))
+        }
+        _ => anyhow::bail!("no such function: {}", name),
+    }
+}
+
+/// The UTC date and time of a Unix timestamp, as `YYYY-MM-DD` and `HH:MM:SS`.
+fn utc_date_time(timestamp: i64) -> (String, String) {
+    let (days, seconds) = (timestamp.div_euclid(86400), timestamp.rem_euclid(86400));
+
+    // Days since 1970-01-01 to a date in the proleptic Gregorian calendar, counting from
+    // 0000-03-01 so that leap days fall at the end of each year
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = (month_from_march + 2) % 12 + 1;
+    let year = era * 400 + year_of_era + (month <= 2) as i64;
+
+    (
+        format!("{:04}-{:02}-{:02}", year, month, day),
+        format!(
+            "{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{utc_date_time, FrameAggregate};
+    use crate::{collation::Collation, record::Value};
+
+    #[test]
+    fn moving_frames() {
+        let rows = [Some(3), None, Some(1), Some(4), Some(1), Some(5)]
+            .iter()
+            .map(|n| vec![n.map_or(Value::Null, Value::Integer)])
+            .collect::<Vec<_>>();
+        let values = |name, frames: &[(usize, usize)]| {
+            let mut aggregate = FrameAggregate::new(
+                name,
+                rows.clone(),
+                false,
+                vec![false],
+                vec![Collation::Binary],
+            );
+            frames
+                .iter()
+                .map(|(start, end)| aggregate.value(*start, *end).unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let sliding = [(0, 2), (0, 3), (1, 4), (2, 5), (3, 6), (4, 6), (6, 6)];
+        assert_eq!(
+            values("min", &sliding),
+            vec!["3", "1", "1", "1", "1", "1", ""]
+        );
+        assert_eq!(
+            values("max", &sliding),
+            vec!["3", "3", "4", "4", "5", "5", ""]
+        );
+        assert_eq!(
+            values("sum", &sliding),
+            vec!["3", "4", "5", "6", "10", "6", ""]
+        );
+        assert_eq!(
+            values("count", &sliding),
+            vec!["1", "2", "2", "3", "3", "2", "0"]
+        );
+        // Frames which move back are started again
+        assert_eq!(values("min", &[(2, 6), (0, 2)]), vec!["1", "3"]);
+    }
+
+    #[test]
+    fn utc_dates() {
+        let date_time = |timestamp| {
+            let (date, time) = utc_date_time(timestamp);
+            format!("{} {}", date, time)
+        };
+        assert_eq!(date_time(0), "1970-01-01 00:00:00");
+        assert_eq!(date_time(951_782_400), "2000-02-29 00:00:00");
+        assert_eq!(date_time(1_792_368_000 + 45_296), "2026-10-19 12:34:56");
+        assert_eq!(date_time(-1), "1969-12-31 23:59:59");
+    }
+}
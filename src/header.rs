@@ -50,7 +50,7 @@ pub struct Header {
     pub sqlite_version_number: u32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FormatVersion {
     Legacy = 1,
     WriteAheadLog = 2,
@@ -71,7 +71,7 @@ impl TryFrom<u8> for FormatVersion {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TextEncoding {
     Utf8 = 1,
     Utf16le = 2,
@@ -94,10 +94,28 @@ impl TryFrom<u32> for TextEncoding {
     }
 }
 
+impl TextEncoding {
+    /// The encoding's name, as `PRAGMA encoding` gives it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "utf8",
+            TextEncoding::Utf16le => "utf16le",
+            TextEncoding::Utf16be => "utf16be",
+        }
+    }
+}
+
+/// An error for a header field with an invalid value.
+fn invalid<I>(message: String) -> nom::Err<MyError<I>> {
+    Error(MyError::InvalidValueError(InvalidValueError(message)))
+}
+
 impl Header {
     pub fn parse(input: &[u8]) -> IResult<&[u8], Self, MyError<&[u8]>> {
         let (input, header_string) = take(16usize)(input)?;
-        assert_eq!(header_string, b"SQLite format 3\0");
+        if header_string != b"SQLite format 3\0" {
+            return Err(invalid("invalid header string".to_owned()));
+        }
 
         let (input, page_size) = {
             let (input, value) = be_u16(input)?;
@@ -109,9 +127,7 @@ impl Header {
                         if value >= 512 && (value & (value - 1)) == 0 {
                             value as u32
                         } else {
-                            return Err(Error(MyError::InvalidValueError(InvalidValueError(
-                                format!("invalid page size {}", value),
-                            ))));
+                            return Err(invalid(format!("invalid page size {}", value)));
                         }
                     }
                 },
@@ -127,12 +143,14 @@ impl Header {
 
         let (input, end_page_reserved_bytes) = u8(input)?;
 
-        let (input, max_embedded_payload_fraction) = u8(input)?;
-        assert_eq!(max_embedded_payload_fraction, 64);
-        let (input, min_embedded_payload_fraction) = u8(input)?;
-        assert_eq!(min_embedded_payload_fraction, 32);
-        let (input, leaf_payload_fraction) = u8(input)?;
-        assert_eq!(leaf_payload_fraction, 32);
+        // The payload fractions are fixed
+        let (input, payload_fractions) = take(3usize)(input)?;
+        if payload_fractions != [64, 32, 32] {
+            return Err(invalid(format!(
+                "invalid payload fractions {:?}",
+                payload_fractions
+            )));
+        }
 
         let (input, file_change_counter) = be_u32(input)?;
         let (input, size_in_pages) = be_u32(input)?;
@@ -142,9 +160,7 @@ impl Header {
 
         let (input, schema_format) = be_u32(input)?;
         if !(1..=4).contains(&schema_format) {
-            return Err(Error(MyError::InvalidValueError(InvalidValueError(
-                format!("invalid schema format {}", schema_format),
-            ))));
+            return Err(invalid(format!("invalid schema format {}", schema_format)));
         }
 
         let (input, default_page_cache_size) = be_u32(input)?;
@@ -157,13 +173,18 @@ impl Header {
         let (input, user_version) = be_u32(input)?;
 
         let (input, incremental_vacuum_mode) = be_u32(input)?;
-        assert!(incremental_vacuum_mode == 0 || incremental_vacuum_mode == 1);
-        let incremental_vacuum_mode = incremental_vacuum_mode != 0;
+        let incremental_vacuum_mode = match incremental_vacuum_mode {
+            0 => false,
+            1 => true,
+            mode => return Err(invalid(format!("invalid incremental vacuum mode {}", mode))),
+        };
 
         let (input, application_id) = be_u32(input)?;
 
         let (input, zeros) = take(20usize)(input)?;
-        assert!(zeros.iter().all(|b| *b == 0));
+        if zeros.iter().any(|b| *b != 0) {
+            return Err(invalid("reserved space isn't zeroed".to_owned()));
+        }
 
         let (input, version_valid_for) = be_u32(input)?;
         let (input, sqlite_version_number) = be_u32(input)?;
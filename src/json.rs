@@ -0,0 +1,809 @@
+use crate::record::{format_real, Value};
+
+/// A parsed JSON document. Object members keep their original order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    True,
+    False,
+    Integer(i64),
+    Real(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// The columns of the `json_each` and `json_tree` table-valued functions. The last two are
+/// hidden, and so aren't included in `SELECT *`.
+pub const JSON_EACH_COLUMNS: [&str; 10] = [
+    "key", "value", "type", "atom", "id", "parent", "fullkey", "path", "json", "root",
+];
+pub const JSON_EACH_HIDDEN_COLUMNS: usize = 2;
+
+impl Json {
+    pub fn parse(input: &str) -> anyhow::Result<Self> {
+        let mut parser = JsonParser {
+            input: input.as_bytes(),
+            position: 0,
+        };
+        parser.skip_whitespace();
+        let json = parser.value()?;
+        parser.skip_whitespace();
+        if parser.position != parser.input.len() {
+            anyhow::bail!("malformed JSON");
+        }
+        Ok(json)
+    }
+
+    /// Convert a SQL value into JSON. Text is treated as a JSON string unless `is_json` is set,
+    /// in which case it is parsed as JSON.
+    pub fn from_value(value: &Value, is_json: bool) -> anyhow::Result<Self> {
+        Ok(match value {
+            Value::Null => Json::Null,
+            Value::Integer(n) => Json::Integer(*n),
+            Value::Real(f) => Json::Real(*f),
+            Value::Text(s) if is_json => Json::parse(s)?,
+            Value::Text(s) => Json::String(s.clone()),
+            Value::Blob(_) => anyhow::bail!("JSON cannot hold BLOB values"),
+        })
+    }
+
+    /// Convert into a SQL value. Arrays and objects become their JSON text.
+    pub fn to_value(&self) -> Value {
+        match self {
+            Json::Null => Value::Null,
+            Json::True => Value::Integer(1),
+            Json::False => Value::Integer(0),
+            Json::Integer(n) => Value::Integer(*n),
+            Json::Real(f) => Value::Real(*f),
+            Json::String(s) => Value::Text(s.clone()),
+            Json::Array(_) | Json::Object(_) => Value::Text(self.to_string()),
+        }
+    }
+
+    /// The type name of this element, as returned by `json_type()`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Json::Null => "null",
+            Json::True => "true",
+            Json::False => "false",
+            Json::Integer(_) => "integer",
+            Json::Real(_) => "real",
+            Json::String(_) => "text",
+            Json::Array(_) => "array",
+            Json::Object(_) => "object",
+        }
+    }
+
+    /// Look up the element at `path`, e.g. `$.a.b[2]` or `$[#-1]`. Returns `None` if there is
+    /// no such element.
+    pub fn lookup(&self, path: &str) -> anyhow::Result<Option<&Json>> {
+        let mut current = self;
+        for step in parse_path(path)? {
+            let next = match (step, current) {
+                (PathStep::Key(key), Json::Object(members)) => members
+                    .iter()
+                    .rev()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, v)| v),
+                (PathStep::Index(i), Json::Array(elements)) => elements.get(i),
+                (PathStep::FromEnd(i), Json::Array(elements)) => {
+                    elements.len().checked_sub(i).and_then(|i| elements.get(i))
+                }
+                _ => None,
+            };
+            match next {
+                Some(next) => current = next,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(current))
+    }
+}
+
+impl std::fmt::Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::True => write!(f, "true"),
+            Json::False => write!(f, "false"),
+            Json::Integer(n) => write!(f, "{}", n),
+            Json::Real(r) if r.is_finite() => write!(f, "{}", format_real(*r)),
+            Json::Real(r) => write!(f, "{}", if *r > 0.0 { "9e999" } else { "-9e999" }),
+            Json::String(s) => write_json_string(f, s),
+            Json::Array(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_json_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_json_string(f: &mut std::fmt::Formatter<'_>, s: &str) -> std::fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            '\u{08}' => write!(f, "\\b")?,
+            '\u{0c}' => write!(f, "\\f")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+struct JsonParser<'input> {
+    input: &'input [u8],
+    position: usize,
+}
+
+impl<'input> JsonParser<'input> {
+    fn skip_whitespace(&mut self) {
+        while self.position < self.input.len()
+            && matches!(self.input[self.position], b' ' | b'\t' | b'\n' | b'\r')
+        {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.position).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> anyhow::Result<()> {
+        self.skip_whitespace();
+        if self.peek() != Some(byte) {
+            anyhow::bail!("malformed JSON");
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, literal: &[u8], value: Json) -> anyhow::Result<Json> {
+        if !self.input[self.position..].starts_with(literal) {
+            anyhow::bail!("malformed JSON");
+        }
+        self.position += literal.len();
+        Ok(value)
+    }
+
+    fn value(&mut self) -> anyhow::Result<Json> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => {
+                self.position += 1;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b'}') {
+                    self.position += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() != Some(b'"') {
+                        anyhow::bail!("malformed JSON");
+                    }
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    let value = self.value()?;
+                    members.push((key, value));
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b',') => self.position += 1,
+                        Some(b'}') => {
+                            self.position += 1;
+                            return Ok(Json::Object(members));
+                        }
+                        _ => anyhow::bail!("malformed JSON"),
+                    }
+                }
+            }
+            Some(b'[') => {
+                self.position += 1;
+                let mut elements = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b']') {
+                    self.position += 1;
+                    return Ok(Json::Array(elements));
+                }
+                loop {
+                    elements.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b',') => self.position += 1,
+                        Some(b']') => {
+                            self.position += 1;
+                            return Ok(Json::Array(elements));
+                        }
+                        _ => anyhow::bail!("malformed JSON"),
+                    }
+                }
+            }
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b'n') => self.literal(b"null", Json::Null),
+            Some(b't') => self.literal(b"true", Json::True),
+            Some(b'f') => self.literal(b"false", Json::False),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => anyhow::bail!("malformed JSON"),
+        }
+    }
+
+    fn number(&mut self) -> anyhow::Result<Json> {
+        let start = self.position;
+        let mut is_real = false;
+        if self.peek() == Some(b'-') {
+            self.position += 1;
+        }
+        while let Some(b) = self.peek() {
+            match b {
+                b'0'..=b'9' => {}
+                b'.' | b'e' | b'E' | b'+' | b'-' => is_real = true,
+                _ => break,
+            }
+            self.position += 1;
+        }
+        let text = std::str::from_utf8(&self.input[start..self.position])?;
+        if !is_real {
+            if let Ok(n) = text.parse::<i64>() {
+                return Ok(Json::Integer(n));
+            }
+        }
+        text.parse::<f64>()
+            .map(Json::Real)
+            .map_err(|_| anyhow::format_err!("malformed JSON"))
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        // Skip the opening quote
+        self.position += 1;
+        let mut bytes = Vec::new();
+        loop {
+            match self.peek() {
+                None => anyhow::bail!("malformed JSON"),
+                Some(b'"') => {
+                    self.position += 1;
+                    break;
+                }
+                Some(b'\\') => {
+                    self.position += 1;
+                    let escaped = self
+                        .peek()
+                        .ok_or_else(|| anyhow::format_err!("malformed JSON"))?;
+                    self.position += 1;
+                    match escaped {
+                        b'"' => bytes.push(b'"'),
+                        b'\\' => bytes.push(b'\\'),
+                        b'/' => bytes.push(b'/'),
+                        b'b' => bytes.push(0x08),
+                        b'f' => bytes.push(0x0c),
+                        b'n' => bytes.push(b'\n'),
+                        b'r' => bytes.push(b'\r'),
+                        b't' => bytes.push(b'\t'),
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            if (0xd800..0xdc00).contains(&code)
+                                && self.input[self.position..].starts_with(b"\\u")
+                            {
+                                self.position += 2;
+                                let low = self.hex4()?;
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            let c = char::from_u32(code)
+                                .ok_or_else(|| anyhow::format_err!("malformed JSON"))?;
+                            let mut buf = [0; 4];
+                            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                        }
+                        _ => anyhow::bail!("malformed JSON"),
+                    }
+                }
+                Some(b) if b < 0x20 => anyhow::bail!("malformed JSON"),
+                Some(b) => {
+                    bytes.push(b);
+                    self.position += 1;
+                }
+            }
+        }
+        Ok(String::from_utf8(bytes)?)
+    }
+
+    fn hex4(&mut self) -> anyhow::Result<u32> {
+        let hex = self
+            .input
+            .get(self.position..self.position + 4)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u32::from_str_radix(h, 16).ok())
+            .ok_or_else(|| anyhow::format_err!("malformed JSON"))?;
+        self.position += 4;
+        Ok(hex)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum PathStep {
+    Key(String),
+    Index(usize),
+    /// `[#-N]`, counting back from the end of an array.
+    FromEnd(usize),
+}
+
+fn parse_path(path: &str) -> anyhow::Result<Vec<PathStep>> {
+    let bad_path = || anyhow::format_err!("bad JSON path: '{}'", path);
+
+    let mut rest = path.strip_prefix('$').ok_or_else(bad_path)?;
+    let mut steps = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            if let Some(quoted) = after_dot.strip_prefix('"') {
+                let end = quoted.find('"').ok_or_else(bad_path)?;
+                steps.push(PathStep::Key(quoted[..end].to_owned()));
+                rest = &quoted[end + 1..];
+            } else {
+                let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+                if end == 0 {
+                    return Err(bad_path());
+                }
+                steps.push(PathStep::Key(after_dot[..end].to_owned()));
+                rest = &after_dot[end..];
+            }
+        } else if let Some(after_bracket) = rest.strip_prefix('[') {
+            let end = after_bracket.find(']').ok_or_else(bad_path)?;
+            let index = after_bracket[..end].trim();
+            let step = if let Some(from_end) = index.strip_prefix('#') {
+                let from_end = from_end.trim();
+                if from_end.is_empty() {
+                    PathStep::FromEnd(0)
+                } else {
+                    let n = from_end
+                        .strip_prefix('-')
+                        .and_then(|n| n.trim().parse().ok())
+                        .ok_or_else(bad_path)?;
+                    PathStep::FromEnd(n)
+                }
+            } else {
+                PathStep::Index(index.parse().map_err(|_| bad_path())?)
+            };
+            steps.push(step);
+            rest = &after_bracket[end + 1..];
+        } else {
+            return Err(bad_path());
+        }
+    }
+    Ok(steps)
+}
+
+/// The path for the right-hand side of the `->` and `->>` operators, which may be a full
+/// path, an object label or an array index.
+fn operator_path(value: &Value) -> Option<String> {
+    match value {
+        Value::Text(s) if s.starts_with('$') => Some(s.clone()),
+        Value::Text(s) => Some(format!("$.\"{}\"", s)),
+        Value::Integer(n) if *n < 0 => Some(format!("$[#{}]", n)),
+        Value::Integer(n) => Some(format!("$[{}]", n)),
+        _ => None,
+    }
+}
+
+/// Evaluate the `->` operator (returning JSON text) or the `->>` operator (returning a SQL
+/// value).
+pub fn extract_operator(left: &Value, right: &Value, as_sql: bool) -> anyhow::Result<Value> {
+    let (Some(json), Some(path)) = (parse_argument(left)?, operator_path(right)) else {
+        return Ok(Value::Null);
+    };
+    Ok(match json.lookup(&path)? {
+        Some(element) if as_sql => element.to_value(),
+        Some(element) => Value::Text(element.to_string()),
+        None => Value::Null,
+    })
+}
+
+/// Parse a JSON function argument, returning `None` for NULL.
+fn parse_argument(value: &Value) -> anyhow::Result<Option<Json>> {
+    match value {
+        Value::Null => Ok(None),
+        Value::Text(s) => Ok(Some(Json::parse(s)?)),
+        Value::Integer(_) | Value::Real(_) => Ok(Some(Json::parse(&value.to_string())?)),
+        Value::Blob(_) => anyhow::bail!("JSON cannot hold BLOB values"),
+    }
+}
+
+fn path_argument(value: &Value) -> anyhow::Result<Option<&str>> {
+    match value {
+        Value::Null => Ok(None),
+        Value::Text(s) => Ok(Some(s)),
+        _ => anyhow::bail!("bad JSON path: '{}'", value),
+    }
+}
+
+/// Does a function with this name return a value with the JSON subtype? Arguments produced by
+/// these functions are embedded as JSON rather than as strings.
+pub fn returns_json(name: &str) -> bool {
+    matches!(
+        name,
+        "json" | "json_array" | "json_object" | "json_group_array" | "json_group_object"
+    )
+}
+
+/// Call a scalar JSON function. Returns `None` if `name` isn't a JSON function.
+pub fn call(name: &str, args: &[Value], json_args: &[bool]) -> Option<anyhow::Result<Value>> {
+    let result = match name {
+        "json" => json(args),
+        "json_extract" => json_extract(args),
+        "json_type" => json_type(args),
+        "json_valid" => json_valid(args),
+        "json_array_length" => json_array_length(args),
+        "json_array" => json_array(args, json_args),
+        "json_object" => json_object(args, json_args),
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn check_arg_count(
+    name: &str,
+    args: &[Value],
+    range: std::ops::RangeInclusive<usize>,
+) -> anyhow::Result<()> {
+    if !range.contains(&args.len()) {
+        anyhow::bail!("wrong number of arguments to function {}()", name);
+    }
+    Ok(())
+}
+
+fn json(args: &[Value]) -> anyhow::Result<Value> {
+    check_arg_count("json", args, 1..=1)?;
+    Ok(match parse_argument(&args[0])? {
+        Some(json) => Value::Text(json.to_string()),
+        None => Value::Null,
+    })
+}
+
+fn json_extract(args: &[Value]) -> anyhow::Result<Value> {
+    check_arg_count("json_extract", args, 2..=usize::MAX)?;
+    let Some(json) = parse_argument(&args[0])? else {
+        return Ok(Value::Null);
+    };
+
+    if args.len() == 2 {
+        let Some(path) = path_argument(&args[1])? else {
+            return Ok(Value::Null);
+        };
+        return Ok(json
+            .lookup(path)?
+            .map(Json::to_value)
+            .unwrap_or(Value::Null));
+    }
+
+    // With multiple paths, the result is an array of the extracted elements
+    let mut elements = Vec::new();
+    for path in args[1..].iter() {
+        let Some(path) = path_argument(path)? else {
+            return Ok(Value::Null);
+        };
+        elements.push(json.lookup(path)?.cloned().unwrap_or(Json::Null));
+    }
+    Ok(Value::Text(Json::Array(elements).to_string()))
+}
+
+fn json_type(args: &[Value]) -> anyhow::Result<Value> {
+    check_arg_count("json_type", args, 1..=2)?;
+    let Some(json) = parse_argument(&args[0])? else {
+        return Ok(Value::Null);
+    };
+    let path = match args.get(1) {
+        Some(path) => match path_argument(path)? {
+            Some(path) => path,
+            None => return Ok(Value::Null),
+        },
+        None => "$",
+    };
+    Ok(json
+        .lookup(path)?
+        .map(|element| Value::Text(element.type_name().to_owned()))
+        .unwrap_or(Value::Null))
+}
+
+fn json_valid(args: &[Value]) -> anyhow::Result<Value> {
+    check_arg_count("json_valid", args, 1..=1)?;
+    Ok(match &args[0] {
+        Value::Null => Value::Null,
+        Value::Text(s) => Value::Integer(Json::parse(s).is_ok() as i64),
+        Value::Integer(_) | Value::Real(_) => Value::Integer(1),
+        Value::Blob(_) => Value::Integer(0),
+    })
+}
+
+fn json_array_length(args: &[Value]) -> anyhow::Result<Value> {
+    check_arg_count("json_array_length", args, 1..=2)?;
+    let Some(json) = parse_argument(&args[0])? else {
+        return Ok(Value::Null);
+    };
+    let path = match args.get(1) {
+        Some(path) => match path_argument(path)? {
+            Some(path) => path,
+            None => return Ok(Value::Null),
+        },
+        None => "$",
+    };
+    Ok(match json.lookup(path)? {
+        Some(Json::Array(elements)) => Value::Integer(elements.len() as i64),
+        Some(_) => Value::Integer(0),
+        None => Value::Null,
+    })
+}
+
+fn json_array(args: &[Value], json_args: &[bool]) -> anyhow::Result<Value> {
+    let elements = args
+        .iter()
+        .zip(json_args)
+        .map(|(arg, is_json)| Json::from_value(arg, *is_json))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(Value::Text(Json::Array(elements).to_string()))
+}
+
+fn json_object(args: &[Value], json_args: &[bool]) -> anyhow::Result<Value> {
+    if args.len() % 2 != 0 {
+        anyhow::bail!("json_object() requires an even number of arguments");
+    }
+    let mut members = Vec::new();
+    for (pair, is_json) in args.chunks(2).zip(json_args.chunks(2)) {
+        let Value::Text(key) = &pair[0] else {
+            anyhow::bail!("json_object() labels must be TEXT");
+        };
+        members.push((key.clone(), Json::from_value(&pair[1], is_json[1])?));
+    }
+    Ok(Value::Text(Json::Object(members).to_string()))
+}
+
+/// `json_group_array(X)`, given the value of `X` for each row in the group.
+pub fn group_array(values: &[Value], is_json: bool) -> anyhow::Result<Value> {
+    let elements = values
+        .iter()
+        .map(|value| Json::from_value(value, is_json))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(Value::Text(Json::Array(elements).to_string()))
+}
+
+/// `json_group_object(K, V)`, given the key and value for each row in the group. Rows with a
+/// NULL key are skipped.
+pub fn group_object(pairs: &[(Value, Value)], is_json: bool) -> anyhow::Result<Value> {
+    let mut members = Vec::new();
+    for (key, value) in pairs.iter() {
+        match key {
+            Value::Null => continue,
+            Value::Text(key) => members.push((key.clone(), Json::from_value(value, is_json)?)),
+            _ => anyhow::bail!("json_group_object() labels must be TEXT"),
+        }
+    }
+    Ok(Value::Text(Json::Object(members).to_string()))
+}
+
+/// Produce the rows of the `json_each(X[, P])` or (when `recursive` is set) `json_tree(X[, P])`
+/// table-valued functions. Each row has the columns in [`JSON_EACH_COLUMNS`].
+pub fn each(args: &[Value], recursive: bool) -> anyhow::Result<Vec<Vec<Value>>> {
+    let name = if recursive { "json_tree" } else { "json_each" };
+    check_arg_count(name, args, 1..=2)?;
+
+    let Some(json) = parse_argument(&args[0])? else {
+        return Ok(Vec::new());
+    };
+    let root = match args.get(1) {
+        Some(path) => match path_argument(path)? {
+            Some(path) => path.to_owned(),
+            None => return Ok(Vec::new()),
+        },
+        None => "$".to_owned(),
+    };
+    let Some(start) = json.lookup(&root)? else {
+        return Ok(Vec::new());
+    };
+
+    let mut walker = EachWalker {
+        json: &args[0],
+        root: &root,
+        rows: Vec::new(),
+        next_id: 0,
+    };
+    let parent_path = parent_path(&root);
+    let root_key = if root == "$" {
+        Value::Null
+    } else {
+        last_path_key(&root)
+    };
+
+    if recursive {
+        walker.walk(start, root_key, None, &root, &parent_path);
+    } else {
+        match start {
+            Json::Array(elements) => {
+                for (i, element) in elements.iter().enumerate() {
+                    let full_key = format!("{}[{}]", root, i);
+                    walker.push(element, Value::Integer(i as i64), None, &full_key, &root);
+                }
+            }
+            Json::Object(members) => {
+                for (key, element) in members.iter() {
+                    let full_key = format!("{}.{}", root, quote_key(key));
+                    walker.push(element, Value::Text(key.clone()), None, &full_key, &root);
+                }
+            }
+            _ => {
+                walker.push(start, root_key, None, &root, &parent_path);
+            }
+        }
+    }
+
+    Ok(walker.rows)
+}
+
+struct EachWalker<'a> {
+    json: &'a Value,
+    root: &'a str,
+    rows: Vec<Vec<Value>>,
+    next_id: i64,
+}
+
+impl<'a> EachWalker<'a> {
+    fn push(
+        &mut self,
+        element: &Json,
+        key: Value,
+        parent: Option<i64>,
+        full_key: &str,
+        path: &str,
+    ) -> i64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let atom = match element {
+            Json::Array(_) | Json::Object(_) => Value::Null,
+            _ => element.to_value(),
+        };
+        self.rows.push(vec![
+            key,
+            element.to_value(),
+            Value::Text(element.type_name().to_owned()),
+            atom,
+            Value::Integer(id),
+            parent.map(Value::Integer).unwrap_or(Value::Null),
+            Value::Text(full_key.to_owned()),
+            Value::Text(path.to_owned()),
+            self.json.clone(),
+            Value::Text(self.root.to_owned()),
+        ]);
+        id
+    }
+
+    fn walk(
+        &mut self,
+        element: &Json,
+        key: Value,
+        parent: Option<i64>,
+        full_key: &str,
+        path: &str,
+    ) {
+        let id = self.push(element, key, parent, full_key, path);
+        match element {
+            Json::Array(elements) => {
+                for (i, child) in elements.iter().enumerate() {
+                    let child_key = format!("{}[{}]", full_key, i);
+                    self.walk(
+                        child,
+                        Value::Integer(i as i64),
+                        Some(id),
+                        &child_key,
+                        full_key,
+                    );
+                }
+            }
+            Json::Object(members) => {
+                for (key, child) in members.iter() {
+                    let child_key = format!("{}.{}", full_key, quote_key(key));
+                    self.walk(
+                        child,
+                        Value::Text(key.clone()),
+                        Some(id),
+                        &child_key,
+                        full_key,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Quote an object key for use in a path, if it isn't a plain identifier.
+fn quote_key(key: &str) -> String {
+    if !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        key.to_owned()
+    } else {
+        format!("\"{}\"", key)
+    }
+}
+
+/// The path of the container holding the element at `path`.
+fn parent_path(path: &str) -> String {
+    match path.rfind(['.', '[']) {
+        Some(i) if i > 0 => path[..i].to_owned(),
+        _ => "$".to_owned(),
+    }
+}
+
+/// The key of the element at `path` within its container.
+fn last_path_key(path: &str) -> Value {
+    match parse_path(path).ok().and_then(|mut steps| steps.pop()) {
+        Some(PathStep::Key(key)) => Value::Text(key),
+        Some(PathStep::Index(i)) => Value::Integer(i as i64),
+        _ => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_path, Json, PathStep};
+
+    #[test]
+    fn parse_and_print() {
+        let input = r#" { "a" : [1, 2.5, "x\"y"], "b": {"c": null, "d": true}, "e": -3e2 } "#;
+        let json = Json::parse(input).unwrap();
+        assert_eq!(
+            json.to_string(),
+            r#"{"a":[1,2.5,"x\"y"],"b":{"c":null,"d":true},"e":-300.0}"#
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(Json::parse("{\"a\":}").is_err());
+        assert!(Json::parse("[1,2").is_err());
+        assert!(Json::parse("nul").is_err());
+        assert!(Json::parse("1 2").is_err());
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            parse_path("$.a.\"b c\"[2][#-1]").unwrap(),
+            vec![
+                PathStep::Key("a".into()),
+                PathStep::Key("b c".into()),
+                PathStep::Index(2),
+                PathStep::FromEnd(1),
+            ]
+        );
+        assert!(parse_path("a.b").is_err());
+    }
+
+    #[test]
+    fn lookup() {
+        let json = Json::parse(r#"{"a": [10, 20, {"b": "c"}]}"#).unwrap();
+        assert_eq!(json.lookup("$.a[1]").unwrap(), Some(&Json::Integer(20)));
+        assert_eq!(
+            json.lookup("$.a[#-1].b").unwrap(),
+            Some(&Json::String("c".into()))
+        );
+        assert_eq!(json.lookup("$.x").unwrap(), None);
+        assert_eq!(json.lookup("$.a[5]").unwrap(), None);
+    }
+}
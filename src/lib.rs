@@ -0,0 +1,34 @@
+//! A read-only SQLite database engine.
+
+mod affinity;
+mod cache;
+mod cell;
+mod collation;
+mod connection;
+mod database;
+#[cfg(feature = "serde")]
+mod de;
+mod error;
+mod expr;
+mod function;
+mod header;
+mod json;
+mod mmap;
+mod page;
+mod pager;
+mod parser;
+mod query;
+mod record;
+mod tokenizer;
+mod varint;
+mod window;
+
+pub use connection::{Connection, FromValue, Row, Rows, Statement};
+pub use database::{
+    Database, IndexSchema, ObjectSchema, Schema, Stats, TableSchema, TriggerSchema, ViewSchema,
+};
+pub use error::CorruptError;
+pub use header::{FormatVersion, Header, TextEncoding};
+pub use pager::{FilePager, MemoryPager, MmapPager, Pager};
+pub use record::Value;
+pub use tokenizer::quote_identifier;
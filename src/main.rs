@@ -1,50 +1,125 @@
-use anyhow::{bail, Result};
-use query::Query;
-
-mod cell;
-mod database;
-mod error;
-mod header;
-mod page;
-mod query;
-mod record;
-mod varint;
-
-fn main() -> Result<()> {
-    let args = std::env::args().collect::<Vec<_>>();
-    match args.len() {
-        0 | 1 => bail!("Missing <database path> and <command>"),
-        2 => bail!("Missing <command>"),
-        _ => {}
-    }
+use std::{io::IsTerminal, process::ExitCode};
 
-    let mut file = std::fs::File::open(&args[1])?;
-    let mut db = database::Database::parse_header_and_schema(&mut file)?;
+use anyhow::Result;
+use sqlite_starter_rust::{Connection, CorruptError};
 
-    let command = &args[2];
-    match command.as_str() {
-        ".dbinfo" => {
-            println!("database page size: {}", db.header.page_size);
-            println!("number of tables: {}", db.schema.table_count());
-        }
-        ".tables" => {
-            let tables = db.schema.table_names();
-            let tables_string = tables.join(" ");
-            println!("{}", tables_string);
+mod output;
+mod shell;
+
+use output::{Mode, Output};
+use shell::Shell;
+
+/// Exit codes, following sysexits(3) where there's one that fits. SQL errors exit with 1, as
+/// they do from sqlite3.
+const EXIT_SQL_ERROR: u8 = 1;
+const EXIT_USAGE: u8 = 64;
+const EXIT_CORRUPT: u8 = 65;
+const EXIT_IO_ERROR: u8 = 74;
+
+/// A mistake in the command-line arguments.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+struct UsageError(String);
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("Error: {:#}", err);
+            exit_code(&err)
         }
-        query_str => {
-            let query = Query::parse(query_str)?;
-            let results = query.execute(&mut db, &mut file)?;
-            for row in results.iter() {
-                println!("{}", row.join("|"));
+    }
+}
+
+/// The exit code for an error, from the first cause in its chain which says what went wrong.
+fn exit_code(err: &anyhow::Error) -> ExitCode {
+    let code = err
+        .chain()
+        .find_map(|cause| {
+            if cause.is::<UsageError>() {
+                Some(EXIT_USAGE)
+            } else if cause.is::<CorruptError>() {
+                Some(EXIT_CORRUPT)
+            } else if cause.is::<std::io::Error>() {
+                Some(EXIT_IO_ERROR)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(EXIT_SQL_ERROR);
+    ExitCode::from(code)
+}
+
+fn usage(message: String) -> anyhow::Error {
+    UsageError(message).into()
+}
+
+fn run() -> Result<ExitCode> {
+    let mut args = std::env::args().skip(1);
+    let mut output = Output::default();
+    let mut mmap_size = 0;
+    let mut stats = false;
+    let mut paths_and_commands = Vec::new();
+
+    // Options can come before or after the database path, as with sqlite3
+    while let Some(arg) = args.next() {
+        let Some(option) = arg.strip_prefix('-') else {
+            paths_and_commands.push(arg);
+            continue;
+        };
+        let option = option.strip_prefix('-').unwrap_or(option);
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| usage(format!("Missing argument to -{}", option)))
+        };
+        match option {
+            // `-mmap N` reads up to N bytes of the database through a memory map
+            "mmap" => {
+                let size = value()?;
+                mmap_size = size
+                    .parse()
+                    .map_err(|_| usage(format!("invalid size for -mmap: {}", size)))?;
             }
+            "stats" => stats = true,
+            "header" => output.set_headers(true),
+            "noheader" => output.set_headers(false),
+            "nullvalue" => output.null_value = value()?,
+            "separator" => output.column_separator = value()?,
+            "newline" => output.row_separator = value()?,
+            mode => match Mode::parse(mode, None) {
+                Ok(mode) => output.set_mode_from_flag(mode),
+                Err(_) => return Err(usage(format!("unknown option: {}", arg))),
+            },
         }
     }
 
-    eprintln!(
-        "Parsed {} table pages and {} index pages",
-        db.table_pages_parsed, db.index_pages_parsed
-    );
+    let mut paths_and_commands = paths_and_commands.into_iter();
+    let Some(path) = paths_and_commands.next() else {
+        return Err(usage("Missing <database path>".to_string()));
+    };
+    let mut conn = Connection::open(path)?;
+    if mmap_size > 0 {
+        conn.prepare(&format!("PRAGMA mmap_size = {}", mmap_size))?
+            .execute(&[])?;
+    }
+    let mut shell = Shell::new(conn, output);
+    shell.stats = stats;
 
-    Ok(())
+    // Without a command, statements are read from the terminal, or a script on stdin
+    let commands = paths_and_commands.collect::<Vec<_>>();
+    if commands.is_empty() {
+        if std::io::stdin().is_terminal() {
+            shell.run_interactive()?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        // Errors in a script have already been reported, but still fail the run
+        shell.run_script(std::io::stdin().lock())?;
+        if let Some(err) = shell.script_error.as_ref() {
+            return Ok(exit_code(err));
+        }
+    }
+    for command in commands {
+        shell.run_command(&command)?;
+    }
+    Ok(ExitCode::SUCCESS)
 }
@@ -0,0 +1,91 @@
+/// A read-only memory map of a whole file.
+pub struct Mmap {
+    ptr: *const u8,
+    len: usize,
+}
+
+impl Mmap {
+    /// Map a file, or return `None` if it can't be mapped on this platform, or is empty.
+    pub fn map(file: &std::fs::File) -> anyhow::Result<Option<Self>> {
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return Ok(None);
+        }
+        Ok(sys::map(file, len).map(|ptr| Mmap { ptr, len }))
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        // SAFETY: `ptr` points to `len` readable bytes until the map is dropped
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+// SAFETY: the mapping is read-only, and is only unmapped when the `Mmap` is dropped
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        sys::unmap(self.ptr, self.len);
+    }
+}
+
+impl std::fmt::Debug for Mmap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mmap").field("len", &self.len).finish()
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::os::unix::io::AsRawFd;
+
+    use libc::{mmap, munmap, MAP_FAILED, MAP_SHARED, PROT_READ};
+
+    pub fn map(file: &std::fs::File, len: usize) -> Option<*const u8> {
+        // SAFETY: a fresh read-only mapping doesn't alias any Rust memory
+        let ptr = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                len,
+                PROT_READ,
+                MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        (ptr != MAP_FAILED).then_some(ptr as *const u8)
+    }
+
+    pub fn unmap(ptr: *const u8, len: usize) {
+        // SAFETY: the mapping was made by `map` with this length, and nothing borrows it
+        unsafe {
+            munmap(ptr as *mut libc::c_void, len);
+        }
+    }
+}
+
+/// Platforms without `mmap` fall back to reading pages from the file.
+#[cfg(not(unix))]
+mod sys {
+    pub fn map(_file: &std::fs::File, _len: usize) -> Option<*const u8> {
+        None
+    }
+
+    pub fn unmap(_ptr: *const u8, _len: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mmap;
+
+    #[test]
+    fn map_file() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db");
+        let file = std::fs::File::open(path).unwrap();
+        let Some(mmap) = Mmap::map(&file).unwrap() else {
+            return;
+        };
+        assert_eq!(mmap.as_slice(), std::fs::read(path).unwrap());
+    }
+}
@@ -0,0 +1,705 @@
+use std::io::Write;
+
+use anyhow::{bail, Result};
+use sqlite_starter_rust::{quote_identifier, Value};
+
+/// How result rows are printed, as chosen by `.mode`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mode {
+    /// Values separated by the column separator.
+    List,
+    /// RFC 4180 CSV.
+    Csv,
+    /// A JSON array of objects.
+    Json,
+    /// A JSON object per line.
+    Ndjson,
+    /// A `name = value` line per column.
+    Line,
+    /// Left-aligned columns.
+    Column,
+    /// An ASCII-art table.
+    Table,
+    /// A table drawn with box-drawing characters.
+    Box,
+    Markdown,
+    /// HTML table rows.
+    Html,
+    /// Values separated by tabs.
+    Tabs,
+    /// `INSERT` statements into the given table.
+    Insert(String),
+    /// Values as SQL literals.
+    Quote,
+}
+
+impl Mode {
+    /// Parse the arguments of `.mode`.
+    pub fn parse(name: &str, table: Option<&str>) -> Result<Self> {
+        let mode = match name {
+            "list" => Mode::List,
+            "csv" => Mode::Csv,
+            "json" => Mode::Json,
+            "ndjson" => Mode::Ndjson,
+            "line" | "lines" => Mode::Line,
+            "column" | "columns" => Mode::Column,
+            "table" => Mode::Table,
+            "box" => Mode::Box,
+            "markdown" => Mode::Markdown,
+            "html" => Mode::Html,
+            "tabs" => Mode::Tabs,
+            "insert" => Mode::Insert(table.unwrap_or("table").to_owned()),
+            "quote" => Mode::Quote,
+            _ => bail!(
+                "mode should be one of: box column csv html insert json line list markdown \
+                 ndjson quote table tabs"
+            ),
+        };
+        if table.is_some() && !matches!(mode, Mode::Insert(_)) {
+            bail!("only insert mode takes a table name");
+        }
+        Ok(mode)
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Mode::List => "list".to_owned(),
+            Mode::Csv => "csv".to_owned(),
+            Mode::Json => "json".to_owned(),
+            Mode::Ndjson => "ndjson".to_owned(),
+            Mode::Line => "line".to_owned(),
+            Mode::Column => "column".to_owned(),
+            Mode::Table => "table".to_owned(),
+            Mode::Box => "box".to_owned(),
+            Mode::Markdown => "markdown".to_owned(),
+            Mode::Html => "html".to_owned(),
+            Mode::Tabs => "tabs".to_owned(),
+            Mode::Insert(table) => format!("insert {}", table),
+            Mode::Quote => "quote".to_owned(),
+        }
+    }
+}
+
+/// The settings for printing result rows.
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub mode: Mode,
+    pub headers: bool,
+    /// Whether headers were turned on or off explicitly, rather than by `.mode column`.
+    pub headers_set: bool,
+    /// What NULL is printed as, in the modes which print values as they are.
+    pub null_value: String,
+    pub column_separator: String,
+    pub row_separator: String,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Output {
+            mode: Mode::List,
+            headers: false,
+            headers_set: false,
+            null_value: String::new(),
+            column_separator: "|".to_owned(),
+            row_separator: "\n".to_owned(),
+        }
+    }
+}
+
+/// The box-drawing characters of a table's borders: its left, middle and right joints, and its
+/// horizontal line.
+type Border = [&'static str; 4];
+
+const TABLE_BORDERS: [Border; 3] = [["+", "+", "+", "-"]; 3];
+const BOX_BORDERS: [Border; 3] = [
+    ["┌", "┬", "┐", "─"],
+    ["├", "┼", "┤", "─"],
+    ["└", "┴", "┘", "─"],
+];
+/// The narrowest column names are padded to in `line` mode.
+const MIN_LINE_NAME_WIDTH: usize = 5;
+
+const TABLE_EDGE: &str = "|";
+const BOX_EDGE: &str = "│";
+
+impl Output {
+    /// Switch mode, with sqlite3's side effects: the separators are reset for the modes which
+    /// use them, and `column` mode turns headers on unless they've been turned off.
+    pub fn set_mode(&mut self, mode: Mode) {
+        match mode {
+            Mode::List => self.set_separators("|", "\n"),
+            Mode::Csv => self.set_separators(",", "\r\n"),
+            Mode::Tabs => self.set_separators("\t", "\n"),
+            Mode::Quote => self.set_separators(",", "\n"),
+            Mode::Column if !self.headers_set => self.headers = true,
+            _ => {}
+        }
+        self.mode = mode;
+    }
+
+    /// Switch mode from a command-line flag such as `-csv`. As in sqlite3, this only sets the
+    /// column separator, so CSV rows end with `\n` rather than `\r\n`, and headers are left as
+    /// they are.
+    pub fn set_mode_from_flag(&mut self, mode: Mode) {
+        match mode {
+            Mode::List => self.column_separator = "|".to_owned(),
+            Mode::Csv | Mode::Quote => self.column_separator = ",".to_owned(),
+            Mode::Tabs => self.column_separator = "\t".to_owned(),
+            _ => {}
+        }
+        self.mode = mode;
+    }
+
+    fn set_separators(&mut self, column: &str, row: &str) {
+        self.column_separator = column.to_owned();
+        self.row_separator = row.to_owned();
+    }
+
+    pub fn set_headers(&mut self, headers: bool) {
+        self.headers = headers;
+        self.headers_set = true;
+    }
+
+    /// Print a statement's result rows. Nothing is printed if there are none.
+    pub fn print_rows<I>(&self, out: &mut dyn Write, column_names: &[String], rows: I) -> Result<()>
+    where
+        I: Iterator<Item = Result<Vec<Value>>>,
+    {
+        if matches!(
+            self.mode,
+            Mode::Column | Mode::Table | Mode::Box | Mode::Markdown
+        ) {
+            let rows = rows.collect::<Result<Vec<_>>>()?;
+            if !rows.is_empty() {
+                self.print_columns(out, column_names, &rows)?;
+            }
+            return Ok(());
+        }
+
+        let mut count = 0;
+        for row in rows {
+            let row = row?;
+            if count == 0 && self.mode == Mode::Json {
+                write!(out, "[")?;
+            }
+            if count == 0 && self.headers {
+                self.print_headers(out, column_names)?;
+            }
+            self.print_row(out, column_names, &row, count)?;
+            count += 1;
+        }
+
+        if self.mode == Mode::Json && count > 0 {
+            writeln!(out, "]")?;
+        }
+        Ok(())
+    }
+
+    /// The text a value is printed as in the modes which print values as they are.
+    fn text(&self, value: &Value) -> String {
+        match value {
+            Value::Null => self.null_value.clone(),
+            value => value.to_string(),
+        }
+    }
+
+    fn print_headers(&self, out: &mut dyn Write, column_names: &[String]) -> Result<()> {
+        let (sep, row_sep) = (&self.column_separator, &self.row_separator);
+        match &self.mode {
+            Mode::List | Mode::Tabs => write!(out, "{}{}", column_names.join(sep), row_sep)?,
+            Mode::Csv => {
+                let names = column_names.iter().map(|n| csv_quote(n, sep));
+                write!(out, "{}{}", names.collect::<Vec<_>>().join(sep), row_sep)?;
+            }
+            Mode::Quote => {
+                let names = column_names
+                    .iter()
+                    .map(|n| Value::Text(n.clone()).to_sql_literal());
+                write!(out, "{}{}", names.collect::<Vec<_>>().join(sep), row_sep)?;
+            }
+            Mode::Html => {
+                write!(out, "<TR>")?;
+                for name in column_names {
+                    writeln!(out, "<TH>{}</TH>", html_escape(name))?;
+                }
+                writeln!(out, "</TR>")?;
+            }
+            // Other modes name the columns on each row, or don't name them
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn print_row(
+        &self,
+        out: &mut dyn Write,
+        column_names: &[String],
+        row: &[Value],
+        index: usize,
+    ) -> Result<()> {
+        let (sep, row_sep) = (&self.column_separator, &self.row_separator);
+        match &self.mode {
+            Mode::List | Mode::Tabs => {
+                let values = row.iter().map(|v| self.text(v)).collect::<Vec<_>>();
+                write!(out, "{}{}", values.join(sep), row_sep)?;
+            }
+            Mode::Csv => {
+                let values = row
+                    .iter()
+                    .map(|v| match v {
+                        Value::Text(s) => csv_quote(s, sep),
+                        Value::Blob(_) => csv_quote(&v.to_string(), sep),
+                        v => self.text(v),
+                    })
+                    .collect::<Vec<_>>();
+                write!(out, "{}{}", values.join(sep), row_sep)?;
+            }
+            Mode::Quote => {
+                let values = row.iter().map(|v| v.to_sql_literal()).collect::<Vec<_>>();
+                write!(out, "{}{}", values.join(sep), row_sep)?;
+            }
+            Mode::Json | Mode::Ndjson => {
+                if self.mode == Mode::Json && index > 0 {
+                    writeln!(out, ",")?;
+                }
+                let fields = column_names
+                    .iter()
+                    .zip(row)
+                    .map(|(name, value)| format!("{}:{}", json_string(name), json_value(value)))
+                    .collect::<Vec<_>>();
+                write!(out, "{{{}}}", fields.join(","))?;
+                if self.mode == Mode::Ndjson {
+                    writeln!(out)?;
+                }
+            }
+            Mode::Line => {
+                // Rows are separated by a blank line
+                if index > 0 {
+                    write!(out, "{}", row_sep)?;
+                }
+                let width = column_names
+                    .iter()
+                    .map(|n| n.chars().count())
+                    .fold(MIN_LINE_NAME_WIDTH, usize::max);
+                for (name, value) in column_names.iter().zip(row) {
+                    write!(
+                        out,
+                        "{:>width$} = {}{}",
+                        name,
+                        self.text(value),
+                        row_sep,
+                        width = width
+                    )?;
+                }
+            }
+            Mode::Html => {
+                write!(out, "<TR>")?;
+                for value in row {
+                    writeln!(out, "<TD>{}</TD>", html_escape(&self.text(value)))?;
+                }
+                writeln!(out, "</TR>")?;
+            }
+            Mode::Insert(table) => {
+                write!(out, "INSERT INTO {}", quote_identifier(table))?;
+                if self.headers {
+                    let names = column_names.iter().map(|n| quote_identifier(n));
+                    write!(out, "({})", names.collect::<Vec<_>>().join(","))?;
+                }
+                let values = row.iter().map(|v| sql_literal(v, true)).collect::<Vec<_>>();
+                writeln!(out, " VALUES({});", values.join(","))?;
+            }
+            Mode::Column | Mode::Table | Mode::Box | Mode::Markdown => {
+                unreachable!("columns are printed once all the rows are read")
+            }
+        }
+        Ok(())
+    }
+
+    /// Print rows in aligned columns. Values with newlines span several lines.
+    fn print_columns(
+        &self,
+        out: &mut dyn Write,
+        column_names: &[String],
+        rows: &[Vec<Value>],
+    ) -> Result<()> {
+        let cells = rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|v| {
+                        let text = expand_tabs(&self.text(v));
+                        text.split('\n').map(str::to_owned).collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let multi_line = cells.iter().flatten().any(|lines| lines.len() > 1);
+        let headers = self.headers || self.mode != Mode::Column;
+
+        let mut widths = column_names
+            .iter()
+            .map(|n| if headers { n.chars().count() } else { 0 })
+            .collect::<Vec<_>>();
+        for lines in cells.iter().flat_map(|row| row.iter().enumerate()) {
+            let (i, lines) = lines;
+            for line in lines {
+                widths[i] = widths[i].max(line.chars().count());
+            }
+        }
+
+        let pad = |text: &str, width: usize| {
+            format!("{}{}", text, " ".repeat(width - text.chars().count()))
+        };
+        let center = |text: &str, width: usize| {
+            let space = width - text.chars().count();
+            format!(
+                "{}{}{}",
+                " ".repeat(space / 2),
+                text,
+                " ".repeat(space - space / 2)
+            )
+        };
+        // Each line of a row, with the cells of the row which have fewer lines left blank
+        let row_lines = |row: &[Vec<String>]| {
+            let height = row.iter().map(|lines| lines.len()).max().unwrap_or(1);
+            (0..height)
+                .map(|line| {
+                    row.iter()
+                        .enumerate()
+                        .map(|(i, lines)| pad(lines.get(line).map_or("", |l| l), widths[i]))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        if self.mode == Mode::Column {
+            if headers {
+                let names = column_names.iter().enumerate();
+                let names = names.map(|(i, n)| pad(n, widths[i])).collect::<Vec<_>>();
+                writeln!(out, "{}", names.join("  "))?;
+                let dashes = widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>();
+                writeln!(out, "{}", dashes.join("  "))?;
+            }
+            for (i, row) in cells.iter().enumerate() {
+                if multi_line && i > 0 {
+                    writeln!(out)?;
+                }
+                for line in row_lines(row) {
+                    writeln!(out, "{}", line.join("  "))?;
+                }
+            }
+            return Ok(());
+        }
+
+        let (borders, edge) = match self.mode {
+            Mode::Box => (BOX_BORDERS, BOX_EDGE),
+            _ => (TABLE_BORDERS, TABLE_EDGE),
+        };
+        let border = |[left, middle, right, line]: Border| {
+            let lines = widths.iter().map(|w| line.repeat(w + 2));
+            format!(
+                "{}{}{}",
+                left,
+                lines.collect::<Vec<_>>().join(middle),
+                right
+            )
+        };
+        let cells_line = |cells: Vec<String>| {
+            let inner = format!(" {} ", edge);
+            format!("{} {} {}", edge, cells.join(&inner), edge)
+        };
+
+        let names = column_names.iter().enumerate();
+        let names = names.map(|(i, n)| center(n, widths[i])).collect::<Vec<_>>();
+        if self.mode == Mode::Markdown {
+            writeln!(out, "{}", cells_line(names))?;
+            writeln!(out, "{}", border(["|", "|", "|", "-"]))?;
+            for row in cells.iter() {
+                for line in row_lines(row) {
+                    writeln!(out, "{}", cells_line(line))?;
+                }
+            }
+            return Ok(());
+        }
+
+        writeln!(out, "{}", border(borders[0]))?;
+        writeln!(out, "{}", cells_line(names))?;
+        writeln!(out, "{}", border(borders[1]))?;
+        for (i, row) in cells.iter().enumerate() {
+            if multi_line && i > 0 {
+                writeln!(out, "{}", border(borders[1]))?;
+            }
+            for line in row_lines(row) {
+                writeln!(out, "{}", cells_line(line))?;
+            }
+        }
+        writeln!(out, "{}", border(borders[2]))?;
+        Ok(())
+    }
+}
+
+/// Quote a CSV field if it's empty, or has spaces, quotes, control characters, non-ASCII
+/// characters or the separator, as sqlite3 does.
+fn csv_quote(text: &str, separator: &str) -> String {
+    let needs_quotes = text.is_empty()
+        || text.contains(separator)
+        || text
+            .chars()
+            .any(|c| c <= ' ' || c == '"' || c == '\'' || c >= '\x7f');
+    if needs_quotes {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_owned()
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            '\u{8}' => json.push_str("\\b"),
+            '\u{c}' => json.push_str("\\f"),
+            c if c < ' ' => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn json_value(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_owned(),
+        Value::Real(f) if !f.is_finite() => "null".to_owned(),
+        Value::Integer(_) | Value::Real(_) => value.to_string(),
+        Value::Text(s) => json_string(s),
+        Value::Blob(_) => json_string(&value.to_string()),
+    }
+}
+
+/// A value as an SQL literal which reads back as the same value, as insert mode and `.dump`
+/// write it. Text with control characters is written with `unistr()` escapes so that each
+/// statement is on one line, unless `escape_controls` is off.
+pub fn sql_literal(value: &Value, escape_controls: bool) -> String {
+    match value {
+        Value::Real(f) if f.is_finite() => exact_real(*f),
+        Value::Text(s) if escape_controls && s.chars().any(|c| c < ' ') => {
+            let mut literal = String::from("unistr('");
+            for c in s.chars() {
+                match c {
+                    '\'' => literal.push_str("''"),
+                    '\\' => literal.push_str("\\\\"),
+                    c if c < ' ' => literal.push_str(&format!("\\u{:04x}", c as u32)),
+                    c => literal.push(c),
+                }
+            }
+            literal.push_str("')");
+            literal
+        }
+        Value::Blob(s) => format!(
+            "X'{}'",
+            s.iter().map(|b| format!("{:02x}", b)).collect::<String>()
+        ),
+        value => value.to_sql_literal(),
+    }
+}
+
+/// A real with as many digits as it needs to be read back exactly, rather than the 15
+/// significant digits it's normally shown with.
+fn exact_real(f: f64) -> String {
+    if f == 0.0 {
+        return "0.0".to_owned();
+    }
+    let scientific = format!("{:e}", f);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent = exponent.parse::<i32>().unwrap();
+    let with_point = |s: String| if s.contains('.') { s } else { s + ".0" };
+    if (-4..20).contains(&exponent) {
+        with_point(f.to_string())
+    } else {
+        format!(
+            "{}e{}{:02}",
+            with_point(mantissa.to_owned()),
+            if exponent < 0 { '-' } else { '+' },
+            exponent.abs()
+        )
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    let mut html = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '&' => html.push_str("&amp;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+    html
+}
+
+/// Replace tabs with spaces up to the next multiple of 8 characters on their line.
+fn expand_tabs(text: &str) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut column = 0;
+    for c in text.chars() {
+        match c {
+            '\t' => {
+                let spaces = 8 - column % 8;
+                expanded.push_str(&" ".repeat(spaces));
+                column += spaces;
+            }
+            '\n' => {
+                expanded.push(c);
+                column = 0;
+            }
+            c => {
+                expanded.push(c);
+                column += 1;
+            }
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlite_starter_rust::Value;
+
+    use super::{sql_literal, Mode, Output};
+
+    fn render(mode: &str, headers: bool, rows: &[Vec<Value>]) -> String {
+        let mut output = Output::default();
+        let (name, table) = match mode.split_once(' ') {
+            Some((name, table)) => (name, Some(table)),
+            None => (mode, None),
+        };
+        output.set_mode(Mode::parse(name, table).unwrap());
+        output.set_headers(headers);
+        let names = ["a".to_owned(), "text".to_owned()];
+        let mut out = Vec::new();
+        let rows = rows.iter().cloned().map(Ok);
+        output.print_rows(&mut out, &names, rows).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn rows() -> Vec<Vec<Value>> {
+        vec![
+            vec![Value::Integer(1), Value::Text("a|b, \"c\"".into())],
+            vec![Value::Null, Value::Real(2.0)],
+        ]
+    }
+
+    #[test]
+    fn delimited_modes() {
+        assert_eq!(
+            render("list", true, &rows()),
+            "a|text\n1|a|b, \"c\"\n|2.0\n"
+        );
+        assert_eq!(
+            render("csv", false, &rows()),
+            "1,\"a|b, \"\"c\"\"\"\r\n,2.0\r\n"
+        );
+        assert_eq!(render("tabs", false, &rows()), "1\ta|b, \"c\"\n\t2.0\n");
+        assert_eq!(
+            render("quote", false, &rows()),
+            "1,'a|b, \"c\"'\nNULL,2.0\n"
+        );
+        assert_eq!(
+            render("insert t", true, &rows()),
+            "INSERT INTO t(a,text) VALUES(1,'a|b, \"c\"');\n\
+             INSERT INTO t(a,text) VALUES(NULL,2.0);\n"
+        );
+        assert_eq!(render("list", true, &[]), "");
+    }
+
+    #[test]
+    fn structured_modes() {
+        assert_eq!(
+            render("json", false, &rows()),
+            "[{\"a\":1,\"text\":\"a|b, \\\"c\\\"\"},\n{\"a\":null,\"text\":2.0}]\n"
+        );
+        assert_eq!(
+            render("ndjson", false, &rows()),
+            "{\"a\":1,\"text\":\"a|b, \\\"c\\\"\"}\n{\"a\":null,\"text\":2.0}\n"
+        );
+        assert_eq!(
+            render("line", false, &rows()),
+            "    a = 1\n text = a|b, \"c\"\n\n    a = \n text = 2.0\n"
+        );
+        assert_eq!(
+            render("html", true, &rows()[..1]),
+            "<TR><TH>a</TH>\n<TH>text</TH>\n</TR>\n\
+             <TR><TD>1</TD>\n<TD>a|b, &quot;c&quot;</TD>\n</TR>\n"
+        );
+    }
+
+    #[test]
+    fn column_modes() {
+        assert_eq!(
+            render("column", true, &rows()),
+            "a  text    \n\
+             -  --------\n\
+             1  a|b, \"c\"\n\
+             \x20  2.0     \n"
+        );
+        assert_eq!(
+            render("table", false, &rows()),
+            "+---+----------+\n\
+             | a |   text   |\n\
+             +---+----------+\n\
+             | 1 | a|b, \"c\" |\n\
+             |   | 2.0      |\n\
+             +---+----------+\n"
+        );
+        assert_eq!(
+            render(
+                "box",
+                false,
+                &[vec![Value::Integer(1), Value::Text("x\ny".into())]]
+            ),
+            "┌───┬──────┐\n\
+             │ a │ text │\n\
+             ├───┼──────┤\n\
+             │ 1 │ x    │\n\
+             │   │ y    │\n\
+             └───┴──────┘\n"
+        );
+        assert_eq!(
+            render("markdown", false, &rows()),
+            "| a |   text   |\n\
+             |---|----------|\n\
+             | 1 | a|b, \"c\" |\n\
+             |   | 2.0      |\n"
+        );
+    }
+
+    #[test]
+    fn exact_sql_literals() {
+        let literal = |value| sql_literal(&value, true);
+        assert_eq!(literal(Value::Real(0.1)), "0.1");
+        assert_eq!(
+            literal(Value::Real(123456789.12345679)),
+            "123456789.12345679"
+        );
+        assert_eq!(literal(Value::Real(1e300)), "1.0e+300");
+        assert_eq!(literal(Value::Real(-2.5e-7)), "-2.5e-07");
+        assert_eq!(literal(Value::Real(-0.0)), "0.0");
+        assert_eq!(literal(Value::Blob(b"\x01A".to_vec())), "X'0141'");
+        assert_eq!(literal(Value::Text("a\\b'".into())), "'a\\b'''");
+        assert_eq!(
+            literal(Value::Text("a\n\\'".into())),
+            "unistr('a\\u000a\\\\''')"
+        );
+        assert_eq!(sql_literal(&Value::Text("\t".into()), false), "'\t'");
+    }
+}
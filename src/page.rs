@@ -1,15 +1,20 @@
 use nom::{
     bytes::complete::take,
+    error::ErrorKind,
     multi::count,
     number::complete::{be_u16, be_u32, u8},
     IResult,
 };
 
-use crate::cell::Cell;
+use crate::{
+    cell::{Cell, Overflow, OverflowPayloads},
+    header::HEADER_SIZE,
+};
 
-pub struct Page {
+#[allow(dead_code)]
+pub struct Page<'a> {
     pub ty: PageType,
-    pub cells: Vec<Cell>,
+    pub cells: Vec<Cell<'a>>,
     pub rightmost_pointer: Option<usize>,
 }
 
@@ -63,89 +68,117 @@ impl TryFrom<u8> for PageType {
     }
 }
 
-impl Page {
-    pub fn parse<'input>(
-        input: &'input [u8],
+/// A B-tree page's header, and the cells it points to.
+struct PageHeader<'a> {
+    ty: BTreePageType,
+    rightmost_pointer: Option<usize>,
+    /// Each cell, up to the end of the page, in key order.
+    cells: Vec<&'a [u8]>,
+}
+
+impl<'a> Page<'a> {
+    /// Parse a B-tree page. The payloads of cells which continue on overflow pages are taken
+    /// from `overflow_payloads`, which must hold each of the page's [`Page::overflows`].
+    pub fn parse(
+        input: &'a [u8],
         is_first_page: bool,
-        column_names: &[&str],
-        column_indices: &[usize],
+        rowid_alias: Option<usize>,
         usable_page_size: usize,
-    ) -> IResult<&'input [u8], Self> {
-        let (input, page_type) = u8(input)?;
-        let page_type = PageType::try_from(page_type).expect("invalid page type");
-
-        let (input, cells, rightmost_pointer) = match &page_type {
-            PageType::BTree(b_tree_page_type) => {
-                // Header
-                let (input, _first_freelock) = be_u16(input)?;
-                let (input, cell_count) = be_u16(input)?;
-
-                let (input, cell_content_offset) = be_u16(input)?;
-                let _cell_content_offset = if cell_content_offset == 0 {
-                    65536
-                } else {
-                    cell_content_offset as usize
-                };
-
-                let (input, _num_fragmented_free_bytes) = u8(input)?;
-
-                let (input, rightmost_pointer) = if matches!(
-                    b_tree_page_type,
-                    BTreePageType::IndexInterior | BTreePageType::TableInterior
-                ) {
-                    let (input, rightmost_pointer) = be_u32(input)?;
-                    (input, Some(rightmost_pointer as usize))
-                } else {
-                    (input, None)
-                };
-
-                // Cell pointer array
-                let (input, mut cell_pointers) = count(be_u16, cell_count as usize)(input)?;
-                cell_pointers.sort_unstable();
-
-                // Calculate our current position in the page
-                let mut position = if is_first_page { 100 } else { 0 }
-                    + if matches!(
-                        b_tree_page_type,
-                        BTreePageType::IndexInterior | BTreePageType::TableInterior
-                    ) {
-                        12
-                    } else {
-                        8
-                    }
-                    + cell_count as usize * 2;
-
-                // Read cells
-                let mut rest = input;
-                let mut cells = Vec::with_capacity(cell_count as usize);
-                for cell_offset in cell_pointers.iter() {
-                    let (remainder, _) = take(*cell_offset as usize - position)(rest)?;
-                    position = *cell_offset as usize;
-                    rest = remainder;
-                    let (remainder, cell) = Cell::parse(
-                        rest,
-                        *b_tree_page_type,
-                        usable_page_size,
-                        column_names,
-                        column_indices,
-                    )?;
-                    cells.push(cell);
-                    let cell_size = rest.len() - remainder.len();
-                    rest = remainder;
-                    position += cell_size;
-                }
-
-                (input, cells, rightmost_pointer)
-            }
-            _ => todo!("non b-tree page"),
-        };
+        overflow_payloads: &'a OverflowPayloads,
+    ) -> IResult<&'a [u8], Self> {
+        let (input, header) = PageHeader::parse(input, is_first_page)?;
+        let mut cells = Vec::with_capacity(header.cells.len());
+        for cell_input in header.cells {
+            let (_, cell) = Cell::parse(
+                cell_input,
+                header.ty,
+                usable_page_size,
+                rowid_alias,
+                overflow_payloads,
+            )?;
+            cells.push(cell);
+        }
 
         Ok((
             input,
             Page {
-                ty: page_type,
+                ty: PageType::BTree(header.ty),
                 cells,
+                rightmost_pointer: header.rightmost_pointer,
+            },
+        ))
+    }
+
+    /// The cells of a B-tree page whose payloads continue on overflow pages.
+    pub fn overflows(
+        input: &'a [u8],
+        is_first_page: bool,
+        usable_page_size: usize,
+    ) -> IResult<&'a [u8], Vec<Overflow<'a>>> {
+        let (input, header) = PageHeader::parse(input, is_first_page)?;
+        let mut overflows = Vec::new();
+        for cell_input in header.cells {
+            let (_, overflow) = Cell::overflow(cell_input, header.ty, usable_page_size)?;
+            overflows.extend(overflow);
+        }
+        Ok((input, overflows))
+    }
+}
+
+impl<'a> PageHeader<'a> {
+    fn parse(input: &'a [u8], is_first_page: bool) -> IResult<&'a [u8], Self> {
+        let page = input;
+        let malformed = || nom::Err::Failure(nom::error::Error::new(page, ErrorKind::Verify));
+        let (input, page_type) = u8(input)?;
+        // Only B-tree pages are ever walked to
+        let Ok(PageType::BTree(ty)) = PageType::try_from(page_type) else {
+            return Err(malformed());
+        };
+
+        // Header
+        let (input, _first_freelock) = be_u16(input)?;
+        let (input, cell_count) = be_u16(input)?;
+
+        let (input, cell_content_offset) = be_u16(input)?;
+        let _cell_content_offset = if cell_content_offset == 0 {
+            65536
+        } else {
+            cell_content_offset as usize
+        };
+
+        let (input, _num_fragmented_free_bytes) = u8(input)?;
+
+        let (input, rightmost_pointer) = if matches!(
+            ty,
+            BTreePageType::IndexInterior | BTreePageType::TableInterior
+        ) {
+            let (input, rightmost_pointer) = be_u32(input)?;
+            (input, Some(rightmost_pointer as usize))
+        } else {
+            (input, None)
+        };
+
+        // Cell pointer array
+        let (input, cell_pointers) = count(be_u16, cell_count as usize)(input)?;
+
+        // Cells are in cell pointer array order, which is key order. Offsets are from the
+        // start of the page, which includes the file header on the first page.
+        let header_size = if is_first_page { HEADER_SIZE } else { 0 };
+        let mut cells = Vec::with_capacity(cell_count as usize);
+        for cell_offset in cell_pointers.iter() {
+            let cell_offset = (*cell_offset as usize)
+                .checked_sub(header_size)
+                .ok_or_else(malformed)?;
+            let (cell_input, _) = take(cell_offset)(page)?;
+            cells.push(cell_input);
+        }
+
+        Ok((
+            input,
+            PageHeader {
+                ty,
                 rightmost_pointer,
+                cells,
             },
         ))
     }
@@ -0,0 +1,151 @@
+use std::borrow::Cow;
+
+use crate::{error::CorruptError, mmap::Mmap};
+
+/// Where a database's pages come from. Storage which transforms pages, such as compressed or
+/// encrypted files, can be supported by implementing this. Pagers must be `Send`, so that a
+/// connection can be moved to another thread.
+pub trait Pager: Send {
+    /// Read a page, numbered from 1, when pages are `page_size` bytes. Reading page 1 with a
+    /// page size of the header's size reads the header. Pagers which hold the whole database
+    /// in memory can lend out the page rather than copying it.
+    fn read_page(&mut self, page_index: usize, page_size: usize) -> anyhow::Result<Cow<'_, [u8]>>;
+
+    /// The most bytes of the database which are read through a memory map.
+    fn mmap_size(&self) -> usize {
+        0
+    }
+
+    /// Set the most bytes of the database which are read through a memory map, as with
+    /// `PRAGMA mmap_size`. Pagers which can't map the database ignore this.
+    fn set_mmap_size(&mut self, _size: usize) {}
+}
+
+/// Reads pages from a file, or anything else which can be read and seeked.
+pub struct FilePager<R> {
+    file: R,
+}
+
+impl<R> FilePager<R> {
+    pub fn new(file: R) -> Self {
+        FilePager { file }
+    }
+}
+
+impl<R> Pager for FilePager<R>
+where
+    R: std::io::Read + std::io::Seek + Send,
+{
+    fn read_page(&mut self, page_index: usize, page_size: usize) -> anyhow::Result<Cow<'_, [u8]>> {
+        let mut page = vec![0; page_size];
+        self.file.seek(std::io::SeekFrom::Start(
+            (page_size * (page_index - 1)) as u64,
+        ))?;
+        self.file
+            .read_exact(&mut page)
+            .map_err(|err| match err.kind() {
+                std::io::ErrorKind::UnexpectedEof => past_the_end(page_index),
+                _ => err.into(),
+            })?;
+        Ok(Cow::Owned(page))
+    }
+}
+
+/// Lends out pages of a database held in memory.
+pub struct MemoryPager {
+    data: Vec<u8>,
+}
+
+impl MemoryPager {
+    pub fn new(data: Vec<u8>) -> Self {
+        MemoryPager { data }
+    }
+}
+
+impl Pager for MemoryPager {
+    fn read_page(&mut self, page_index: usize, page_size: usize) -> anyhow::Result<Cow<'_, [u8]>> {
+        page_slice(&self.data, page_index, page_size).map(Cow::Borrowed)
+    }
+}
+
+/// Lends out pages within the first `mmap_size` bytes of a file from a memory map of it, and
+/// reads the rest from the file. The file is mapped when `mmap_size` is first set, and is read
+/// as usual if it can't be mapped.
+pub struct MmapPager {
+    file: FilePager<std::fs::File>,
+    mmap: Option<Mmap>,
+    mmap_size: usize,
+}
+
+impl MmapPager {
+    pub fn new(file: std::fs::File) -> Self {
+        MmapPager {
+            file: FilePager::new(file),
+            mmap: None,
+            mmap_size: 0,
+        }
+    }
+}
+
+impl Pager for MmapPager {
+    fn read_page(&mut self, page_index: usize, page_size: usize) -> anyhow::Result<Cow<'_, [u8]>> {
+        let page_end = page_size * page_index;
+        match self.mmap.as_ref() {
+            Some(mmap) if page_end <= mmap.as_slice().len().min(self.mmap_size) => {
+                page_slice(mmap.as_slice(), page_index, page_size).map(Cow::Borrowed)
+            }
+            _ => self.file.read_page(page_index, page_size),
+        }
+    }
+
+    fn mmap_size(&self) -> usize {
+        self.mmap_size
+    }
+
+    fn set_mmap_size(&mut self, size: usize) {
+        self.mmap_size = size;
+        if size > 0 && self.mmap.is_none() {
+            // Unmappable files are read as usual
+            self.mmap = Mmap::map(&self.file.file).ok().flatten();
+        }
+    }
+}
+
+/// Borrow a page from a whole database.
+fn page_slice(data: &[u8], page_index: usize, page_size: usize) -> anyhow::Result<&[u8]> {
+    let page_start = page_size * (page_index - 1);
+    data.get(page_start..page_start + page_size)
+        .ok_or_else(|| past_the_end(page_index))
+}
+
+fn past_the_end(page_index: usize) -> anyhow::Error {
+    CorruptError::Malformed(format!(
+        "page {} is past the end of the database",
+        page_index
+    ))
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FilePager, MemoryPager, MmapPager, Pager};
+
+    #[test]
+    fn pagers_agree() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db");
+        let data = std::fs::read(path).unwrap();
+        let page = |pager: &mut dyn Pager| pager.read_page(2, 4096).unwrap().into_owned();
+
+        let expected = data[4096..8192].to_vec();
+        let mut file = FilePager::new(std::io::Cursor::new(data.clone()));
+        assert_eq!(page(&mut file), expected);
+        let mut memory = MemoryPager::new(data);
+        assert_eq!(page(&mut memory), expected);
+        assert!(memory.read_page(100, 4096).is_err());
+
+        let mut mmap = MmapPager::new(std::fs::File::open(path).unwrap());
+        assert_eq!(page(&mut mmap), expected);
+        mmap.set_mmap_size(1 << 20);
+        assert_eq!(page(&mut mmap), expected);
+    }
+}
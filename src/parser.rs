@@ -0,0 +1,1689 @@
+use crate::{
+    affinity::Affinity,
+    expr::{BinaryOperator, Expr, UnaryOperator},
+    query::{
+        readonly, Column, ColumnDefinition, ColumnPrimaryKey, CreateIndexQuery, CreateTableQuery,
+        CreateTriggerQuery, CreateViewQuery, ForeignKey, GeneratedColumn, IndexedColumn, Join,
+        JoinKind, OrderingTerm, PragmaQuery, Query, SelectQuery, TableConstraint, TableSource,
+        TriggerEvent, TriggerTiming,
+    },
+    record::Value,
+    tokenizer::{tokenize, Token, TokenKind},
+    window::{Frame, FrameBound, FrameUnits, Window},
+};
+
+/// Keywords that can't be used as an implicit (`AS`-less) alias.
+const RESERVED_KEYWORDS: &[&str] = &[
+    "all",
+    "and",
+    "as",
+    "asc",
+    "between",
+    "by",
+    "case",
+    "cross",
+    "desc",
+    "distinct",
+    "else",
+    "end",
+    "except",
+    "from",
+    "glob",
+    "group",
+    "having",
+    "in",
+    "inner",
+    "intersect",
+    "is",
+    "join",
+    "left",
+    "like",
+    "limit",
+    "natural",
+    "not",
+    "null",
+    "offset",
+    "on",
+    "or",
+    "order",
+    "outer",
+    "select",
+    "then",
+    "union",
+    "using",
+    "when",
+    "where",
+];
+
+/// Keywords that start a column constraint, so end a column's type name.
+const COLUMN_CONSTRAINT_KEYWORDS: &[&str] = &[
+    "as",
+    "check",
+    "collate",
+    "constraint",
+    "default",
+    "generated",
+    "not",
+    "null",
+    "primary",
+    "references",
+    "unique",
+];
+
+/// Keywords that start a table constraint.
+const TABLE_CONSTRAINT_KEYWORDS: &[&str] = &["check", "constraint", "foreign", "primary", "unique"];
+
+/// Keywords that start a statement which changes the database.
+const WRITE_KEYWORDS: &[&str] = &[
+    "alter", "analyze", "delete", "drop", "insert", "reindex", "replace", "update", "vacuum",
+];
+
+/// Keywords for the current date and time, which are evaluated like functions.
+const CURRENT_TIME_KEYWORDS: &[&str] = &["current_date", "current_time", "current_timestamp"];
+
+/// A recursive descent parser for SQL statements.
+pub struct Parser {
+    input: String,
+    tokens: Vec<Token>,
+    position: usize,
+    /// The name of each parameter seen so far, by number, or `None` for numbered parameters.
+    parameters: Vec<Option<String>>,
+}
+
+/// The highest number a parameter can have.
+const MAX_PARAMETER: usize = 32766;
+
+impl Parser {
+    pub fn new(input: &str) -> anyhow::Result<Self> {
+        Ok(Parser {
+            input: input.to_owned(),
+            tokens: tokenize(input)?,
+            position: 0,
+            parameters: Vec::new(),
+        })
+    }
+
+    /// The parameters seen so far, by number: each is its name if it's named.
+    pub fn parameters(&self) -> &[Option<String>] {
+        &self.parameters
+    }
+
+    /// Number a parameter. `?` takes the number after the highest so far, `?NNN` is numbered
+    /// NNN, and named parameters share the number of any earlier parameter with the same name.
+    fn parameter(&mut self, variable: String) -> anyhow::Result<Expr> {
+        let (index, name) = if variable == "?" {
+            (self.parameters.len() + 1, None)
+        } else if let Some(digits) = variable.strip_prefix('?') {
+            let index = digits
+                .parse()
+                .ok()
+                .filter(|n| (1..=MAX_PARAMETER).contains(n))
+                .ok_or_else(|| {
+                    anyhow::format_err!("variable number must be between ?1 and ?{}", MAX_PARAMETER)
+                })?;
+            (index, None)
+        } else {
+            let existing = self
+                .parameters
+                .iter()
+                .position(|p| p.as_deref() == Some(&variable));
+            let index = existing.unwrap_or(self.parameters.len()) + 1;
+            (index, Some(variable))
+        };
+        if index > MAX_PARAMETER {
+            anyhow::bail!("too many SQL variables");
+        }
+        if index > self.parameters.len() {
+            self.parameters.resize(index, None);
+        }
+        if name.is_some() {
+            self.parameters[index - 1] = name.clone();
+        }
+        Ok(Expr::Parameter { index, name })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_kind(&self) -> Option<&TokenKind> {
+        self.peek().map(|t| &t.kind)
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        self.peek().is_some_and(|t| t.is_keyword(keyword))
+    }
+
+    fn peek_nth_keyword(&self, n: usize, keyword: &str) -> bool {
+        self.tokens
+            .get(self.position + n)
+            .is_some_and(|t| t.is_keyword(keyword))
+    }
+
+    fn consume(&mut self, kind: &TokenKind) -> bool {
+        if self.peek_kind() == Some(kind) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, kind: &TokenKind) -> anyhow::Result<()> {
+        if self.consume(kind) {
+            Ok(())
+        } else {
+            Err(self.syntax_error())
+        }
+    }
+
+    fn consume_keyword(&mut self, keyword: &str) -> bool {
+        if self.peek_keyword(keyword) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> anyhow::Result<()> {
+        if self.consume_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.syntax_error())
+        }
+    }
+
+    /// An error pointing at the current token, in the style of SQLite's error messages.
+    pub fn syntax_error(&self) -> anyhow::Error {
+        match self.peek() {
+            Some(token) => anyhow::format_err!("near \"{}\": syntax error", token.text),
+            None => anyhow::format_err!("incomplete input"),
+        }
+    }
+
+    /// Parse an identifier, which may be quoted.
+    fn identifier(&mut self) -> anyhow::Result<String> {
+        match self.peek_kind() {
+            Some(TokenKind::Identifier(s)) | Some(TokenKind::QuotedIdentifier(s)) => {
+                let s = s.clone();
+                self.position += 1;
+                Ok(s)
+            }
+            _ => Err(self.syntax_error()),
+        }
+    }
+
+    /// Parse an optional alias, with or without `AS`.
+    fn alias(&mut self) -> anyhow::Result<Option<String>> {
+        if self.consume_keyword("as") {
+            return match self.peek_kind() {
+                Some(TokenKind::String(s)) => {
+                    let s = s.clone();
+                    self.position += 1;
+                    Ok(Some(s))
+                }
+                _ => self.identifier().map(Some),
+            };
+        }
+        match self.peek_kind() {
+            Some(TokenKind::Identifier(s))
+                if !RESERVED_KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(s)) =>
+            {
+                self.identifier().map(Some)
+            }
+            Some(TokenKind::QuotedIdentifier(_)) => self.identifier().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Check that the whole input has been consumed, apart from trailing semicolons.
+    pub fn finish(&mut self) -> anyhow::Result<()> {
+        while self.consume(&TokenKind::Semicolon) {}
+        if self.peek().is_some() {
+            return Err(self.syntax_error());
+        }
+        Ok(())
+    }
+
+    pub fn parse_select(&mut self) -> anyhow::Result<SelectQuery> {
+        self.expect_keyword("select")?;
+        let distinct = if self.consume_keyword("distinct") {
+            true
+        } else {
+            self.consume_keyword("all");
+            false
+        };
+
+        let mut columns = Vec::new();
+        loop {
+            columns.push(self.result_column()?);
+            if !self.consume(&TokenKind::Comma) {
+                break;
+            }
+        }
+
+        let mut from = Vec::new();
+        if self.consume_keyword("from") {
+            from.push(Join {
+                kind: JoinKind::Inner,
+                source: self.table_source()?,
+                constraint: None,
+            });
+            loop {
+                let kind = if self.consume(&TokenKind::Comma) || self.consume_keyword("join") {
+                    JoinKind::Inner
+                } else if (self.peek_keyword("inner") || self.peek_keyword("cross"))
+                    && self.peek_nth_keyword(1, "join")
+                {
+                    self.position += 2;
+                    JoinKind::Inner
+                } else if self.consume_keyword("left") {
+                    self.consume_keyword("outer");
+                    self.expect_keyword("join")?;
+                    JoinKind::Left
+                } else {
+                    break;
+                };
+                let source = self.table_source()?;
+                let constraint = if self.consume_keyword("on") {
+                    Some(self.parse_expr()?)
+                } else {
+                    None
+                };
+                from.push(Join {
+                    kind,
+                    source,
+                    constraint,
+                });
+            }
+        }
+
+        let filter = if self.consume_keyword("where") {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+
+        let mut group_by = Vec::new();
+        if self.consume_keyword("group") {
+            self.expect_keyword("by")?;
+            group_by = self.expr_list()?;
+        }
+        let having = if self.consume_keyword("having") {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+
+        let mut order_by = Vec::new();
+        if self.consume_keyword("order") {
+            self.expect_keyword("by")?;
+            order_by = self.ordering_terms()?;
+        }
+
+        let (mut limit, mut offset) = (None, None);
+        if self.consume_keyword("limit") {
+            let first = self.parse_expr()?;
+            if self.consume_keyword("offset") {
+                limit = Some(first);
+                offset = Some(self.parse_expr()?);
+            } else if self.consume(&TokenKind::Comma) {
+                // `LIMIT offset, limit`
+                offset = Some(first);
+                limit = Some(self.parse_expr()?);
+            } else {
+                limit = Some(first);
+            }
+        }
+
+        Ok(SelectQuery {
+            distinct,
+            columns,
+            from,
+            filter,
+            group_by,
+            having,
+            order_by,
+            limit,
+            offset,
+        })
+    }
+
+    /// Parse a comma separated list of `ORDER BY` terms.
+    fn ordering_terms(&mut self) -> anyhow::Result<Vec<OrderingTerm>> {
+        let mut terms = Vec::new();
+        loop {
+            let expr = self.parse_expr()?;
+            let descending = if self.consume_keyword("desc") {
+                true
+            } else {
+                self.consume_keyword("asc");
+                false
+            };
+            let nulls_first = if self.consume_keyword("nulls") {
+                if self.consume_keyword("first") {
+                    Some(true)
+                } else {
+                    self.expect_keyword("last")?;
+                    Some(false)
+                }
+            } else {
+                None
+            };
+            terms.push(OrderingTerm {
+                expr,
+                descending,
+                nulls_first,
+            });
+            if !self.consume(&TokenKind::Comma) {
+                return Ok(terms);
+            }
+        }
+    }
+
+    /// Parse a window definition, after the `OVER` keyword.
+    fn window(&mut self) -> anyhow::Result<Window> {
+        self.expect(&TokenKind::LeftParen)?;
+
+        let mut partition_by = Vec::new();
+        if self.consume_keyword("partition") {
+            self.expect_keyword("by")?;
+            partition_by = self.expr_list()?;
+        }
+        let mut order_by = Vec::new();
+        if self.consume_keyword("order") {
+            self.expect_keyword("by")?;
+            order_by = self.ordering_terms()?;
+        }
+
+        let units = if self.consume_keyword("rows") {
+            Some(FrameUnits::Rows)
+        } else if self.consume_keyword("range") {
+            Some(FrameUnits::Range)
+        } else if self.consume_keyword("groups") {
+            Some(FrameUnits::Groups)
+        } else {
+            None
+        };
+        let frame = match units {
+            Some(units) => {
+                let (start, end) = if self.consume_keyword("between") {
+                    let start = self.frame_bound()?;
+                    self.expect_keyword("and")?;
+                    (start, self.frame_bound()?)
+                } else {
+                    (self.frame_bound()?, FrameBound::CurrentRow)
+                };
+                Some(Frame { units, start, end })
+            }
+            None => None,
+        };
+
+        self.expect(&TokenKind::RightParen)?;
+        Ok(Window {
+            partition_by,
+            order_by,
+            frame,
+        })
+    }
+
+    fn frame_bound(&mut self) -> anyhow::Result<FrameBound> {
+        if self.consume_keyword("unbounded") {
+            if self.consume_keyword("preceding") {
+                return Ok(FrameBound::UnboundedPreceding);
+            }
+            self.expect_keyword("following")?;
+            return Ok(FrameBound::UnboundedFollowing);
+        }
+        if self.consume_keyword("current") {
+            self.expect_keyword("row")?;
+            return Ok(FrameBound::CurrentRow);
+        }
+        let offset = self.parse_expr()?;
+        if self.consume_keyword("preceding") {
+            Ok(FrameBound::Preceding(offset))
+        } else {
+            self.expect_keyword("following")?;
+            Ok(FrameBound::Following(offset))
+        }
+    }
+
+    /// Parse a `SELECT`, `CREATE` or `PRAGMA` statement. Other statements which change the
+    /// database are rejected, as it can only be read.
+    pub fn parse_statement(&mut self) -> anyhow::Result<Query> {
+        if self.peek_keyword("select") {
+            self.parse_select().map(Query::Select)
+        } else if self.peek_keyword("create") {
+            self.parse_create()
+        } else if self.peek_keyword("pragma") {
+            self.parse_pragma().map(Query::Pragma)
+        } else if WRITE_KEYWORDS.iter().any(|k| self.peek_keyword(k)) {
+            Err(readonly())
+        } else {
+            Err(self.syntax_error())
+        }
+    }
+
+    /// Parse a `CREATE TABLE`, `INDEX`, `VIEW` or `TRIGGER` statement, as stored in
+    /// `sqlite_schema`.
+    pub fn parse_create(&mut self) -> anyhow::Result<Query> {
+        self.expect_keyword("create")?;
+        if !self.consume_keyword("temp") {
+            self.consume_keyword("temporary");
+        }
+        let unique = self.consume_keyword("unique");
+        if !unique {
+            if self.consume_keyword("table") {
+                return self.create_table().map(Query::CreateTable);
+            }
+            if self.consume_keyword("view") {
+                return self.create_view().map(Query::CreateView);
+            }
+            if self.consume_keyword("trigger") {
+                return self.create_trigger().map(Query::CreateTrigger);
+            }
+        }
+        self.expect_keyword("index")?;
+        self.create_index(unique).map(Query::CreateIndex)
+    }
+
+    /// Parse a `PRAGMA` statement, which may set the pragma with `= value` or `(value)`.
+    pub fn parse_pragma(&mut self) -> anyhow::Result<PragmaQuery> {
+        self.expect_keyword("pragma")?;
+        let mut name = self.name()?;
+        if self.consume(&TokenKind::Dot) {
+            name = self.name()?;
+        }
+        let value = if self.consume(&TokenKind::Equals) {
+            Some(self.parse_expr()?)
+        } else if self.consume(&TokenKind::LeftParen) {
+            let value = self.parse_expr()?;
+            self.expect(&TokenKind::RightParen)?;
+            Some(value)
+        } else {
+            None
+        };
+        Ok(PragmaQuery { name, value })
+    }
+
+    /// Parse an optional `IF NOT EXISTS` followed by a (possibly schema qualified) name.
+    fn object_name(&mut self) -> anyhow::Result<String> {
+        if self.peek_keyword("if") {
+            self.position += 1;
+            self.expect_keyword("not")?;
+            self.expect_keyword("exists")?;
+        }
+        let mut name = self.name()?;
+        if self.consume(&TokenKind::Dot) {
+            name = self.name()?;
+        }
+        Ok(name)
+    }
+
+    /// An identifier, or a string literal used as one.
+    fn name(&mut self) -> anyhow::Result<String> {
+        match self.peek_kind() {
+            Some(TokenKind::String(s)) => {
+                let s = s.clone();
+                self.position += 1;
+                Ok(s)
+            }
+            _ => self.identifier(),
+        }
+    }
+
+    /// Parse a parenthesised, comma separated list of names.
+    fn name_list(&mut self) -> anyhow::Result<Vec<String>> {
+        self.expect(&TokenKind::LeftParen)?;
+        let mut names = vec![self.name()?];
+        while self.consume(&TokenKind::Comma) {
+            names.push(self.name()?);
+        }
+        self.expect(&TokenKind::RightParen)?;
+        Ok(names)
+    }
+
+    /// Parse the rest of a `CREATE TABLE` statement, after `TABLE`.
+    fn create_table(&mut self) -> anyhow::Result<CreateTableQuery> {
+        let name = self.object_name()?;
+        self.expect(&TokenKind::LeftParen)?;
+
+        let mut columns = Vec::new();
+        let mut constraints = Vec::new();
+        loop {
+            if TABLE_CONSTRAINT_KEYWORDS
+                .iter()
+                .any(|k| self.peek_keyword(k))
+            {
+                // Table constraints come after all the columns, and can be separated by
+                // whitespace rather than commas
+                loop {
+                    constraints.push(self.table_constraint()?);
+                    self.consume(&TokenKind::Comma);
+                    if !TABLE_CONSTRAINT_KEYWORDS
+                        .iter()
+                        .any(|k| self.peek_keyword(k))
+                    {
+                        break;
+                    }
+                }
+                break;
+            }
+            columns.push(self.column_definition()?);
+            if !self.consume(&TokenKind::Comma) {
+                break;
+            }
+        }
+        self.expect(&TokenKind::RightParen)?;
+
+        // `STRICT` only changes what can be written, so it's accepted and ignored
+        let mut without_rowid = false;
+        loop {
+            if self.consume_keyword("without") {
+                self.expect_keyword("rowid")?;
+                without_rowid = true;
+            } else if !self.consume_keyword("strict") {
+                break;
+            }
+            if !self.consume(&TokenKind::Comma) {
+                break;
+            }
+        }
+
+        Ok(CreateTableQuery {
+            name,
+            columns,
+            constraints,
+            without_rowid,
+        })
+    }
+
+    fn column_definition(&mut self) -> anyhow::Result<ColumnDefinition> {
+        let name = self.name()?;
+        let type_name = self.type_name()?;
+        let mut column = ColumnDefinition {
+            name,
+            affinity: Affinity::from_type_name(&type_name),
+            type_name,
+            not_null: false,
+            default: None,
+            primary_key: None,
+            unique: false,
+            collation: None,
+            checks: Vec::new(),
+            references: None,
+            generated: None,
+        };
+
+        loop {
+            if self.consume_keyword("constraint") {
+                self.name()?;
+            }
+
+            if self.consume_keyword("primary") {
+                self.expect_keyword("key")?;
+                let descending = self.consume_keyword("desc");
+                if !descending {
+                    self.consume_keyword("asc");
+                }
+                self.conflict_clause()?;
+                let autoincrement = self.consume_keyword("autoincrement");
+                column.primary_key = Some(ColumnPrimaryKey {
+                    descending,
+                    autoincrement,
+                });
+            } else if self.consume_keyword("not") {
+                self.expect_keyword("null")?;
+                self.conflict_clause()?;
+                column.not_null = true;
+            } else if self.consume_keyword("null") {
+                self.conflict_clause()?;
+            } else if self.consume_keyword("unique") {
+                self.conflict_clause()?;
+                column.unique = true;
+            } else if self.consume_keyword("check") {
+                column.checks.push(self.parenthesized_expr()?);
+            } else if self.consume_keyword("default") {
+                column.default = Some(self.default_value()?);
+            } else if self.consume_keyword("collate") {
+                column.collation = Some(self.name()?);
+            } else if self.peek_keyword("references") {
+                column.references = Some(self.foreign_key_clause()?);
+            } else if self.peek_keyword("generated") || self.peek_keyword("as") {
+                if self.consume_keyword("generated") {
+                    self.expect_keyword("always")?;
+                }
+                self.expect_keyword("as")?;
+                let expr = self.parenthesized_expr()?;
+                let stored = self.consume_keyword("stored");
+                if !stored {
+                    self.consume_keyword("virtual");
+                }
+                column.generated = Some(GeneratedColumn { expr, stored });
+            } else {
+                return Ok(column);
+            }
+        }
+    }
+
+    fn parenthesized_expr(&mut self) -> anyhow::Result<Expr> {
+        self.expect(&TokenKind::LeftParen)?;
+        let expr = self.parse_expr()?;
+        self.expect(&TokenKind::RightParen)?;
+        Ok(expr)
+    }
+
+    /// Parse a column's default value: a literal, a signed number, a parenthesised expression,
+    /// or an identifier, which is taken as text.
+    fn default_value(&mut self) -> anyhow::Result<Expr> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(self.syntax_error());
+        };
+        match token.kind {
+            TokenKind::LeftParen => self.parenthesized_expr(),
+            TokenKind::Identifier(_)
+                if ["null", "true", "false"]
+                    .iter()
+                    .chain(CURRENT_TIME_KEYWORDS.iter())
+                    .any(|k| token.is_keyword(k)) =>
+            {
+                self.primary()
+            }
+            TokenKind::Identifier(name) | TokenKind::QuotedIdentifier(name) => {
+                self.position += 1;
+                Ok(Expr::Literal(Value::Text(name)))
+            }
+            _ => self.unary(),
+        }
+    }
+
+    /// Skip an optional `ON CONFLICT` clause, which only matters when writing.
+    fn conflict_clause(&mut self) -> anyhow::Result<()> {
+        if self.consume_keyword("on") {
+            self.expect_keyword("conflict")?;
+            self.identifier()?;
+        }
+        Ok(())
+    }
+
+    /// Parse a `REFERENCES` clause. Actions and deferral options are skipped.
+    fn foreign_key_clause(&mut self) -> anyhow::Result<ForeignKey> {
+        self.expect_keyword("references")?;
+        let table = self.name()?;
+        let columns = if self.peek_kind() == Some(&TokenKind::LeftParen) {
+            self.name_list()?
+        } else {
+            Vec::new()
+        };
+
+        loop {
+            if self.consume_keyword("on") {
+                if !self.consume_keyword("delete") {
+                    self.expect_keyword("update")?;
+                }
+                if self.consume_keyword("set") {
+                    if !self.consume_keyword("null") {
+                        self.expect_keyword("default")?;
+                    }
+                } else if self.consume_keyword("no") {
+                    self.expect_keyword("action")?;
+                } else if !self.consume_keyword("cascade") {
+                    self.expect_keyword("restrict")?;
+                }
+            } else if self.consume_keyword("match") {
+                self.name()?;
+            } else if self.peek_keyword("deferrable")
+                || (self.peek_keyword("not") && self.peek_nth_keyword(1, "deferrable"))
+            {
+                self.consume_keyword("not");
+                self.position += 1;
+                if self.consume_keyword("initially") && !self.consume_keyword("deferred") {
+                    self.expect_keyword("immediate")?;
+                }
+            } else {
+                return Ok(ForeignKey { table, columns });
+            }
+        }
+    }
+
+    fn table_constraint(&mut self) -> anyhow::Result<TableConstraint> {
+        if self.consume_keyword("constraint") {
+            self.name()?;
+        }
+        if self.consume_keyword("primary") {
+            self.expect_keyword("key")?;
+            let columns = self.indexed_columns()?;
+            self.conflict_clause()?;
+            Ok(TableConstraint::PrimaryKey(columns))
+        } else if self.consume_keyword("unique") {
+            let columns = self.indexed_columns()?;
+            self.conflict_clause()?;
+            Ok(TableConstraint::Unique(columns))
+        } else if self.consume_keyword("check") {
+            Ok(TableConstraint::Check(self.parenthesized_expr()?))
+        } else {
+            self.expect_keyword("foreign")?;
+            self.expect_keyword("key")?;
+            let columns = self.name_list()?;
+            let references = self.foreign_key_clause()?;
+            Ok(TableConstraint::ForeignKey {
+                columns,
+                references,
+            })
+        }
+    }
+
+    /// Parse a parenthesised list of indexed columns, e.g. `(a, b COLLATE NOCASE DESC)`.
+    fn indexed_columns(&mut self) -> anyhow::Result<Vec<IndexedColumn>> {
+        self.expect(&TokenKind::LeftParen)?;
+        let mut columns = Vec::new();
+        loop {
+            // The collation is parsed as part of the expression
+            let (expr, collation) = match self.parse_expr()? {
+                Expr::Collate { expr, collation } => (*expr, Some(collation)),
+                expr => (expr, None),
+            };
+            let descending = self.consume_keyword("desc");
+            if !descending {
+                self.consume_keyword("asc");
+            }
+            columns.push(IndexedColumn {
+                expr,
+                collation,
+                descending,
+            });
+            if !self.consume(&TokenKind::Comma) {
+                break;
+            }
+        }
+        self.expect(&TokenKind::RightParen)?;
+        Ok(columns)
+    }
+
+    /// Parse the rest of a `CREATE INDEX` statement, after `INDEX`.
+    fn create_index(&mut self, unique: bool) -> anyhow::Result<CreateIndexQuery> {
+        let name = self.object_name()?;
+        self.expect_keyword("on")?;
+        let table_name = self.name()?;
+        let columns = self.indexed_columns()?;
+        let filter = if self.consume_keyword("where") {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+        Ok(CreateIndexQuery {
+            name,
+            table_name,
+            unique,
+            columns,
+            filter,
+        })
+    }
+
+    /// Parse the rest of a `CREATE VIEW` statement, after `VIEW`.
+    fn create_view(&mut self) -> anyhow::Result<CreateViewQuery> {
+        let name = self.object_name()?;
+        let columns = if self.peek_kind() == Some(&TokenKind::LeftParen) {
+            self.name_list()?
+        } else {
+            Vec::new()
+        };
+        self.expect_keyword("as")?;
+        let select = self.parse_select()?;
+        Ok(CreateViewQuery {
+            name,
+            columns,
+            select,
+        })
+    }
+
+    /// Parse the rest of a `CREATE TRIGGER` statement, after `TRIGGER`. The statements in the
+    /// trigger's body are skipped, as they're never run.
+    fn create_trigger(&mut self) -> anyhow::Result<CreateTriggerQuery> {
+        let name = self.object_name()?;
+        let timing = if self.consume_keyword("after") {
+            TriggerTiming::After
+        } else if self.consume_keyword("instead") {
+            self.expect_keyword("of")?;
+            TriggerTiming::InsteadOf
+        } else {
+            self.consume_keyword("before");
+            TriggerTiming::Before
+        };
+        let event = if self.consume_keyword("delete") {
+            TriggerEvent::Delete
+        } else if self.consume_keyword("insert") {
+            TriggerEvent::Insert
+        } else {
+            self.expect_keyword("update")?;
+            let mut columns = Vec::new();
+            if self.consume_keyword("of") {
+                columns.push(self.name()?);
+                while self.consume(&TokenKind::Comma) {
+                    columns.push(self.name()?);
+                }
+            }
+            TriggerEvent::Update(columns)
+        };
+        self.expect_keyword("on")?;
+        let table_name = self.name()?;
+        let for_each_row = self.consume_keyword("for");
+        if for_each_row {
+            self.expect_keyword("each")?;
+            self.expect_keyword("row")?;
+        }
+        let when = if self.consume_keyword("when") {
+            Some(self.parse_expr()?)
+        } else {
+            None
+        };
+        self.expect_keyword("begin")?;
+
+        // The body ends at the last `END`, as `CASE` expressions inside it have their own
+        let end = self
+            .tokens
+            .iter()
+            .rposition(|t| t.kind != TokenKind::Semicolon)
+            .filter(|i| *i >= self.position && self.tokens[*i].is_keyword("end"));
+        match end {
+            Some(end) => self.position = end + 1,
+            None => return Err(anyhow::format_err!("incomplete input")),
+        }
+
+        Ok(CreateTriggerQuery {
+            name,
+            table_name,
+            timing,
+            event,
+            for_each_row,
+            when,
+        })
+    }
+
+    fn result_column(&mut self) -> anyhow::Result<Column> {
+        if self.consume(&TokenKind::Star) {
+            return Ok(Column::All);
+        }
+        if matches!(
+            self.peek_kind(),
+            Some(TokenKind::Identifier(_) | TokenKind::QuotedIdentifier(_))
+        ) && self.tokens.get(self.position + 1).map(|t| &t.kind) == Some(&TokenKind::Dot)
+            && self.tokens.get(self.position + 2).map(|t| &t.kind) == Some(&TokenKind::Star)
+        {
+            let table = self.identifier()?;
+            self.position += 2;
+            return Ok(Column::TableAll(table));
+        }
+
+        let start = self.peek().map_or(self.input.len(), |t| t.offset);
+        let expr = self.parse_expr()?;
+        let end = self.tokens[self.position - 1].offset + self.tokens[self.position - 1].text.len();
+        let text = self.input[start..end].to_owned();
+        let alias = self.alias()?;
+        Ok(Column::Expr { expr, alias, text })
+    }
+
+    fn table_source(&mut self) -> anyhow::Result<TableSource> {
+        if self.consume(&TokenKind::LeftParen) {
+            let select = self.parse_select()?;
+            self.expect(&TokenKind::RightParen)?;
+            let alias = self.alias()?;
+            return Ok(TableSource::Subquery {
+                select: Box::new(select),
+                alias,
+            });
+        }
+        let name = self.identifier()?;
+        if self.consume(&TokenKind::LeftParen) {
+            let args = if self.consume(&TokenKind::RightParen) {
+                Vec::new()
+            } else {
+                let args = self.expr_list()?;
+                self.expect(&TokenKind::RightParen)?;
+                args
+            };
+            let alias = self.alias()?;
+            return Ok(TableSource::Function {
+                name: name.to_ascii_lowercase(),
+                args,
+                alias,
+            });
+        }
+        let alias = self.alias()?;
+        Ok(TableSource::Table { name, alias })
+    }
+
+    fn expr_list(&mut self) -> anyhow::Result<Vec<Expr>> {
+        let mut exprs = vec![self.parse_expr()?];
+        while self.consume(&TokenKind::Comma) {
+            exprs.push(self.parse_expr()?);
+        }
+        Ok(exprs)
+    }
+
+    pub fn parse_expr(&mut self) -> anyhow::Result<Expr> {
+        self.or()
+    }
+
+    fn or(&mut self) -> anyhow::Result<Expr> {
+        let mut left = self.and()?;
+        while self.consume_keyword("or") {
+            let right = self.and()?;
+            left = binary(BinaryOperator::Or, left, right);
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> anyhow::Result<Expr> {
+        let mut left = self.not()?;
+        while self.consume_keyword("and") {
+            let right = self.not()?;
+            left = binary(BinaryOperator::And, left, right);
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> anyhow::Result<Expr> {
+        if self.consume_keyword("not") {
+            let expr = self.not()?;
+            return Ok(Expr::Unary {
+                op: UnaryOperator::Not,
+                expr: Box::new(expr),
+            });
+        }
+        self.equality()
+    }
+
+    fn equality(&mut self) -> anyhow::Result<Expr> {
+        let mut left = self.comparison()?;
+        loop {
+            let op = match self.peek_kind() {
+                Some(TokenKind::Equals) => Some(BinaryOperator::Equals),
+                Some(TokenKind::NotEquals) => Some(BinaryOperator::NotEquals),
+                _ => None,
+            };
+            if let Some(op) = op {
+                self.position += 1;
+                let right = self.comparison()?;
+                left = binary(op, left, right);
+                continue;
+            }
+
+            if self.consume_keyword("is") {
+                let op = if self.consume_keyword("not") {
+                    BinaryOperator::IsNot
+                } else {
+                    BinaryOperator::Is
+                };
+                let right = self.comparison()?;
+                left = binary(op, left, right);
+                continue;
+            }
+            if self.consume_keyword("isnull") {
+                left = Expr::IsNull {
+                    expr: Box::new(left),
+                    negated: false,
+                };
+                continue;
+            }
+            if self.consume_keyword("notnull") {
+                left = Expr::IsNull {
+                    expr: Box::new(left),
+                    negated: true,
+                };
+                continue;
+            }
+            if self.peek_keyword("not") && self.peek_nth_keyword(1, "null") {
+                self.position += 2;
+                left = Expr::IsNull {
+                    expr: Box::new(left),
+                    negated: true,
+                };
+                continue;
+            }
+
+            let negated = if self.peek_keyword("not")
+                && ["in", "like", "glob", "between"]
+                    .iter()
+                    .any(|k| self.peek_nth_keyword(1, k))
+            {
+                self.position += 1;
+                true
+            } else {
+                false
+            };
+
+            if self.consume_keyword("in") {
+                self.expect(&TokenKind::LeftParen)?;
+                let list = if self.consume(&TokenKind::RightParen) {
+                    Vec::new()
+                } else {
+                    let list = self.expr_list()?;
+                    self.expect(&TokenKind::RightParen)?;
+                    list
+                };
+                left = Expr::InList {
+                    expr: Box::new(left),
+                    list,
+                    negated,
+                };
+            } else if self.peek_keyword("like") || self.peek_keyword("glob") {
+                let op = if self.consume_keyword("like") {
+                    BinaryOperator::Like
+                } else {
+                    self.position += 1;
+                    BinaryOperator::Glob
+                };
+                let right = self.comparison()?;
+                left = binary(op, left, right);
+                if negated {
+                    left = Expr::Unary {
+                        op: UnaryOperator::Not,
+                        expr: Box::new(left),
+                    };
+                }
+            } else if self.consume_keyword("between") {
+                let low = self.comparison()?;
+                self.expect_keyword("and")?;
+                let high = self.comparison()?;
+                left = Expr::Between {
+                    expr: Box::new(left),
+                    low: Box::new(low),
+                    high: Box::new(high),
+                    negated,
+                };
+            } else {
+                break;
+            }
+        }
+        Ok(left)
+    }
+
+    fn comparison(&mut self) -> anyhow::Result<Expr> {
+        let mut left = self.bitwise()?;
+        loop {
+            let op = match self.peek_kind() {
+                Some(TokenKind::LessThan) => BinaryOperator::LessThan,
+                Some(TokenKind::LessThanOrEquals) => BinaryOperator::LessThanOrEquals,
+                Some(TokenKind::GreaterThan) => BinaryOperator::GreaterThan,
+                Some(TokenKind::GreaterThanOrEquals) => BinaryOperator::GreaterThanOrEquals,
+                _ => break,
+            };
+            self.position += 1;
+            let right = self.bitwise()?;
+            left = binary(op, left, right);
+        }
+        Ok(left)
+    }
+
+    fn bitwise(&mut self) -> anyhow::Result<Expr> {
+        let mut left = self.additive()?;
+        loop {
+            let op = match self.peek_kind() {
+                Some(TokenKind::BitAnd) => BinaryOperator::BitAnd,
+                Some(TokenKind::BitOr) => BinaryOperator::BitOr,
+                Some(TokenKind::ShiftLeft) => BinaryOperator::ShiftLeft,
+                Some(TokenKind::ShiftRight) => BinaryOperator::ShiftRight,
+                _ => break,
+            };
+            self.position += 1;
+            let right = self.additive()?;
+            left = binary(op, left, right);
+        }
+        Ok(left)
+    }
+
+    fn additive(&mut self) -> anyhow::Result<Expr> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = match self.peek_kind() {
+                Some(TokenKind::Plus) => BinaryOperator::Add,
+                Some(TokenKind::Minus) => BinaryOperator::Subtract,
+                _ => break,
+            };
+            self.position += 1;
+            let right = self.multiplicative()?;
+            left = binary(op, left, right);
+        }
+        Ok(left)
+    }
+
+    fn multiplicative(&mut self) -> anyhow::Result<Expr> {
+        let mut left = self.concat()?;
+        loop {
+            let op = match self.peek_kind() {
+                Some(TokenKind::Star) => BinaryOperator::Multiply,
+                Some(TokenKind::Slash) => BinaryOperator::Divide,
+                Some(TokenKind::Percent) => BinaryOperator::Remainder,
+                _ => break,
+            };
+            self.position += 1;
+            let right = self.concat()?;
+            left = binary(op, left, right);
+        }
+        Ok(left)
+    }
+
+    fn concat(&mut self) -> anyhow::Result<Expr> {
+        let mut left = self.collate()?;
+        loop {
+            let op = match self.peek_kind() {
+                Some(TokenKind::Concat) => BinaryOperator::Concat,
+                Some(TokenKind::Arrow) => BinaryOperator::Extract,
+                Some(TokenKind::DoubleArrow) => BinaryOperator::ExtractValue,
+                _ => break,
+            };
+            self.position += 1;
+            let right = self.collate()?;
+            left = binary(op, left, right);
+        }
+        Ok(left)
+    }
+
+    fn collate(&mut self) -> anyhow::Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.consume_keyword("collate") {
+            expr = Expr::Collate {
+                expr: Box::new(expr),
+                collation: self.name()?,
+            };
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> anyhow::Result<Expr> {
+        let op = match self.peek_kind() {
+            Some(TokenKind::Minus) => UnaryOperator::Negate,
+            Some(TokenKind::Plus) => UnaryOperator::Plus,
+            Some(TokenKind::BitNot) => UnaryOperator::BitNot,
+            _ => return self.primary(),
+        };
+        self.position += 1;
+        let expr = self.unary()?;
+
+        // Fold negative literals, so that e.g. `LIMIT -1` is a plain literal
+        Ok(match (op, expr) {
+            (UnaryOperator::Negate, Expr::Literal(Value::Integer(n))) => {
+                Expr::Literal(Value::Integer(-n))
+            }
+            (UnaryOperator::Negate, Expr::Literal(Value::Real(f))) => {
+                Expr::Literal(Value::Real(-f))
+            }
+            (op, expr) => Expr::Unary {
+                op,
+                expr: Box::new(expr),
+            },
+        })
+    }
+
+    fn primary(&mut self) -> anyhow::Result<Expr> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(self.syntax_error());
+        };
+
+        let is_bare_identifier = matches!(token.kind, TokenKind::Identifier(_));
+        match token.kind {
+            TokenKind::Integer(n) => {
+                self.position += 1;
+                Ok(Expr::Literal(Value::Integer(n)))
+            }
+            TokenKind::Real(f) => {
+                self.position += 1;
+                Ok(Expr::Literal(Value::Real(f)))
+            }
+            TokenKind::String(s) => {
+                self.position += 1;
+                Ok(Expr::Literal(Value::Text(s)))
+            }
+            TokenKind::Variable(variable) => {
+                self.position += 1;
+                self.parameter(variable)
+            }
+            TokenKind::Blob(bytes) => {
+                self.position += 1;
+                Ok(Expr::Literal(Value::Blob(bytes)))
+            }
+            TokenKind::LeftParen => {
+                self.position += 1;
+                let expr = self.parse_expr()?;
+                self.expect(&TokenKind::RightParen)?;
+                Ok(expr)
+            }
+            TokenKind::Identifier(_) if token.is_keyword("null") => {
+                self.position += 1;
+                Ok(Expr::Literal(Value::Null))
+            }
+            TokenKind::Identifier(_) if token.is_keyword("true") || token.is_keyword("false") => {
+                self.position += 1;
+                Ok(Expr::Literal(Value::Integer(
+                    token.is_keyword("true") as i64
+                )))
+            }
+            // `CURRENT_TIMESTAMP` and the like are functions called without parentheses
+            TokenKind::Identifier(name)
+                if CURRENT_TIME_KEYWORDS.iter().any(|k| token.is_keyword(k)) =>
+            {
+                self.position += 1;
+                Ok(Expr::Function {
+                    name: name.to_ascii_lowercase(),
+                    args: Vec::new(),
+                    distinct: false,
+                })
+            }
+            TokenKind::Identifier(_) if token.is_keyword("case") => {
+                self.position += 1;
+                self.case()
+            }
+            TokenKind::Identifier(_)
+                if token.is_keyword("cast")
+                    && self.tokens.get(self.position + 1).map(|t| &t.kind)
+                        == Some(&TokenKind::LeftParen) =>
+            {
+                self.position += 2;
+                let expr = self.parse_expr()?;
+                self.expect_keyword("as")?;
+                let type_name = self.type_name()?;
+                self.expect(&TokenKind::RightParen)?;
+                Ok(Expr::Cast {
+                    expr: Box::new(expr),
+                    type_name,
+                })
+            }
+            TokenKind::Identifier(name) | TokenKind::QuotedIdentifier(name) => {
+                if is_bare_identifier
+                    && RESERVED_KEYWORDS
+                        .iter()
+                        .any(|k| k.eq_ignore_ascii_case(&name))
+                {
+                    return Err(self.syntax_error());
+                }
+                self.position += 1;
+
+                if self.consume(&TokenKind::LeftParen) {
+                    let function = self.function_call(name.to_ascii_lowercase())?;
+                    if self.consume_keyword("over") {
+                        return Ok(Expr::Window {
+                            function: Box::new(function),
+                            window: Box::new(self.window()?),
+                        });
+                    }
+                    return Ok(function);
+                }
+
+                if self.consume(&TokenKind::Dot) {
+                    let column = self.identifier()?;
+                    return Ok(Expr::Column {
+                        table: Some(name),
+                        name: column,
+                    });
+                }
+
+                Ok(Expr::Column { table: None, name })
+            }
+            _ => Err(self.syntax_error()),
+        }
+    }
+
+    /// Parse a `CASE` expression, after the `CASE` keyword.
+    fn case(&mut self) -> anyhow::Result<Expr> {
+        let operand = if self.peek_keyword("when") {
+            None
+        } else {
+            Some(Box::new(self.parse_expr()?))
+        };
+
+        let mut branches = Vec::new();
+        while self.consume_keyword("when") {
+            let when = self.parse_expr()?;
+            self.expect_keyword("then")?;
+            branches.push((when, self.parse_expr()?));
+        }
+        if branches.is_empty() {
+            return Err(self.syntax_error());
+        }
+
+        let else_expr = if self.consume_keyword("else") {
+            Some(Box::new(self.parse_expr()?))
+        } else {
+            None
+        };
+        self.expect_keyword("end")?;
+
+        Ok(Expr::Case {
+            operand,
+            branches,
+            else_expr,
+        })
+    }
+
+    /// Parse a type name such as `INTEGER`, `VARCHAR(10)` or `DOUBLE PRECISION`. Returns
+    /// the name as written, with words separated by single spaces.
+    pub fn type_name(&mut self) -> anyhow::Result<String> {
+        let mut words = Vec::new();
+        while let Some(TokenKind::Identifier(word) | TokenKind::QuotedIdentifier(word)) =
+            self.peek_kind()
+        {
+            if COLUMN_CONSTRAINT_KEYWORDS
+                .iter()
+                .any(|k| self.peek_keyword(k))
+            {
+                break;
+            }
+            words.push(word.clone());
+            self.position += 1;
+        }
+        let mut type_name = words.join(" ");
+
+        if !words.is_empty() && self.consume(&TokenKind::LeftParen) {
+            let mut sizes = Vec::new();
+            loop {
+                let negative = self.consume(&TokenKind::Minus);
+                self.consume(&TokenKind::Plus);
+                match self.peek_kind() {
+                    Some(TokenKind::Integer(_) | TokenKind::Real(_)) => {
+                        let text = &self.peek().unwrap().text;
+                        sizes.push(format!("{}{}", if negative { "-" } else { "" }, text));
+                        self.position += 1;
+                    }
+                    _ => return Err(self.syntax_error()),
+                }
+                if !self.consume(&TokenKind::Comma) {
+                    break;
+                }
+            }
+            self.expect(&TokenKind::RightParen)?;
+            type_name = format!("{}({})", type_name, sizes.join(","));
+        }
+
+        Ok(type_name)
+    }
+
+    /// Parse the arguments of a function call, after the opening parenthesis.
+    fn function_call(&mut self, name: String) -> anyhow::Result<Expr> {
+        if self.consume(&TokenKind::Star) {
+            self.expect(&TokenKind::RightParen)?;
+            return Ok(Expr::Function {
+                name,
+                args: Vec::new(),
+                distinct: false,
+            });
+        }
+        if self.consume(&TokenKind::RightParen) {
+            return Ok(Expr::Function {
+                name,
+                args: Vec::new(),
+                distinct: false,
+            });
+        }
+
+        let distinct = self.consume_keyword("distinct");
+        let args = self.expr_list()?;
+        self.expect(&TokenKind::RightParen)?;
+        Ok(Expr::Function {
+            name,
+            args,
+            distinct,
+        })
+    }
+}
+
+fn binary(op: BinaryOperator, left: Expr, right: Expr) -> Expr {
+    Expr::Binary {
+        op,
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Parser;
+    use crate::{
+        affinity::Affinity,
+        expr::{BinaryOperator, Expr},
+        query::{Column, Query, TableConstraint, TableSource, TriggerEvent, TriggerTiming},
+        record::Value,
+    };
+
+    fn column(name: &str) -> Expr {
+        Expr::Column {
+            table: None,
+            name: name.into(),
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        let expr = Parser::new("a = 1 OR b + 2 * 3 > 4")
+            .unwrap()
+            .parse_expr()
+            .unwrap();
+        let Expr::Binary {
+            op: BinaryOperator::Or,
+            left,
+            right,
+        } = expr
+        else {
+            panic!("expected OR, got {:?}", expr);
+        };
+        assert!(matches!(
+            *left,
+            Expr::Binary {
+                op: BinaryOperator::Equals,
+                ..
+            }
+        ));
+        let Expr::Binary {
+            op: BinaryOperator::GreaterThan,
+            left,
+            ..
+        } = *right
+        else {
+            panic!("expected >");
+        };
+        let Expr::Binary {
+            op: BinaryOperator::Add,
+            right,
+            ..
+        } = *left
+        else {
+            panic!("expected +");
+        };
+        assert!(matches!(
+            *right,
+            Expr::Binary {
+                op: BinaryOperator::Multiply,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn collate() {
+        let expr = Parser::new("-a COLLATE nocase || b")
+            .unwrap()
+            .parse_expr()
+            .unwrap();
+        let Expr::Binary {
+            op: BinaryOperator::Concat,
+            left,
+            ..
+        } = expr
+        else {
+            panic!("expected ||, got {:?}", expr);
+        };
+        assert!(matches!(
+            *left,
+            Expr::Collate { expr, collation } if collation == "nocase"
+                && matches!(*expr, Expr::Unary { .. })
+        ));
+    }
+
+    #[test]
+    fn select() {
+        let mut parser = Parser::new(
+            "SELECT name AS n, t.value FROM apples a, json_each(a.data) AS t \
+             WHERE color = 'Red' ORDER BY n DESC LIMIT 2;",
+        )
+        .unwrap();
+        let select = parser.parse_select().unwrap();
+        parser.finish().unwrap();
+
+        assert_eq!(select.columns.len(), 2);
+        assert!(matches!(
+            &select.columns[0],
+            Column::Expr { expr, alias: Some(alias), .. } if *expr == column("name") && alias == "n"
+        ));
+        assert!(matches!(
+            &select.from[0].source,
+            TableSource::Table { name, alias: Some(alias) } if name == "apples" && alias == "a"
+        ));
+        assert!(matches!(
+            &select.from[1].source,
+            TableSource::Function { name, args, .. } if name == "json_each" && args.len() == 1
+        ));
+        assert!(select.order_by[0].descending);
+        assert_eq!(select.limit, Some(Expr::Literal(Value::Integer(2))));
+    }
+
+    #[test]
+    fn create_table() {
+        let mut parser = Parser::new(
+            r#"CREATE TABLE IF NOT EXISTS "t" (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                "group" TEXT NOT NULL DEFAULT 'g' COLLATE NOCASE,
+                price DECIMAL(10,2) CHECK (price > 0),
+                total REAL GENERATED ALWAYS AS (price * 2) STORED,
+                parent INTEGER REFERENCES t(id) ON DELETE CASCADE,
+                UNIQUE (price, "group"),
+                FOREIGN KEY (parent) REFERENCES t(id)
+            )"#,
+        )
+        .unwrap();
+        let create = parser.parse_create().unwrap();
+        parser.finish().unwrap();
+        let create = create.as_create_table().unwrap();
+
+        assert_eq!(create.name, "t");
+        let names = create.columns.iter().map(|c| &c.name).collect::<Vec<_>>();
+        assert_eq!(names, vec!["id", "group", "price", "total", "parent"]);
+        let id = &create.columns[0];
+        assert_eq!(id.type_name, "INTEGER");
+        assert!(id.primary_key.as_ref().is_some_and(|pk| pk.autoincrement));
+        let group = &create.columns[1];
+        assert!(group.not_null);
+        assert_eq!(group.default, Some(Expr::Literal(Value::Text("g".into()))));
+        assert_eq!(group.collation.as_deref(), Some("NOCASE"));
+        assert_eq!(create.columns[2].type_name, "DECIMAL(10,2)");
+        assert_eq!(create.columns[2].affinity, Affinity::Numeric);
+        assert!(create.columns[3]
+            .generated
+            .as_ref()
+            .is_some_and(|g| g.stored));
+        assert_eq!(
+            create.columns[4].references.as_ref().unwrap().columns,
+            vec!["id"]
+        );
+        assert!(matches!(
+            &create.constraints[..],
+            [TableConstraint::Unique(columns), TableConstraint::ForeignKey { .. }]
+                if columns.len() == 2
+        ));
+    }
+
+    #[test]
+    fn create_view_and_trigger() {
+        let create =
+            Query::parse("CREATE VIEW v (x, y) AS SELECT a, b FROM (SELECT a, b FROM t)").unwrap();
+        let create = create.as_create_view().unwrap();
+        assert_eq!(create.columns, vec!["x", "y"]);
+        assert!(matches!(
+            &create.select.from[0].source,
+            TableSource::Subquery { alias: None, .. }
+        ));
+
+        let create = Query::parse(
+            "CREATE TRIGGER IF NOT EXISTS log INSTEAD OF UPDATE OF a, b ON v WHEN new.a > 1 \
+             BEGIN UPDATE t SET a = CASE WHEN new.a THEN 1 END; END",
+        )
+        .unwrap();
+        let create = create.as_create_trigger().unwrap();
+        assert_eq!(create.name, "log");
+        assert_eq!(create.table_name, "v");
+        assert_eq!(create.timing, TriggerTiming::InsteadOf);
+        assert_eq!(
+            create.event,
+            TriggerEvent::Update(vec!["a".into(), "b".into()])
+        );
+        assert!(create.when.is_some());
+    }
+
+    #[test]
+    fn pragma() {
+        let Query::Pragma(pragma) = Query::parse("PRAGMA main.cache_size = -500").unwrap() else {
+            panic!("expected a pragma");
+        };
+        assert_eq!(pragma.name, "cache_size");
+        assert!(matches!(
+            pragma.value,
+            Some(Expr::Unary { .. } | Expr::Literal(_))
+        ));
+        let Query::Pragma(pragma) = Query::parse("pragma cache_size").unwrap() else {
+            panic!("expected a pragma");
+        };
+        assert!(pragma.value.is_none());
+    }
+
+    #[test]
+    fn parameters() {
+        let mut parser = Parser::new("SELECT ?, :a, ?5, ?, @b, :a").unwrap();
+        let select = parser.parse_select().unwrap();
+        let indices = select
+            .columns
+            .iter()
+            .map(|c| match c.as_expr() {
+                Some(Expr::Parameter { index, .. }) => *index,
+                _ => panic!("expected a parameter"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(indices, vec![1, 2, 5, 6, 7, 2]);
+        assert_eq!(
+            parser.parameters(),
+            [
+                None,
+                Some(":a".into()),
+                None,
+                None,
+                None,
+                None,
+                Some("@b".into())
+            ]
+        );
+
+        assert!(Parser::new("SELECT ?0")
+            .and_then(|mut p| p.parse_select())
+            .is_err());
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let error = Parser::new("SELECT name FORM apples")
+            .and_then(|mut p| {
+                p.parse_select()?;
+                p.finish()
+            })
+            .unwrap_err();
+        assert_eq!(error.to_string(), "near \"apples\": syntax error");
+
+        let error = Parser::new("SELECT name FROM")
+            .and_then(|mut p| p.parse_select().map(|_| ()))
+            .unwrap_err();
+        assert_eq!(error.to_string(), "incomplete input");
+
+        let error = Query::parse("selec 1").unwrap_err();
+        assert_eq!(error.to_string(), "near \"selec\": syntax error");
+        let error = Query::parse("INSERT INTO apples VALUES (1)").unwrap_err();
+        assert_eq!(error.to_string(), "attempt to write a readonly database");
+    }
+}
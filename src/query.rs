@@ -1,265 +1,2065 @@
+use std::{collections::BTreeSet, sync::Arc};
+
 use crate::{
-    database::{Database, ObjectSchema},
-    record::Value,
+    affinity::Affinity,
+    collation::{same_collation, Collation, Collations},
+    database::{Database, RecordFilter},
+    expr::{
+        comparison_collation, truthiness, BinaryOperator, EvalContext, Expr, Scope, ScopeColumn,
+    },
+    function, json,
+    parser::Parser,
+    record::{Record, RecordView, Value},
+    window,
 };
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum Query {
     Select(SelectQuery),
-    Create(CreateQuery),
+    CreateTable(CreateTableQuery),
+    CreateIndex(CreateIndexQuery),
+    CreateView(CreateViewQuery),
+    CreateTrigger(CreateTriggerQuery),
+    Pragma(PragmaQuery),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SelectQuery {
-    pub table_name: String,
+    pub distinct: bool,
     pub columns: Vec<Column>,
-    pub filter: Option<Filter>,
+    /// The tables in the `FROM` clause. The first entry's join kind and constraint are unused.
+    pub from: Vec<Join>,
+    pub filter: Option<Expr>,
+    pub group_by: Vec<Expr>,
+    pub having: Option<Expr>,
+    pub order_by: Vec<OrderingTerm>,
+    pub limit: Option<Expr>,
+    pub offset: Option<Expr>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Column {
-    Count,
-    ColumnName(String),
+    /// `*`
+    All,
+    /// `table.*`
+    TableAll(String),
+    Expr {
+        expr: Expr,
+        alias: Option<String>,
+        /// The expression's source text, which names the column if it has no alias.
+        text: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Join {
+    pub kind: JoinKind,
+    pub source: TableSource,
+    pub constraint: Option<Expr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+}
+
+#[derive(Debug, Clone)]
+pub enum TableSource {
+    Table {
+        name: String,
+        alias: Option<String>,
+    },
+    /// A table-valued function, such as `json_each(...)`.
+    Function {
+        name: String,
+        args: Vec<Expr>,
+        alias: Option<String>,
+    },
+    /// A parenthesised `SELECT` statement.
+    Subquery {
+        select: Box<SelectQuery>,
+        alias: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderingTerm {
+    pub expr: Expr,
+    pub descending: bool,
+    pub nulls_first: Option<bool>,
+}
+
+/// A row that a result row is computed from, along with its group in an aggregate query.
+type InputRow = (Vec<Value>, Option<Vec<Vec<Value>>>);
+
+/// An iterator over rows, or the errors hit producing them.
+type RowIter<'a> = Box<dyn Iterator<Item = anyhow::Result<Vec<Value>>> + Send + 'a>;
+
+/// The result rows of a query, which are computed as they're pulled. Queries which sort,
+/// group or use window functions have to read all their input before the first row.
+pub struct Rows<'a> {
+    /// The result columns.
+    pub scope: Scope,
+    rows: RowIter<'a>,
+}
+
+impl Iterator for Rows<'_> {
+    type Item = anyhow::Result<Vec<Value>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows.next()
+    }
+}
+
+/// A source joined onto each row of the sources before it.
+struct JoinStage<'a> {
+    join: &'a Join,
+    /// The columns of the sources before this one.
+    outer_scope: Scope,
+    /// The columns of the sources up to and including this one.
+    joined_scope: Scope,
+    /// The number of columns this source adds.
+    width: usize,
+    /// The terms of the `WHERE` clause which only use the sources before this one, applied
+    /// before it's joined, so that e.g. a table-valued function isn't run for rows the filter
+    /// excludes.
+    filters: Vec<&'a Expr>,
+    /// The source's rows, or `None` for a table-valued function evaluated per row.
+    rows: Option<Vec<Vec<Value>>>,
 }
 
 #[derive(Debug)]
-pub struct Filter {
-    pub column_name: String,
-    pub column_value: Value,
+pub struct CreateTableQuery {
+    pub name: String,
+    pub columns: Vec<ColumnDefinition>,
+    pub constraints: Vec<TableConstraint>,
+    /// `WITHOUT ROWID` tables are stored as index B-trees keyed on the primary key.
+    pub without_rowid: bool,
 }
 
-impl Column {
+#[derive(Debug, Clone)]
+pub struct ColumnDefinition {
+    pub name: String,
+    /// The declared type, or an empty string if there isn't one.
+    pub type_name: String,
+    pub affinity: Affinity,
+    pub not_null: bool,
+    pub default: Option<Expr>,
+    pub primary_key: Option<ColumnPrimaryKey>,
+    pub unique: bool,
+    pub collation: Option<String>,
+    pub checks: Vec<Expr>,
+    pub references: Option<ForeignKey>,
+    pub generated: Option<GeneratedColumn>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnPrimaryKey {
+    pub descending: bool,
+    pub autoincrement: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct GeneratedColumn {
+    pub expr: Expr,
+    /// Stored columns are saved in the record. Virtual columns aren't, and are computed when
+    /// read.
+    pub stored: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForeignKey {
+    pub table: String,
+    pub columns: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TableConstraint {
+    PrimaryKey(Vec<IndexedColumn>),
+    Unique(Vec<IndexedColumn>),
+    Check(Expr),
+    ForeignKey {
+        columns: Vec<String>,
+        references: ForeignKey,
+    },
+}
+
+/// A column (or expression) in an index, or in a table's `PRIMARY KEY` or `UNIQUE` constraint.
+#[derive(Debug, Clone)]
+pub struct IndexedColumn {
+    pub expr: Expr,
+    pub collation: Option<String>,
+    pub descending: bool,
+}
+
+#[derive(Debug)]
+pub struct CreateIndexQuery {
+    pub name: String,
+    pub table_name: String,
+    pub unique: bool,
+    pub columns: Vec<IndexedColumn>,
+    /// The `WHERE` clause of a partial index.
+    pub filter: Option<Expr>,
+}
+
+#[derive(Debug)]
+pub struct CreateViewQuery {
+    pub name: String,
+    /// The column names given after the view's name, if any.
+    pub columns: Vec<String>,
+    pub select: SelectQuery,
+}
+
+#[derive(Debug)]
+pub struct CreateTriggerQuery {
+    pub name: String,
+    pub table_name: String,
+    pub timing: TriggerTiming,
+    pub event: TriggerEvent,
+    pub for_each_row: bool,
+    pub when: Option<Expr>,
+}
+
+#[derive(Debug)]
+pub struct PragmaQuery {
+    pub name: String,
+    /// The value the pragma is set to, if any.
+    pub value: Option<Expr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriggerTiming {
+    Before,
+    After,
+    InsteadOf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriggerEvent {
+    Delete,
+    Insert,
+    /// `UPDATE`, optionally restricted to changes of the given columns.
+    Update(Vec<String>),
+}
+
+impl ColumnDefinition {
+    /// The value of the column's `DEFAULT` clause, or NULL if it doesn't have one.
+    pub fn default_value(&self) -> anyhow::Result<Value> {
+        match self.default.as_ref() {
+            Some(default) => {
+                let (scope, collations) = (Scope::default(), Collations::default());
+                let value = default.eval(&EvalContext::new(&scope, &[], &collations))?;
+                Ok(self.affinity.apply(value))
+            }
+            None => Ok(Value::Null),
+        }
+    }
+}
+
+impl IndexedColumn {
+    /// The column's name, if it's a plain column rather than an expression.
+    pub fn column_name(&self) -> Option<&str> {
+        match &self.expr {
+            Expr::Column { table: None, name } => Some(name),
+            _ => None,
+        }
+    }
+}
+
+impl Query {
+    pub fn parse(query_str: &str) -> anyhow::Result<Self> {
+        Self::parse_with_parameters(query_str).map(|(query, _)| query)
+    }
+
+    /// Parse a query, along with its parameters by number: each is its name if it's named.
+    pub fn parse_with_parameters(query_str: &str) -> anyhow::Result<(Self, Vec<Option<String>>)> {
+        let mut parser = Parser::new(query_str)?;
+        let query = parser.parse_statement()?;
+        parser.finish()?;
+        Ok((query, parser.parameters().to_vec()))
+    }
+
     #[allow(dead_code)]
-    pub fn as_name(&self) -> Option<&str> {
+    pub fn as_create_table(&self) -> Option<&CreateTableQuery> {
         match self {
-            Column::ColumnName(s) => Some(s),
+            Query::CreateTable(create) => Some(create),
             _ => None,
         }
     }
+
+    #[allow(dead_code)]
+    pub fn as_create_view(&self) -> Option<&CreateViewQuery> {
+        match self {
+            Query::CreateView(create) => Some(create),
+            _ => None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn as_create_trigger(&self) -> Option<&CreateTriggerQuery> {
+        match self {
+            Query::CreateTrigger(create) => Some(create),
+            _ => None,
+        }
+    }
+
+    /// Copy the query, replacing its parameters with values by number. Parameters without a
+    /// value are NULL.
+    pub fn bind(&self, values: &[Value]) -> anyhow::Result<Query> {
+        let mut bind = |e: &Expr| match e {
+            Expr::Parameter { index, .. } => Some(Expr::Literal(
+                values.get(index - 1).cloned().unwrap_or(Value::Null),
+            )),
+            _ => None,
+        };
+        match self {
+            Query::Select(select) => Ok(Query::Select(select.replace(&mut bind))),
+            Query::Pragma(pragma) => Ok(Query::Pragma(PragmaQuery {
+                name: pragma.name.clone(),
+                value: pragma.value.as_ref().map(|v| v.replace(&mut bind)),
+            })),
+            _ => anyhow::bail!("parameters can only be bound to SELECT and PRAGMA statements"),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn execute(&self, db: &mut Database) -> anyhow::Result<Vec<Vec<Value>>> {
+        match self {
+            Query::Select(select) => select.execute(db),
+            Query::Pragma(pragma) => pragma.rows(db)?.collect(),
+            _ => Err(readonly()),
+        }
+    }
+
+    /// Run a query, returning an iterator over its result rows.
+    pub fn rows<'a>(&'a self, db: &'a mut Database) -> anyhow::Result<Rows<'a>> {
+        match self {
+            Query::Select(select) => select.rows(db),
+            Query::Pragma(pragma) => pragma.rows(db),
+            _ => Err(readonly()),
+        }
+    }
 }
 
-#[derive(Debug)]
-pub struct CreateQuery {
-    pub column_names: Vec<String>,
+/// The error for statements which would change the database, which can only be read.
+pub fn readonly() -> anyhow::Error {
+    anyhow::format_err!("attempt to write a readonly database")
 }
 
-impl Query {
-    pub fn parse(query_str: &str) -> anyhow::Result<Self> {
-        if query_str.to_ascii_lowercase().starts_with("select") {
-            let mut parts = query_str.split_whitespace().peekable();
-            assert_eq!(
-                parts.next().map(|s| s.to_ascii_lowercase()),
-                Some("select".into())
-            );
-
-            let mut columns = Vec::new();
-            for next_token in parts.by_ref() {
-                if next_token == "," || next_token.to_ascii_lowercase() == "from" {
-                    break;
-                } else if next_token.to_ascii_lowercase().contains("count(") {
-                    columns.push(Column::Count);
-                } else {
-                    let column_name = next_token.trim_start_matches(',').trim_end_matches(',');
-                    columns.push(Column::ColumnName(column_name.to_owned()))
+impl Column {
+    pub fn as_expr(&self) -> Option<&Expr> {
+        match self {
+            Column::Expr { expr, .. } => Some(expr),
+            _ => None,
+        }
+    }
+}
+
+impl TableSource {
+    /// The name columns from this source are qualified with.
+    fn scope_name(&self) -> &str {
+        match self {
+            TableSource::Table { name, alias } | TableSource::Function { name, alias, .. } => {
+                alias.as_deref().unwrap_or(name)
+            }
+            // Columns of unaliased subqueries can only be referred to unqualified
+            TableSource::Subquery { alias, .. } => alias.as_deref().unwrap_or(""),
+        }
+    }
+}
+
+impl PragmaQuery {
+    /// Run the pragma, returning its current value if it isn't being set. Unknown pragmas are
+    /// ignored, as in SQLite.
+    pub fn rows(&self, db: &mut Database) -> anyhow::Result<Rows<'static>> {
+        let empty_scope = Scope::default();
+        let value = match self.value.as_ref() {
+            Some(expr) => Some(expr.eval(&EvalContext::new(&empty_scope, &[], &db.collations))?),
+            None => None,
+        };
+
+        let row = match (self.name.to_ascii_lowercase().as_str(), value) {
+            ("cache_size", None) => Some(Value::Integer(db.page_cache.size())),
+            ("cache_size", Some(value)) => {
+                match Affinity::Integer.apply(value) {
+                    Value::Integer(size) => db.page_cache.set_size(size),
+                    _ => anyhow::bail!("datatype mismatch"),
+                }
+                None
+            }
+            ("mmap_size", None) => Some(Value::Integer(db.pager.mmap_size() as i64)),
+            ("mmap_size", Some(value)) => {
+                match Affinity::Integer.apply(value) {
+                    Value::Integer(size) => db.pager.set_mmap_size(size.max(0) as usize),
+                    _ => anyhow::bail!("datatype mismatch"),
                 }
+                None
             }
+            _ => None,
+        };
 
-            let table_name = parts.next().unwrap().to_ascii_lowercase();
+        let scope = Scope {
+            columns: row
+                .iter()
+                .map(|_| ScopeColumn {
+                    table: None,
+                    name: self.name.clone(),
+                    affinity: Affinity::Numeric,
+                    collation: None,
+                    hidden: false,
+                })
+                .collect(),
+        };
+        Ok(Rows {
+            scope,
+            rows: Box::new(row.map(|value| Ok(vec![value])).into_iter()),
+        })
+    }
+}
 
-            let mut filter = None;
-            if parts.next().map(|s| s.to_ascii_lowercase()) == Some("where".into()) {
-                let column_name = parts.next().unwrap().to_ascii_lowercase();
-                assert_eq!(parts.next(), Some("="));
+impl SelectQuery {
+    /// Copy the query, replacing each expression in it for which `f` returns a replacement, as
+    /// with `Expr::replace`. Subqueries are searched too.
+    pub fn replace(&self, f: &mut dyn FnMut(&Expr) -> Option<Expr>) -> SelectQuery {
+        let columns = self
+            .columns
+            .iter()
+            .map(|c| match c {
+                Column::Expr { expr, alias, text } => Column::Expr {
+                    expr: expr.replace(f),
+                    alias: alias.clone(),
+                    text: text.clone(),
+                },
+                c => c.clone(),
+            })
+            .collect();
+        let from = self
+            .from
+            .iter()
+            .map(|join| Join {
+                kind: join.kind,
+                source: match &join.source {
+                    TableSource::Function { name, args, alias } => TableSource::Function {
+                        name: name.clone(),
+                        args: args.iter().map(|a| a.replace(f)).collect(),
+                        alias: alias.clone(),
+                    },
+                    TableSource::Subquery { select, alias } => TableSource::Subquery {
+                        select: Box::new(select.replace(f)),
+                        alias: alias.clone(),
+                    },
+                    source => source.clone(),
+                },
+                constraint: join.constraint.as_ref().map(|c| c.replace(f)),
+            })
+            .collect();
+        SelectQuery {
+            distinct: self.distinct,
+            columns,
+            from,
+            filter: self.filter.as_ref().map(|e| e.replace(f)),
+            group_by: self.group_by.iter().map(|e| e.replace(f)).collect(),
+            having: self.having.as_ref().map(|e| e.replace(f)),
+            order_by: self
+                .order_by
+                .iter()
+                .map(|o| OrderingTerm {
+                    expr: o.expr.replace(f),
+                    ..o.clone()
+                })
+                .collect(),
+            limit: self.limit.as_ref().map(|e| e.replace(f)),
+            offset: self.offset.as_ref().map(|e| e.replace(f)),
+        }
+    }
 
-                let column_value = if parts.peek().unwrap().starts_with('\'') {
-                    // Interpret as text
-                    let mut text = String::new();
-                    loop {
-                        let next_part = parts.next().unwrap();
-                        text.push_str(next_part.trim_matches('\''));
-                        if next_part.ends_with('\'') {
-                            break;
+    pub fn execute(&self, db: &mut Database) -> anyhow::Result<Vec<Vec<Value>>> {
+        self.rows(db)?.collect()
+    }
+
+    /// Run the query, returning an iterator over its result rows.
+    pub fn rows<'a>(&'a self, db: &'a mut Database) -> anyhow::Result<Rows<'a>> {
+        let collations = db.collations.clone();
+
+        let empty_scope = Scope::default();
+        let constant = |expr: &Option<Expr>| -> anyhow::Result<Option<i64>> {
+            match expr {
+                Some(expr) => match expr.eval(&EvalContext::new(&empty_scope, &[], &collations))? {
+                    Value::Integer(n) => Ok(Some(n)),
+                    _ => anyhow::bail!("datatype mismatch"),
+                },
+                None => Ok(None),
+            }
+        };
+        let offset = constant(&self.offset)?.unwrap_or(0).max(0) as usize;
+        let limit = constant(&self.limit)?
+            .filter(|n| *n >= 0)
+            .map(|n| n as usize);
+
+        let (mut scope, input) = self.input_rows(db, &collations)?;
+
+        let mut columns = self.expand_columns(&scope)?;
+        let mut order_by = self.order_by.clone();
+
+        let is_aggregate_query = !self.group_by.is_empty()
+            || self.having.is_some()
+            || columns.iter().any(|(expr, _)| expr.contains_aggregate())
+            || order_by.iter().any(|o| o.expr.contains_aggregate());
+        let mut has_windows = false;
+        for expr in columns
+            .iter()
+            .map(|(e, _)| e)
+            .chain(order_by.iter().map(|o| &o.expr))
+        {
+            expr.walk(&mut |e| has_windows |= matches!(e, Expr::Window { .. }));
+        }
+
+        let result_scope;
+        let mut results: RowIter<'a>;
+        if !is_aggregate_query && !has_windows && order_by.is_empty() {
+            // Each row can be projected as soon as it's read
+            result_scope = project_scope(&columns, &scope);
+            let collations = collations.clone();
+            results = Box::new(input.map(move |row| {
+                let row = row?;
+                let ctx = EvalContext::new(&scope, &row, &collations);
+                columns.iter().map(|(expr, _)| expr.eval(&ctx)).collect()
+            }));
+        } else {
+            let rows = input.collect::<anyhow::Result<Vec<_>>>()?;
+
+            // The rows results are computed from, along with their groups for aggregate queries
+            let mut inputs: Vec<InputRow> = Vec::new();
+            if is_aggregate_query {
+                let null_row = vec![Value::Null; scope.columns.len()];
+                let group_by = self.resolve_group_by(&columns, &scope)?;
+                for group in Self::group_rows(&group_by, &scope, rows, &collations)? {
+                    let row = group.last().unwrap_or(&null_row).clone();
+                    if let Some(having) = self.having.as_ref() {
+                        let ctx = EvalContext {
+                            scope: &scope,
+                            row: &row,
+                            group: Some(&group),
+                            collations: &collations,
+                        };
+                        if truthiness(&having.eval(&ctx)?) != Some(true) {
+                            continue;
                         }
-                        text.push(' ');
                     }
-                    Value::Text(text)
-                } else {
-                    // Interpret as number
-                    todo!()
-                };
-
-                filter = Some(Filter {
-                    column_name,
-                    column_value,
-                });
+                    inputs.push((row, Some(group)));
+                }
+            } else {
+                inputs = rows.into_iter().map(|row| (row, None)).collect();
             }
 
-            Ok(Query::Select(SelectQuery {
-                table_name,
-                columns,
-                filter,
-            }))
-        } else if query_str.to_ascii_lowercase().starts_with("create") {
-            let (_, columns_info) = query_str.split_once('(').unwrap();
-            let columns_info = columns_info.strip_suffix(')').unwrap();
-            let columns = columns_info.split(',');
-
-            let mut column_names = Vec::new();
-            for column_info in columns {
-                let column_name = column_info.split_whitespace().next().unwrap();
-                column_names.push(column_name.to_owned());
+            apply_windows(
+                &mut scope,
+                &mut inputs,
+                &mut columns,
+                &mut order_by,
+                &collations,
+            )?;
+            result_scope = project_scope(&columns, &scope);
+
+            // Each result is the output row plus its ORDER BY keys
+            let mut sorted: Vec<(Vec<Value>, Vec<Value>)> = Vec::new();
+            for (row, group) in inputs.iter() {
+                let ctx = EvalContext {
+                    scope: &scope,
+                    row,
+                    group: group.as_deref(),
+                    collations: &collations,
+                };
+                sorted.push(project(&ctx, &columns, &order_by)?);
             }
 
-            Ok(Query::Create(CreateQuery { column_names }))
-        } else {
-            Err(anyhow::format_err!("unsupported or invalid query type"))
+            if !order_by.is_empty() {
+                let sort_collations = sort_collations(&order_by, &columns, &scope, &collations)?;
+                sorted
+                    .sort_by(|(_, a), (_, b)| compare_sort_keys(&order_by, &sort_collations, a, b));
+            }
+            results = Box::new(sorted.into_iter().map(|(row, _)| Ok(row)));
         }
-    }
 
-    pub fn as_create(&self) -> Option<&CreateQuery> {
-        match self {
-            Query::Create(create) => Some(create),
-            _ => None,
+        if self.distinct {
+            let result_collations = result_scope
+                .columns
+                .iter()
+                .map(|c| collations.resolve(c.collation.as_deref()))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let mut seen = DistinctRows::new(result_collations);
+            results = Box::new(results.filter(move |row| match row {
+                Ok(row) => seen.insert(row),
+                Err(_) => true,
+            }));
         }
+
+        // Rows past the limit are never read
+        let results = results.skip(offset).take(limit.unwrap_or(usize::MAX));
+        Ok(Rows {
+            scope: result_scope,
+            rows: Box::new(results),
+        })
     }
 
-    pub fn execute<R>(&self, db: &mut Database, mut file: R) -> anyhow::Result<Vec<Vec<String>>>
-    where
-        R: std::io::Read + std::io::Seek,
-    {
-        match self {
-            Query::Select(select) => {
-                if select.columns.iter().any(|c| matches!(c, Column::Count))
-                    && select.columns.len() != 1
-                {
-                    anyhow::bail!("count() queries with more than one select column not supported");
+    /// The rows of the `FROM` clause which pass the `WHERE` clause, along with the scope naming
+    /// their columns. Rows of the first source are read as they're needed; the other sources
+    /// are loaded up front, as they're joined onto each of its rows.
+    fn input_rows<'a>(
+        &'a self,
+        db: &'a mut Database,
+        collations: &Collations,
+    ) -> anyhow::Result<(Scope, RowIter<'a>)> {
+        let mut joined_sources = Vec::new();
+        for join in self.from.iter().skip(1) {
+            joined_sources.push(match &join.source {
+                TableSource::Function { name, .. } => {
+                    (table_function_scope(name, join.source.scope_name())?, None)
+                }
+                source => {
+                    let (scope, rows) = self.source_rows(db, source)?;
+                    (scope, Some(rows.collect::<anyhow::Result<Vec<_>>>()?))
                 }
+            });
+        }
 
-                let table_root_page = db.schema.table_root_page(&select.table_name)?;
-                let table_column_names = db
-                    .schema
-                    .objects
+        let (mut scope, mut rows): (Scope, RowIter<'a>) = match self.from.first() {
+            None => (Scope::default(), Box::new(std::iter::once(Ok(Vec::new())))),
+            Some(Join {
+                source: source @ TableSource::Function { name, args, .. },
+                ..
+            }) => {
+                let empty_scope = Scope::default();
+                let ctx = EvalContext::new(&empty_scope, &[], collations);
+                let arg_values = args
                     .iter()
-                    .find(|o| {
-                        matches!(o, ObjectSchema::Table(_))
-                            && o.as_table().unwrap().root_page == table_root_page
-                    })
-                    .map(|o| o.as_table().unwrap().column_names.clone())
-                    .unwrap();
-
-                let is_count_query = select.columns.iter().any(|c| matches!(c, Column::Count));
-
-                let mut select_column_names = if is_count_query {
-                    vec!["id".to_string()]
-                } else {
-                    select
-                        .columns
-                        .iter()
-                        .map(|c| c.as_name().unwrap().to_owned())
-                        .collect::<Vec<_>>()
-                };
-                if let Some(filter_column_name) =
-                    select.filter.as_ref().map(|f| f.column_name.clone())
-                {
-                    if !select_column_names.contains(&filter_column_name) {
-                        select_column_names.push(filter_column_name);
-                    }
+                    .map(|a| a.eval(&ctx))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                let function_rows = table_function_rows(name, &arg_values)?;
+                (
+                    table_function_scope(name, source.scope_name())?,
+                    Box::new(function_rows.into_iter().map(Ok)),
+                )
+            }
+            Some(join) => self.source_rows(db, &join.source)?,
+        };
+
+        let mut filters = self.filter.as_ref().map_or(Vec::new(), conjuncts);
+        if !joined_sources.is_empty() {
+            let mut stages = Vec::new();
+            for (join, (source_scope, source_rows)) in self.from.iter().skip(1).zip(joined_sources)
+            {
+                let outer_scope = scope.clone();
+                let (early_filters, later_filters) = filters
+                    .into_iter()
+                    .partition(|f| uses_only(f, &outer_scope));
+                filters = later_filters;
+                scope.extend(&source_scope);
+                stages.push(JoinStage {
+                    join,
+                    outer_scope,
+                    joined_scope: scope.clone(),
+                    width: source_scope.columns.len(),
+                    filters: early_filters,
+                    rows: source_rows,
+                });
+            }
+            let collations = collations.clone();
+            rows = Box::new(rows.flat_map(move |row| {
+                let joined = row.and_then(|row| join_sources(row, &stages, &collations));
+                match joined {
+                    Ok(rows) => rows.into_iter().map(Ok).collect::<Vec<_>>(),
+                    Err(e) => vec![Err(e)],
+                }
+            }));
+        }
+
+        if !filters.is_empty() {
+            let scope = scope.clone();
+            let collations = collations.clone();
+            rows = Box::new(rows.filter_map(move |row| {
+                let passes = row
+                    .as_ref()
+                    .map_err(|_| ())
+                    .map(|row| passes_filters(&filters, &scope, row, &collations));
+                match passes {
+                    Ok(Ok(true)) => Some(row),
+                    Ok(Ok(false)) => None,
+                    Ok(Err(e)) => Some(Err(e)),
+                    Err(()) => Some(row),
                 }
+            }));
+        }
 
-                let mut column_names = Vec::new();
-                let mut column_indices = Vec::new();
-                let mut filter_column_index = None;
-                for (i, column_name) in table_column_names.iter().enumerate() {
-                    if select_column_names.contains(column_name) {
-                        column_names.push(column_name.as_str());
-                        column_indices.push(i);
-                        if let Some(filter_column_name) =
-                            select.filter.as_ref().map(|f| f.column_name.clone())
+        Ok((scope, rows))
+    }
+
+    /// Load the rows of a table, view or subquery in the `FROM` clause.
+    fn source_rows<'a>(
+        &'a self,
+        db: &'a mut Database,
+        source: &'a TableSource,
+    ) -> anyhow::Result<(Scope, RowIter<'a>)> {
+        let scope_name = source.scope_name();
+        match source {
+            TableSource::Table { name, .. } => match db.schema.view(name) {
+                // Views are expanded as subqueries. As they're run from a copy of the view's
+                // `SELECT`, their rows are read up front.
+                Some(view) => {
+                    let (select, columns) = (view.select.clone(), view.columns.clone());
+                    let (scope, rows) = subquery_rows(db, &select, &columns, scope_name)?;
+                    let rows = rows.collect::<anyhow::Result<Vec<_>>>()?;
+                    Ok((scope, Box::new(rows.into_iter().map(Ok))))
+                }
+                None => self.table_rows(db, name, scope_name),
+            },
+            TableSource::Subquery { select, .. } => subquery_rows(db, select, &[], scope_name),
+            TableSource::Function { .. } => {
+                unreachable!("table-valued functions are joined per row")
+            }
+        }
+    }
+
+    /// Read the rows of a table, using an index to narrow down the rows read if the filter
+    /// allows.
+    fn table_rows<'a>(
+        &'a self,
+        db: &'a mut Database,
+        table_name: &str,
+        scope_name: &str,
+    ) -> anyhow::Result<(Scope, RowIter<'a>)> {
+        let table = db
+            .schema
+            .table(table_name)
+            .ok_or_else(|| anyhow::format_err!("no such table: {}", table_name))?;
+        let table_root_page = table.root_page;
+        let columns = table.columns.clone();
+
+        let mut scope = Scope::new(Some(scope_name), &table.column_names());
+        for (scope_column, column) in scope.columns.iter_mut().zip(columns.iter()) {
+            scope_column.affinity = column.affinity;
+            scope_column.collation = column.collation.clone();
+        }
+
+        // The record's values aren't necessarily in the same order as the table's columns
+        let storage_order = table.storage_order();
+        let column_indices = (0..storage_order.len()).collect::<Vec<_>>();
+        let rowid_alias = table
+            .rowid_alias()
+            .and_then(|i| storage_order.iter().position(|c| *c == i));
+        let primary_key = table.primary_key();
+        let without_rowid = table.without_rowid;
+        let primary_key_searchable = table.primary_key_searchable();
+        let primary_key_collation = table.primary_key_collation();
+
+        // See if we can use an index, or the primary key of a `WITHOUT ROWID` table
+        let mut index_search = None;
+        let mut primary_key_search = None;
+        if self.from.len() == 1 {
+            if let Some(filter) = self.filter.as_ref() {
+                for (column_name, key, collation) in equality_constraints(filter, &scope) {
+                    let Ok(column) = scope.resolve(None, &column_name) else {
+                        continue;
+                    };
+                    // The key is compared using the column's affinity, and the B-tree must be
+                    // ordered by the comparison's collation
+                    let key = scope.columns[column].affinity.apply(key);
+                    if without_rowid {
+                        // Indexes on `WITHOUT ROWID` tables hold primary keys rather than rowids,
+                        // so only the table itself can be searched
+                        if primary_key_searchable
+                            && primary_key.first() == Some(&column)
+                            && same_collation(
+                                primary_key_collation.as_deref(),
+                                collation.as_deref(),
+                            )
                         {
-                            if filter_column_name == *column_name {
-                                filter_column_index = Some(column_names.len() - 1);
-                            }
+                            primary_key_search = Some((key, collation));
+                            break;
                         }
+                    } else if let Some(index) = db.schema.index_on(
+                        table_name,
+                        &column_name,
+                        collation.as_deref(),
+                        &conjuncts(filter),
+                    ) {
+                        index_search = Some((index.root_page, key, collation));
+                        break;
                     }
                 }
+            }
+        }
 
-                // Set to false laster if we find we can use an index instead
-                let mut need_to_filter = true;
-
-                let records = if let Some(filter) = select.filter.as_ref() {
-                    // See if we can use an index
-                    let mut index = None;
-                    for object in db.schema.objects.iter() {
-                        if let ObjectSchema::Index(idx) = object {
-                            if idx.column_name == filter.column_name {
-                                index = Some(idx);
-                            }
-                        }
+        let collations = db.collations.clone();
+
+        // `column = literal` terms are checked against each record before any of its values
+        // are decoded. Records too short to hold the column are left to the full filter.
+        let mut record_constraints = Vec::new();
+        if let Some(filter) = self.filter.as_ref().filter(|_| self.from.len() == 1) {
+            for (column_name, key, collation) in equality_constraints(filter, &scope) {
+                let Ok(column) = scope.resolve(None, &column_name) else {
+                    continue;
+                };
+                let Some(position) = storage_order.iter().position(|c| *c == column) else {
+                    continue;
+                };
+                let key = scope.columns[column].affinity.apply(key);
+                let collation = collations.resolve(collation.as_deref())?;
+                record_constraints.push((position, key, collation));
+            }
+        }
+        let record_filter: RecordFilter = Box::new(move |view: &RecordView| {
+            Ok(record_constraints.iter().all(|(position, key, collation)| {
+                *position >= view.len()
+                    || collation.compare_ref(view.column(*position), key).is_eq()
+            }))
+        });
+
+        let records: Box<dyn Iterator<Item = anyhow::Result<Record>> + Send> =
+            if let Some((index_root_page, key, collation)) = index_search {
+                let collation = collations.resolve(collation.as_deref())?;
+                let row_ids = db.search_index(index_root_page, key, &collation)?;
+                Box::new(
+                    db.cursor_by_row_ids(table_root_page, &row_ids, rowid_alias, &column_indices)
+                        .with_filter(record_filter),
+                )
+            } else if let Some((key, collation)) = primary_key_search {
+                let collation = collations.resolve(collation.as_deref())?;
+                let records = db.seek(table_root_page, &key, &column_indices, &collation)?;
+                Box::new(records.into_iter().map(Ok))
+            } else {
+                // Full table scan
+                Box::new(
+                    db.cursor(table_root_page, rowid_alias, &column_indices)
+                        .with_filter(record_filter),
+                )
+            };
+
+        // Records written before columns were added with `ALTER TABLE` are missing those
+        // columns, which take their default values. Each is only evaluated once a record
+        // missing it turns up, and then once for the whole statement.
+        let mut defaults: Vec<Option<Value>> = vec![None; storage_order.len()];
+
+        let row_scope = scope.clone();
+        let rows = records.map(move |record| {
+            let record = record?;
+            let mut row = vec![Value::Null; columns.len()];
+            let stored = record.values.len();
+            for (position, i) in storage_order.iter().enumerate().skip(stored) {
+                row[*i] = match &defaults[position] {
+                    Some(default) => default.clone(),
+                    None => defaults[position]
+                        .insert(columns[*i].default_value()?)
+                        .clone(),
+                };
+            }
+            for (value, i) in record.values.into_iter().zip(storage_order.iter()) {
+                row[*i] = match columns[*i].affinity {
+                    // Integers stored in REAL columns are read back as reals
+                    Affinity::Real => Affinity::Real.apply(value),
+                    _ => value,
+                };
+            }
+
+            for (i, column) in columns.iter().enumerate() {
+                let is_virtual = column.generated.as_ref().is_some_and(|g| !g.stored);
+                if let Some(generated) = column.generated.as_ref().filter(|_| is_virtual) {
+                    let ctx = EvalContext::new(&row_scope, &row, &collations);
+                    let value = generated.expr.eval(&ctx)?;
+                    row[i] = column.affinity.apply(value);
+                }
+            }
+            Ok(row)
+        });
+
+        Ok((scope, Box::new(rows)))
+    }
+
+    /// Expand `*` and `table.*` into the columns they refer to, returning each result column's
+    /// expression and name.
+    fn expand_columns(&self, scope: &Scope) -> anyhow::Result<Vec<(Expr, String)>> {
+        let column_expr = |c: &ScopeColumn| {
+            (
+                Expr::Column {
+                    table: c.table.clone(),
+                    name: c.name.clone(),
+                },
+                c.name.clone(),
+            )
+        };
+
+        let mut columns = Vec::new();
+        for column in self.columns.iter() {
+            match column {
+                Column::All => {
+                    if self.from.is_empty() {
+                        anyhow::bail!("no tables specified");
                     }
-                    if let Some(index) = index {
-                        let row_ids = db.search_index(
-                            &mut file,
-                            index.root_page,
-                            filter.column_value.clone(),
-                        )?;
-                        need_to_filter = false;
-                        let table_root_page = db.schema.table_root_page(&select.table_name)?;
-                        db.get_by_row_ids(
-                            file,
-                            table_root_page,
-                            &row_ids,
-                            &column_names,
-                            &column_indices,
-                        )?
-                    } else {
-                        // Full table scan
-                        db.get_full_table(file, table_root_page, &column_names, &column_indices)?
+                    columns.extend(scope.columns.iter().filter(|c| !c.hidden).map(column_expr));
+                }
+                Column::TableAll(table) => {
+                    let before = columns.len();
+                    columns.extend(
+                        scope
+                            .columns
+                            .iter()
+                            .filter(|c| {
+                                !c.hidden
+                                    && c.table
+                                        .as_deref()
+                                        .is_some_and(|t| t.eq_ignore_ascii_case(table))
+                            })
+                            .map(column_expr),
+                    );
+                    if columns.len() == before {
+                        anyhow::bail!("no such table: {}", table);
                     }
-                } else {
-                    // Full table scan
+                }
+                Column::Expr { expr, alias, text } => {
+                    let name = match (alias, expr) {
+                        (Some(alias), _) => alias.clone(),
+                        (None, Expr::Column { name, .. }) => name.clone(),
+                        (None, _) => text.clone(),
+                    };
+                    columns.push((expr.clone(), name));
+                }
+            }
+        }
+        Ok(columns)
+    }
 
-                    db.get_full_table(file, table_root_page, &column_names, &column_indices)?
+    /// The `GROUP BY` terms, with result column numbers and aliases replaced by the result
+    /// columns' expressions. Unlike in `ORDER BY`, table columns take precedence over aliases.
+    fn resolve_group_by(
+        &self,
+        columns: &[(Expr, String)],
+        scope: &Scope,
+    ) -> anyhow::Result<Vec<Expr>> {
+        self.group_by
+            .iter()
+            .map(|term| {
+                let expr = match term {
+                    // `GROUP BY 2` groups by the second result column
+                    Expr::Literal(Value::Integer(n)) => {
+                        let index = (*n as usize)
+                            .checked_sub(1)
+                            .filter(|i| *i < columns.len())
+                            .ok_or_else(|| {
+                                anyhow::format_err!(
+                                    "GROUP BY term out of range - should be between 1 and {}",
+                                    columns.len()
+                                )
+                            })?;
+                        columns[index].0.clone()
+                    }
+                    Expr::Column { table: None, name } if scope.resolve(None, name).is_err() => {
+                        columns
+                            .iter()
+                            .find(|(_, alias)| alias.eq_ignore_ascii_case(name))
+                            .map_or_else(|| term.clone(), |(expr, _)| expr.clone())
+                    }
+                    term => term.clone(),
                 };
+                if expr.contains_aggregate() {
+                    anyhow::bail!("aggregate functions are not allowed in the GROUP BY clause");
+                }
+                Ok(expr)
+            })
+            .collect()
+    }
 
-                let mut results = Vec::new();
-                let mut result_count = 0;
-                for record in records.iter() {
-                    if need_to_filter {
-                        if let Some(filter) = select.filter.as_ref() {
-                            let value = &record.values[filter_column_index.unwrap()];
-                            if *value != filter.column_value {
-                                // Exclude this record from the results
-                                continue;
-                            }
-                        }
-                    }
+    /// Split rows into groups by the `GROUP BY` expressions. Without a `GROUP BY` clause all
+    /// rows form a single group, even if there are none.
+    fn group_rows(
+        group_by: &[Expr],
+        scope: &Scope,
+        rows: Vec<Vec<Value>>,
+        collations: &Collations,
+    ) -> anyhow::Result<Vec<Vec<Vec<Value>>>> {
+        if group_by.is_empty() {
+            return Ok(vec![rows]);
+        }
 
-                    result_count += 1;
-
-                    if !is_count_query {
-                        let mut row = Vec::new();
-                        for column in select.columns.iter() {
-                            #[allow(clippy::single_match)]
-                            match column {
-                                Column::ColumnName(column_name) => {
-                                    let record_index =
-                                        column_names.iter().position(|c| c == column_name).unwrap();
-                                    row.push(record.values[record_index].to_string());
-                                }
-                                _ => {}
-                            }
-                        }
-                        results.push(row);
+        let key_collations = group_by
+            .iter()
+            .map(|e| collations.resolve(e.collation(scope).as_deref()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut keyed = rows
+            .into_iter()
+            .map(|row| {
+                let ctx = EvalContext::new(scope, &row, collations);
+                let key = group_by
+                    .iter()
+                    .map(|e| e.eval(&ctx))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                Ok((key, row))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        // Sorting brings each group's rows together, still in the order they were read
+        keyed.sort_by(|(a, _), (b, _)| compare_keys(&key_collations, a, b));
+
+        let mut groups: Vec<(Vec<Value>, Vec<Vec<Value>>)> = Vec::new();
+        for (key, row) in keyed.into_iter() {
+            match groups.last_mut() {
+                Some((last_key, group)) if rows_equal(last_key, &key, &key_collations) => {
+                    group.push(row)
+                }
+                _ => groups.push((key, vec![row])),
+            }
+        }
+        Ok(groups.into_iter().map(|(_, group)| group).collect())
+    }
+}
+
+/// Evaluate the result columns and ORDER BY keys for a row (or group).
+fn project(
+    ctx: &EvalContext,
+    columns: &[(Expr, String)],
+    order_by: &[OrderingTerm],
+) -> anyhow::Result<(Vec<Value>, Vec<Value>)> {
+    let row = columns
+        .iter()
+        .map(|(expr, _)| expr.eval(ctx))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut sort_keys = Vec::with_capacity(order_by.len());
+    for term in order_by.iter() {
+        let key = match &term.expr {
+            // `ORDER BY 2` sorts by the second result column
+            Expr::Literal(Value::Integer(n)) => {
+                let index = (*n as usize)
+                    .checked_sub(1)
+                    .filter(|i| *i < row.len())
+                    .ok_or_else(|| {
+                        anyhow::format_err!(
+                            "ORDER BY term out of range - should be between 1 and {}",
+                            row.len()
+                        )
+                    })?;
+                row[index].clone()
+            }
+            // Result column aliases take precedence over table columns
+            Expr::Column { table: None, name } => {
+                match columns
+                    .iter()
+                    .position(|(_, alias)| alias.eq_ignore_ascii_case(name))
+                {
+                    Some(index) => row[index].clone(),
+                    None => term.expr.eval(ctx)?,
+                }
+            }
+            expr => expr.eval(ctx)?,
+        };
+        sort_keys.push(key);
+    }
+
+    Ok((row, sort_keys))
+}
+
+/// Compute the window functions in the result columns and ORDER BY terms. Each one's values
+/// are added to the input rows as a hidden column, and the window function is replaced with a
+/// reference to that column.
+fn apply_windows(
+    scope: &mut Scope,
+    inputs: &mut Vec<InputRow>,
+    columns: &mut [(Expr, String)],
+    order_by: &mut [OrderingTerm],
+    collations: &Collations,
+) -> anyhow::Result<()> {
+    let mut windows: Vec<Expr> = Vec::new();
+    for expr in columns
+        .iter()
+        .map(|(e, _)| e)
+        .chain(order_by.iter().map(|o| &o.expr))
+    {
+        expr.walk(&mut |e| {
+            if matches!(e, Expr::Window { .. }) && !windows.contains(e) {
+                windows.push(e.clone());
+            }
+        });
+    }
+    if windows.is_empty() {
+        return Ok(());
+    }
+
+    let add_column = |scope: &mut Scope, inputs: &mut [(Vec<Value>, _)], values: Vec<Value>| {
+        let name = format!("<window {}>", scope.columns.len());
+        scope.columns.push(ScopeColumn {
+            table: None,
+            name: name.clone(),
+            affinity: Affinity::Blob,
+            collation: None,
+            hidden: true,
+        });
+        for ((row, _), value) in inputs.iter_mut().zip(values) {
+            row.push(value);
+        }
+        Expr::Column { table: None, name }
+    };
+
+    // In aggregate queries, aggregates used within window functions (e.g. the `count(*)` in
+    // `rank() OVER (ORDER BY count(*))`) are computed per group first
+    let mut aggregates: Vec<Expr> = Vec::new();
+    if inputs.iter().any(|(_, group)| group.is_some()) {
+        for window in windows.iter() {
+            window.walk(&mut |e| {
+                if let Expr::Function { name, args, .. } = e {
+                    if function::is_aggregate(name, args.len()) && !aggregates.contains(e) {
+                        aggregates.push(e.clone());
                     }
                 }
+            });
+        }
+    }
+    let mut aggregate_columns = Vec::new();
+    for aggregate in aggregates.iter() {
+        let values = inputs
+            .iter()
+            .map(|(row, group)| {
+                aggregate.eval(&EvalContext {
+                    scope,
+                    row,
+                    group: group.as_deref(),
+                    collations,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        aggregate_columns.push(add_column(scope, inputs, values));
+    }
+    let resolved_windows = windows
+        .iter()
+        .map(|w| {
+            w.replace(&mut |e| {
+                let i = aggregates.iter().position(|a| a == e)?;
+                Some(aggregate_columns[i].clone())
+            })
+        })
+        .collect::<Vec<_>>();
 
-                if is_count_query {
-                    results.push(vec![result_count.to_string()]);
+    // Results come out in the order of the first window's partitions if there's no ORDER BY
+    let rows = inputs
+        .iter()
+        .map(|(row, _)| row.clone())
+        .collect::<Vec<_>>();
+    if let Expr::Window { window, .. } = &resolved_windows[0] {
+        let order = window.sorted_order(scope, &rows, collations)?;
+        let mut unsorted = std::mem::take(inputs)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        *inputs = order.iter().map(|i| unsorted[*i].take().unwrap()).collect();
+    }
+
+    let mut window_columns = Vec::new();
+    for window_expr in resolved_windows.iter() {
+        let Expr::Window { function, window } = window_expr else {
+            unreachable!();
+        };
+        let rows = inputs
+            .iter()
+            .map(|(row, _)| row.clone())
+            .collect::<Vec<_>>();
+        let values = window::evaluate(function, window, scope, &rows, collations)?;
+        window_columns.push(add_column(scope, inputs, values));
+    }
+
+    let mut replace_windows = |e: &Expr| {
+        let i = windows.iter().position(|w| w == e)?;
+        Some(window_columns[i].clone())
+    };
+    for (expr, _) in columns.iter_mut() {
+        *expr = expr.replace(&mut replace_windows);
+    }
+    for term in order_by.iter_mut() {
+        term.expr = term.expr.replace(&mut replace_windows);
+    }
+    Ok(())
+}
+
+/// The collation each ORDER BY term sorts with. Terms referring to result columns by number or
+/// alias use the result column's collation.
+fn sort_collations(
+    order_by: &[OrderingTerm],
+    columns: &[(Expr, String)],
+    scope: &Scope,
+    collations: &Collations,
+) -> anyhow::Result<Vec<Collation>> {
+    order_by
+        .iter()
+        .map(|term| {
+            let result_column = match &term.expr {
+                Expr::Literal(Value::Integer(n)) => {
+                    (*n as usize).checked_sub(1).and_then(|i| columns.get(i))
                 }
+                Expr::Column { table: None, name } => columns
+                    .iter()
+                    .find(|(_, alias)| alias.eq_ignore_ascii_case(name)),
+                _ => None,
+            };
+            let expr = result_column.map_or(&term.expr, |(expr, _)| expr);
+            collations.resolve(expr.collation(scope).as_deref())
+        })
+        .collect()
+}
+
+/// Compare two rows' ORDER BY keys, using each term's collation.
+pub fn compare_sort_keys(
+    terms: &[OrderingTerm],
+    collations: &[Collation],
+    a: &[Value],
+    b: &[Value],
+) -> std::cmp::Ordering {
+    for ((term, collation), (a, b)) in terms.iter().zip(collations).zip(a.iter().zip(b.iter())) {
+        let nulls_first = term.nulls_first.unwrap_or(!term.descending);
+        let ordering = match (a.is_null(), b.is_null()) {
+            (true, true) => std::cmp::Ordering::Equal,
+            (true, false) if nulls_first => std::cmp::Ordering::Less,
+            (true, false) => std::cmp::Ordering::Greater,
+            (false, true) if nulls_first => std::cmp::Ordering::Greater,
+            (false, true) => std::cmp::Ordering::Less,
+            (false, false) if term.descending => collation.compare(b, a),
+            (false, false) => collation.compare(a, b),
+        };
+        if ordering != std::cmp::Ordering::Equal {
+            return ordering;
+        }
+    }
+    std::cmp::Ordering::Equal
+}
+
+/// Combine a row with each candidate row from the next source in a join, keeping those that
+/// satisfy the join constraint.
+/// Run a subquery as the source of a `FROM` clause, naming its columns `columns` if given.
+fn subquery_rows<'a>(
+    db: &'a mut Database,
+    select: &'a SelectQuery,
+    columns: &[String],
+    scope_name: &str,
+) -> anyhow::Result<(Scope, RowIter<'a>)> {
+    let Rows { mut scope, rows } = select.rows(db)?;
+    if !columns.is_empty() && columns.len() != scope.columns.len() {
+        anyhow::bail!(
+            "expected {} columns for '{}' but got {}",
+            columns.len(),
+            scope_name,
+            scope.columns.len()
+        );
+    }
+    for (i, column) in scope.columns.iter_mut().enumerate() {
+        column.table = Some(scope_name.to_owned());
+        if let Some(name) = columns.get(i) {
+            column.name = name.clone();
+        }
+    }
+    Ok((scope, rows))
+}
 
-                Ok(results)
+/// Join the sources after the first onto one of its rows.
+fn join_sources(
+    row: Vec<Value>,
+    stages: &[JoinStage],
+    collations: &Collations,
+) -> anyhow::Result<Vec<Vec<Value>>> {
+    let mut rows = vec![row];
+    for stage in stages.iter() {
+        let mut joined_rows = Vec::new();
+        for row in rows.iter() {
+            if !passes_filters(&stage.filters, &stage.outer_scope, row, collations)? {
+                continue;
             }
-            _ => todo!("non select query"),
+            let candidates = match (&stage.rows, &stage.join.source) {
+                (Some(rows), _) => rows.clone(),
+                (None, TableSource::Function { name, args, .. }) => {
+                    let ctx = EvalContext::new(&stage.outer_scope, row, collations);
+                    let arg_values = args
+                        .iter()
+                        .map(|a| a.eval(&ctx))
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+                    table_function_rows(name, &arg_values)?
+                }
+                (None, _) => unreachable!(),
+            };
+            let matched = join_rows(
+                &stage.joined_scope,
+                row,
+                candidates.into_iter(),
+                stage.join.constraint.as_ref(),
+                collations,
+            )?;
+            pad_unmatched(stage.join.kind, row, matched, stage.width, &mut joined_rows);
         }
+        rows = joined_rows;
+    }
+    Ok(rows)
+}
+
+/// Does a row pass every one of the filters?
+fn passes_filters(
+    filters: &[&Expr],
+    scope: &Scope,
+    row: &[Value],
+    collations: &Collations,
+) -> anyhow::Result<bool> {
+    let ctx = EvalContext::new(scope, row, collations);
+    for filter in filters.iter() {
+        if truthiness(&filter.eval(&ctx)?) != Some(true) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Split a filter into the terms of its top-level `AND`s.
+pub fn conjuncts(filter: &Expr) -> Vec<&Expr> {
+    match filter {
+        Expr::Binary {
+            op: BinaryOperator::And,
+            left,
+            right,
+        } => {
+            let mut terms = conjuncts(left);
+            terms.extend(conjuncts(right));
+            terms
+        }
+        filter => vec![filter],
+    }
+}
+
+/// Does every column an expression uses resolve in `scope`?
+fn uses_only(expr: &Expr, scope: &Scope) -> bool {
+    let mut resolves = true;
+    expr.walk(&mut |e| {
+        if let Expr::Column { table, name } = e {
+            resolves &= scope.resolve(table.as_deref(), name).is_ok();
+        }
+    });
+    resolves
+}
+
+/// The scope naming a query's result columns.
+fn project_scope(columns: &[(Expr, String)], scope: &Scope) -> Scope {
+    Scope {
+        columns: columns
+            .iter()
+            .map(|(expr, name)| ScopeColumn {
+                table: None,
+                name: name.clone(),
+                affinity: expr.affinity(scope),
+                collation: expr.collation(scope),
+                hidden: false,
+            })
+            .collect(),
+    }
+}
+
+fn join_rows(
+    joined_scope: &Scope,
+    row: &[Value],
+    candidates: impl Iterator<Item = Vec<Value>>,
+    constraint: Option<&Expr>,
+    collations: &Collations,
+) -> anyhow::Result<Vec<Vec<Value>>> {
+    let mut matched = Vec::new();
+    for candidate in candidates {
+        let mut joined = row.to_vec();
+        joined.extend(candidate);
+        if let Some(constraint) = constraint {
+            let ctx = EvalContext::new(joined_scope, &joined, collations);
+            if truthiness(&constraint.eval(&ctx)?) != Some(true) {
+                continue;
+            }
+        }
+        matched.push(joined);
+    }
+    Ok(matched)
+}
+
+/// Add the matched rows of a join to the output, or a NULL-padded row for a `LEFT JOIN` with no
+/// matches.
+fn pad_unmatched(
+    kind: JoinKind,
+    row: &[Value],
+    matched: Vec<Vec<Value>>,
+    width: usize,
+    output: &mut Vec<Vec<Value>>,
+) {
+    if matched.is_empty() && kind == JoinKind::Left {
+        let mut padded = row.to_vec();
+        padded.extend(std::iter::repeat(Value::Null).take(width));
+        output.push(padded);
+    } else {
+        output.extend(matched);
+    }
+}
+
+fn table_function_scope(name: &str, scope_name: &str) -> anyhow::Result<Scope> {
+    match name {
+        "json_each" | "json_tree" => {
+            let mut scope = Scope::new(Some(scope_name), &json::JSON_EACH_COLUMNS);
+            let visible = scope.columns.len() - json::JSON_EACH_HIDDEN_COLUMNS;
+            for column in scope.columns[visible..].iter_mut() {
+                column.hidden = true;
+            }
+            Ok(scope)
+        }
+        _ => anyhow::bail!("no such table-valued function: {}", name),
+    }
+}
+
+fn table_function_rows(name: &str, args: &[Value]) -> anyhow::Result<Vec<Vec<Value>>> {
+    match name {
+        "json_each" => json::each(args, false),
+        "json_tree" => json::each(args, true),
+        _ => anyhow::bail!("no such table-valued function: {}", name),
+    }
+}
+
+/// Find `column = literal` terms in a filter's top-level `AND`s, which could be satisfied with
+/// an index.
+fn equality_constraints(filter: &Expr, scope: &Scope) -> Vec<(String, Value, Option<String>)> {
+    let without_collate = |e: &Expr| match e {
+        Expr::Collate { expr, .. } => expr.as_ref().clone(),
+        e => e.clone(),
+    };
+    match filter {
+        Expr::Binary {
+            op: BinaryOperator::And,
+            left,
+            right,
+        } => {
+            let mut constraints = equality_constraints(left, scope);
+            constraints.extend(equality_constraints(right, scope));
+            constraints
+        }
+        Expr::Binary {
+            op: BinaryOperator::Equals,
+            left,
+            right,
+        } => match (without_collate(left), without_collate(right)) {
+            (Expr::Column { name, .. }, Expr::Literal(value))
+            | (Expr::Literal(value), Expr::Column { name, .. })
+                if !value.is_null() =>
+            {
+                let collation = comparison_collation(left, right, scope);
+                vec![(name, value, collation)]
+            }
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// Are two rows equal, treating NULLs as equal to each other (as `DISTINCT` and `GROUP BY` do)?
+pub fn rows_equal(a: &[Value], b: &[Value], collations: &[Collation]) -> bool {
+    a.len() == b.len() && compare_keys(collations, a, b).is_eq()
+}
+
+/// The distinct rows seen so far, for dropping repeats of them as `DISTINCT` does.
+pub struct DistinctRows {
+    rows: BTreeSet<DistinctRow>,
+    collations: Arc<[Collation]>,
+}
+
+/// A row ordered by its values, each compared using its own collation.
+struct DistinctRow {
+    values: Vec<Value>,
+    collations: Arc<[Collation]>,
+}
+
+impl DistinctRows {
+    /// Start with no rows, whose values will be compared with `collations`.
+    pub fn new(collations: Vec<Collation>) -> Self {
+        DistinctRows {
+            rows: BTreeSet::new(),
+            collations: collations.into(),
+        }
+    }
+
+    /// Add a row, returning whether it's distinct from every row before it.
+    pub fn insert(&mut self, row: &[Value]) -> bool {
+        self.rows.insert(DistinctRow {
+            values: row.to_vec(),
+            collations: self.collations.clone(),
+        })
+    }
+}
+
+impl Ord for DistinctRow {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        compare_keys(&self.collations, &self.values, &other.values)
+    }
+}
+
+impl PartialOrd for DistinctRow {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for DistinctRow {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for DistinctRow {}
+
+/// Compare two lists of keys, each using its own collation.
+pub fn compare_keys(collations: &[Collation], a: &[Value], b: &[Value]) -> std::cmp::Ordering {
+    a.iter()
+        .zip(b.iter())
+        .zip(collations)
+        .map(|((a, b), collation)| collation.compare(a, b))
+        .find(|o| o.is_ne())
+        .unwrap_or(std::cmp::Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{equality_constraints, Query};
+    use crate::{database::Database, expr::Scope, record::Value};
+
+    fn query(sql: &str) -> Vec<Vec<Value>> {
+        let mut db = Database::open(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        Query::parse(sql).unwrap().execute(&mut db).unwrap()
+    }
+
+    /// Open one of the databases in `fixtures/`, which are built from the SQL beside them.
+    fn fixture(name: &str) -> Database {
+        Database::open(format!(
+            "{}/fixtures/{}.db",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        ))
+        .unwrap()
+    }
+
+    fn run(db: &mut Database, sql: &str) -> Vec<String> {
+        text(Query::parse(sql).unwrap().execute(db).unwrap())
+    }
+
+    fn text(rows: Vec<Vec<Value>>) -> Vec<String> {
+        rows.iter()
+            .map(|r| {
+                r.iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join("|")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn select_with_filter() {
+        assert_eq!(
+            text(query("SELECT name, color FROM apples WHERE color = 'Red'")),
+            vec!["Fuji|Red"]
+        );
+        assert_eq!(text(query("SELECT count(*) FROM oranges")), vec!["6"]);
+    }
+
+    #[test]
+    fn streamed_rows() {
+        let mut db = Database::open(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        let query = Query::parse("SELECT name AS n FROM apples LIMIT 1 OFFSET 1").unwrap();
+        let mut rows = query.rows(&mut db).unwrap();
+        assert_eq!(rows.scope.columns[0].name, "n");
+        assert_eq!(
+            rows.next().unwrap().unwrap(),
+            vec![Value::Text("Fuji".to_owned())]
+        );
+        assert!(rows.next().is_none());
+    }
+
+    #[test]
+    fn pragma_cache_size() {
+        let mut db = Database::open(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        let run = |db: &mut Database, sql: &str| Query::parse(sql).unwrap().execute(db).unwrap();
+        assert_eq!(
+            run(&mut db, "PRAGMA cache_size"),
+            vec![vec![Value::Integer(-2000)]]
+        );
+        assert!(run(&mut db, "PRAGMA cache_size = 1").is_empty());
+        assert_eq!(
+            run(&mut db, "PRAGMA cache_size"),
+            vec![vec![Value::Integer(1)]]
+        );
+        assert!(run(&mut db, "PRAGMA no_such_pragma").is_empty());
+
+        run(&mut db, "SELECT * FROM apples");
+        let misses = db.page_cache.misses;
+        run(&mut db, "SELECT * FROM apples");
+        assert_eq!(db.page_cache.misses, misses);
+
+        // Mapped pages skip the page cache altogether
+        assert!(run(&mut db, "PRAGMA mmap_size = 1048576").is_empty());
+        assert_eq!(
+            run(&mut db, "PRAGMA mmap_size"),
+            vec![vec![Value::Integer(1048576)]]
+        );
+        run(&mut db, "PRAGMA cache_size = 0");
+        let (hits, misses) = (db.page_cache.hits, db.page_cache.misses);
+        let rows = run(&mut db, "SELECT name FROM apples WHERE id = 2");
+        assert_eq!(text(rows), vec!["Fuji"]);
+        assert_eq!((db.page_cache.hits, db.page_cache.misses), (hits, misses));
+    }
+
+    #[test]
+    fn bound_parameters_are_seekable() {
+        let query = Query::parse("SELECT * FROM t WHERE a = ? AND ?2 = b").unwrap();
+        let Query::Select(select) = query.bind(&[Value::Integer(1), Value::Null]).unwrap() else {
+            panic!("expected a select");
+        };
+        let scope = Scope::new(Some("t"), &["a", "b"]);
+        let constraints = equality_constraints(select.filter.as_ref().unwrap(), &scope);
+        // NULL never compares equal, so can't be sought
+        assert_eq!(constraints, vec![("a".into(), Value::Integer(1), None)]);
+    }
+
+    #[test]
+    fn without_rowid_tables() {
+        let mut db = fixture("without_rowid");
+        assert_eq!(
+            run(&mut db, "SELECT * FROM pairs"),
+            vec!["two|2|x", "one|1|x", "three|1|y"]
+        );
+        assert_eq!(
+            run(&mut db, "SELECT note FROM pairs WHERE b = 'y'"),
+            vec!["three"]
+        );
+        assert_eq!(
+            run(
+                &mut db,
+                "SELECT count(*), max(word) FROM words WHERE length = 9"
+            ),
+            vec!["167|word0499"]
+        );
+
+        // A primary key seek only reads the pages on the way down to its row
+        let words = db.schema.table("words").unwrap().root_page;
+        let before = db.stats();
+        assert_eq!(
+            run(&mut db, "SELECT length FROM words WHERE word = 'word0321'"),
+            vec!["8"]
+        );
+        assert_eq!(db.stats().since(&before).pages_read[&words], 2);
+        assert!(run(&mut db, "SELECT length FROM words WHERE word = 'word9999'").is_empty());
+    }
+
+    #[test]
+    fn partial_indexes() {
+        let mut db = fixture("partial_index");
+        let btrees_read = |db: &mut Database, sql: &str, expected: Vec<&str>| {
+            let before = db.stats();
+            assert_eq!(run(db, sql), expected);
+            let stats = db.stats().since(&before);
+            stats
+                .pages_read
+                .keys()
+                .map(|root| db.schema.btree_name(*root).unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        // The index is missing (5, -1), so it's only searched if the query only wants rows it
+        // holds
+        assert_eq!(
+            btrees_read(
+                &mut db,
+                "SELECT a, b FROM t WHERE a = 5",
+                vec!["5|1", "5|-1"]
+            ),
+            vec!["t"]
+        );
+        assert_eq!(
+            btrees_read(
+                &mut db,
+                "SELECT a, t.b FROM t WHERE t.b > 0 AND a = 5",
+                vec!["5|1"]
+            ),
+            vec!["t", "ip"]
+        );
+    }
+
+    #[test]
+    fn records_on_overflow_pages() {
+        let mut db = fixture("overflow");
+        let docs = db.schema.table("docs").unwrap().root_page;
+        let before = db.stats();
+        assert_eq!(
+            run(
+                &mut db,
+                "SELECT id, length(body), substr(body, -3) FROM docs"
+            ),
+            vec!["1|4|its", "2|5003|end", "3|3000|\0\0\0", "4|989|yyy"]
+        );
+        // Every overflow page of the table is followed once, and no more of its B-tree is read
+        let stats = db.stats().since(&before);
+        assert_eq!(stats.overflow_pages_read, 7);
+        assert_eq!(stats.pages_read[&docs], 4);
+
+        // Index entries overflow too, and are compared whole
+        let edge = "y".repeat(989);
+        assert_eq!(
+            run(
+                &mut db,
+                &format!("SELECT id, title FROM docs WHERE body = '{}'", edge)
+            ),
+            vec!["4|edge"]
+        );
+        assert_eq!(
+            run(
+                &mut db,
+                "SELECT title FROM docs WHERE body > 'x' ORDER BY body"
+            ),
+            vec!["long", "edge", "blob"]
+        );
+    }
+
+    #[test]
+    fn tables_from_a_multi_page_schema() {
+        let mut db = fixture("large_schema");
+        assert_eq!(
+            run(&mut db, "SELECT id FROM t20 WHERE name = 'table'"),
+            vec!["2"]
+        );
+    }
+
+    #[test]
+    fn views_and_autoindexes() {
+        let mut db = fixture("views");
+        assert_eq!(db.schema.view_count(), 2);
+        assert_eq!(db.schema.trigger_count(), 1);
+        assert_eq!(
+            run(&mut db, "SELECT * FROM team_sizes ORDER BY size"),
+            vec!["blue|1", "red|2"]
+        );
+        assert_eq!(
+            run(&mut db, "SELECT b.team_name FROM big_teams AS b"),
+            vec!["red"]
+        );
+        assert_eq!(
+            run(
+                &mut db,
+                "SELECT p.name FROM people AS p, big_teams WHERE p.team = big_teams.team_name"
+            ),
+            vec!["Ann", "Cy"]
+        );
+
+        // A UNIQUE column is searched through its automatic index
+        let before = db.stats();
+        assert_eq!(
+            run(&mut db, "SELECT id FROM people WHERE name = 'Bo'"),
+            vec!["2"]
+        );
+        let stats = db.stats().since(&before);
+        let btrees = stats
+            .pages_read
+            .keys()
+            .map(|root| db.schema.btree_name(*root).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(btrees, vec!["people", "sqlite_autoindex_people_1"]);
+    }
+
+    #[test]
+    fn collated_indexes() {
+        let mut db = fixture("collations");
+        let mut run_with_btrees = |sql: &str| {
+            let before = db.stats();
+            let rows = run(&mut db, sql);
+            let stats = db.stats().since(&before);
+            let btrees = stats
+                .pages_read
+                .keys()
+                .map(|root| db.schema.btree_name(*root).unwrap().to_owned())
+                .collect::<Vec<_>>();
+            (rows, btrees)
+        };
+
+        // The index is in the NOCASE order of the column it's on
+        assert_eq!(
+            run_with_btrees("SELECT id FROM users WHERE name = 'alice'"),
+            (
+                vec!["1".into(), "3".into()],
+                vec!["users".into(), "users_name".into()]
+            )
+        );
+        // A NOCASE index can't answer a BINARY comparison, so the table is scanned
+        assert_eq!(
+            run_with_btrees("SELECT id FROM users WHERE email = 'bob@example.com'"),
+            (vec![], vec!["users".into()])
+        );
+        assert_eq!(
+            run_with_btrees("SELECT id FROM users WHERE email = 'bob@example.com' COLLATE NOCASE"),
+            (vec!["2".into()], vec!["users".into(), "users_email".into()])
+        );
+        assert_eq!(
+            run(&mut db, "SELECT name FROM users ORDER BY name, id"),
+            vec!["Alice", "ALICE", "bob", "Carol"]
+        );
+    }
+
+    #[test]
+    fn collated_aggregates() {
+        let mut db = fixture("collations");
+        assert_eq!(
+            run(
+                &mut db,
+                "SELECT min(name), max(name), count(DISTINCT name), max(email) FROM users"
+            ),
+            vec!["Alice|Carol|3|carol@example.com"]
+        );
+        assert_eq!(
+            run(
+                &mut db,
+                "SELECT min(name) OVER (ORDER BY id ROWS 1 PRECEDING) FROM users"
+            ),
+            vec!["Alice", "Alice", "ALICE", "ALICE"]
+        );
+    }
+
+    #[test]
+    fn expressions_order_and_limit() {
+        assert_eq!(
+            text(query(
+                "SELECT id * 10, upper(name) AS n FROM apples WHERE id > 1 ORDER BY n DESC LIMIT 2"
+            )),
+            vec!["30|HONEYCRISP", "40|GOLDEN DELICIOUS"]
+        );
+    }
+
+    #[test]
+    fn group_by_result_columns() {
+        assert_eq!(
+            text(query(
+                "SELECT length(name) > 5 AS long, count(*) FROM apples GROUP BY long"
+            )),
+            vec!["0|1", "1|3"]
+        );
+        assert_eq!(
+            text(query(
+                "SELECT length(name) > 5, min(id) FROM apples GROUP BY 1 ORDER BY 2 DESC"
+            )),
+            vec!["0|2", "1|1"]
+        );
+        // Table columns come before aliases
+        assert_eq!(
+            text(query("SELECT id % 2 AS id FROM apples GROUP BY id")),
+            vec!["1", "0", "1", "0"]
+        );
+        assert_eq!(
+            text(query("SELECT DISTINCT id % 2 FROM apples ORDER BY id DESC")),
+            vec!["0", "1"]
+        );
+
+        let error = |sql| {
+            let mut db = Database::open(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+            Query::parse(sql)
+                .unwrap()
+                .execute(&mut db)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("SELECT color FROM apples GROUP BY 2"),
+            "GROUP BY term out of range - should be between 1 and 1"
+        );
+        assert_eq!(
+            error("SELECT color, count(*) AS n FROM apples GROUP BY n"),
+            "aggregate functions are not allowed in the GROUP BY clause"
+        );
+    }
+
+    #[test]
+    fn case_cast_and_affinity() {
+        assert_eq!(
+            text(query(
+                "SELECT CASE id WHEN 1 THEN 'one' WHEN '2' THEN 'two' ELSE 'many' END,
+                        CASE WHEN name LIKE 'g%' THEN 1 END,
+                        CAST(id AS TEXT) || '!'
+                 FROM apples WHERE id < 4"
+            )),
+            vec!["one|1|1!", "two||2!", "many||3!"]
+        );
+        // The text literal is converted to the integer column's affinity
+        assert_eq!(
+            text(query("SELECT name FROM apples WHERE id = '2'")),
+            vec!["Fuji"]
+        );
+        assert_eq!(
+            text(query("SELECT count(*) FROM apples WHERE name = 4")),
+            vec!["0"]
+        );
+        assert_eq!(
+            text(query(
+                "SELECT CAST('1e3' AS NUMERIC), CAST(' 12abc' AS INT), CAST(2 AS REAL)"
+            )),
+            vec!["1000|12|2.0"]
+        );
+    }
+
+    #[test]
+    fn window_functions() {
+        assert_eq!(
+            text(query(
+                "SELECT name,
+                        row_number() OVER (ORDER BY name),
+                        rank() OVER (PARTITION BY length(color) ORDER BY id),
+                        sum(id) OVER (ORDER BY id ROWS BETWEEN 1 PRECEDING AND 1 FOLLOWING),
+                        lag(id, 1, 0) OVER (ORDER BY id)
+                 FROM apples ORDER BY id"
+            )),
+            vec![
+                "Granny Smith|3|1|3|0",
+                "Fuji|1|1|6|1",
+                "Honeycrisp|4|1|9|2",
+                "Golden Delicious|2|1|7|3"
+            ]
+        );
+        // Without an ORDER BY, results come out in window order
+        assert_eq!(
+            text(query(
+                "SELECT id, sum(id) OVER (ORDER BY id DESC) FROM oranges WHERE id > 3"
+            )),
+            vec!["6|6", "5|11", "4|15"]
+        );
+    }
+
+    #[test]
+    fn json_functions() {
+        assert_eq!(
+            text(query(
+                r#"SELECT json_extract('{"a":{"b":[1,2.5,"x"]}}', '$.a.b[1]'),
+                          '{"a":{"b":[1,2]}}' -> '$.a',
+                          '{"a":"x"}' ->> 'a',
+                          '[1,2,3]' -> 1,
+                          json_type('{"a":[]}', '$.a'),
+                          json_valid('{"a":'),
+                          json_array_length('[1,[2,3]]', '$[1]')"#
+            )),
+            vec![r#"2.5|{"b":[1,2]}|x|2|array|0|2"#]
+        );
+        assert_eq!(
+            text(query(
+                "SELECT json_object('a', 1, 'b', json_array(1, 'two', NULL)), json_array('[1]')"
+            )),
+            vec![r#"{"a":1,"b":[1,"two",null]}|["[1]"]"#]
+        );
+    }
+
+    #[test]
+    fn json_aggregates() {
+        assert_eq!(
+            text(query(
+                "SELECT json_group_array(name) FROM apples WHERE id < 3"
+            )),
+            vec![r#"["Granny Smith","Fuji"]"#]
+        );
+        assert_eq!(
+            text(query(
+                "SELECT json_group_object(name, id) FROM oranges WHERE id <= 2"
+            )),
+            vec![r#"{"Mandarin":1,"Tangelo":2}"#]
+        );
+    }
+
+    #[test]
+    fn json_each_and_tree() {
+        assert_eq!(
+            text(query(
+                r#"SELECT key, value, type, fullkey FROM json_each('{"a":1,"b":[2,3]}')"#
+            )),
+            vec!["a|1|integer|$.a", "b|[2,3]|array|$.b"]
+        );
+        assert_eq!(
+            text(query(
+                r#"SELECT fullkey, atom FROM json_tree('{"a":[true]}') WHERE atom IS NOT NULL"#
+            )),
+            vec!["$.a[0]|1"]
+        );
+        assert_eq!(
+            text(query(
+                "SELECT apples.name, j.value FROM apples, json_each(json_array(apples.id, apples.id * 2)) AS j \
+                 WHERE apples.id = 2"
+            )),
+            vec!["Fuji|2", "Fuji|4"]
+        );
+
+        // Only the rows the filter keeps are passed to the function, as the others' names
+        // aren't JSON
+        assert_eq!(
+            text(query(
+                "SELECT j.key, j.value FROM apples, json_each(iif(apples.id = 2, '[7,8]', apples.name)) AS j \
+                 WHERE apples.id = 2 AND j.value > 7"
+            )),
+            vec!["1|8"]
+        );
+    }
+
+    #[test]
+    fn column_defaults() {
+        let create = Query::parse(
+            "CREATE TABLE t (a REAL DEFAULT -1, b TEXT DEFAULT 5, c DEFAULT (2 * 3), d INTEGER)",
+        )
+        .unwrap();
+        let columns = &create.as_create_table().unwrap().columns;
+        let defaults = columns
+            .iter()
+            .map(|c| c.default_value().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(text(vec![defaults]), vec!["-1.0|5|6|"]);
+
+        // Defaults are only evaluated for records which don't store their columns, so
+        // unsupported ones such as `date('now')` don't stop the table being read
+        let mut db = fixture("altered");
+        assert_eq!(
+            run(&mut db, "SELECT * FROM ev"),
+            vec![
+                "1|2026-01-02 03:04:05|2026-01-02|plain|1.0|",
+                "2|2026-03-04 05:06:07|2026-03-04|fancy|2.5|new"
+            ]
+        );
+        assert_eq!(
+            run(&mut db, "SELECT count(*) FROM ev WHERE kind = 'plain'"),
+            vec!["1"]
+        );
+        let now = run(
+            &mut db,
+            "SELECT CURRENT_TIMESTAMP, current_date, CURRENT_TIME",
+        );
+        let [timestamp, date, time] = now[0].split('|').collect::<Vec<_>>()[..] else {
+            panic!("expected three columns");
+        };
+        assert_eq!((timestamp.len(), date.len(), time.len()), (19, 10, 8));
+    }
+
+    #[test]
+    fn collations() {
+        assert_eq!(
+            text(query(
+                "SELECT id FROM apples WHERE name = 'fuji' COLLATE NOCASE OR name = 'fuji'"
+            )),
+            vec!["2"]
+        );
+        assert_eq!(
+            text(query(
+                "SELECT 'a' = 'A' COLLATE NOCASE, 'a  ' COLLATE RTRIM = 'a', 'a' < 'B', \
+                 'a' < 'B' COLLATE NOCASE"
+            )),
+            vec!["1|1|0|1"]
+        );
+
+        let mut db = Database::open(concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db")).unwrap();
+        db.collations
+            .register("by_length", |a, b| a.len().cmp(&b.len()));
+        let rows = Query::parse("SELECT name FROM apples ORDER BY name COLLATE by_length DESC")
+            .unwrap()
+            .execute(&mut db)
+            .unwrap();
+        assert_eq!(
+            text(rows),
+            vec!["Golden Delicious", "Granny Smith", "Honeycrisp", "Fuji"]
+        );
     }
 }
@@ -1,9 +1,11 @@
-use nom::{bytes::complete::take, number::complete::i8, IResult};
+use nom::{bytes::complete::take, IResult};
 
 use crate::varint::varint;
 
 #[derive(Debug)]
 pub struct Record {
+    /// The rowid of a table record.
+    pub row_id: Option<i64>,
     pub values: Vec<Value>,
 }
 
@@ -23,69 +25,76 @@ pub enum ColumnType {
     Text(usize),
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum Value {
     Null,
     Integer(i64),
     Real(f64),
     Text(String),
-    Blob(String),
+    Blob(Vec<u8>),
 }
 
 impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match self {
-            Value::Null => None,
-            Value::Integer(n1) => match other {
-                Value::Integer(n2) => n1.partial_cmp(n2),
-                _ => None,
-            },
-            Value::Real(f1) => match other {
-                Value::Real(f2) => f1.partial_cmp(f2),
-                _ => None,
-            },
-            Value::Text(s1) => match other {
-                Value::Text(s2) => s1.partial_cmp(s2),
-                Value::Blob(s2) => s1.partial_cmp(s2),
-                _ => None,
-            },
-            Value::Blob(s1) => match other {
-                Value::Text(s2) => s1.partial_cmp(s2),
-                Value::Blob(s2) => s1.partial_cmp(s2),
-                _ => None,
-            },
+        if matches!(self, Value::Null) || matches!(other, Value::Null) {
+            None
+        } else {
+            Some(self.compare(other))
         }
     }
 }
 
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
-        match self {
-            Value::Null => false,
-            Value::Integer(n1) => match other {
-                Value::Integer(n2) => n1 == n2,
-                _ => false,
-            },
-            Value::Real(f1) => match other {
-                Value::Real(f2) => f1 == f2,
-                _ => false,
-            },
-            Value::Text(s1) => match other {
-                Value::Text(s2) => s1 == s2,
-                Value::Blob(s2) => s1 == s2,
-                _ => false,
-            },
-            Value::Blob(s1) => match other {
-                Value::Text(s2) => s1 == s2,
-                Value::Blob(s2) => s1 == s2,
-                _ => false,
-            },
-        }
+        self.partial_cmp(other) == Some(std::cmp::Ordering::Equal)
     }
 }
 
 impl Value {
+    /// Compare two values using SQLite's sort order: NULLs first, then numbers, then text,
+    /// then blobs.
+    pub fn compare(&self, other: &Self) -> std::cmp::Ordering {
+        use std::cmp::Ordering;
+
+        fn class(value: &Value) -> u8 {
+            match value {
+                Value::Null => 0,
+                Value::Integer(_) | Value::Real(_) => 1,
+                Value::Text(_) => 2,
+                Value::Blob(_) => 3,
+            }
+        }
+
+        match (self, other) {
+            (Value::Integer(n1), Value::Integer(n2)) => n1.cmp(n2),
+            (Value::Integer(n), Value::Real(f)) => {
+                (*n as f64).partial_cmp(f).unwrap_or(Ordering::Equal)
+            }
+            (Value::Real(f), Value::Integer(n)) => {
+                f.partial_cmp(&(*n as f64)).unwrap_or(Ordering::Equal)
+            }
+            (Value::Real(f1), Value::Real(f2)) => f1.partial_cmp(f2).unwrap_or(Ordering::Equal),
+            (Value::Text(s1), Value::Text(s2)) => s1.cmp(s2),
+            (Value::Blob(b1), Value::Blob(b2)) => b1.cmp(b2),
+            _ => class(self).cmp(&class(other)),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// The name of this value's storage class, as returned by `typeof()`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Integer(_) => "integer",
+            Value::Real(_) => "real",
+            Value::Text(_) => "text",
+            Value::Blob(_) => "blob",
+        }
+    }
+
     pub fn as_integer(&self) -> Option<i64> {
         match self {
             Value::Integer(n) => Some(*n),
@@ -93,7 +102,6 @@ impl Value {
         }
     }
 
-    #[allow(dead_code)]
     pub fn as_real(&self) -> Option<f64> {
         match self {
             Value::Real(f) => Some(*f),
@@ -108,29 +116,80 @@ impl Value {
         }
     }
 
-    #[allow(dead_code)]
-    pub fn as_blob(&self) -> Option<&str> {
+    pub fn as_blob(&self) -> Option<&[u8]> {
         match self {
             Value::Blob(s) => Some(s),
             _ => None,
         }
     }
-}
 
-impl ToString for Value {
-    fn to_string(&self) -> String {
+    /// The value as an SQL literal, as `quote()` writes it.
+    pub fn to_sql_literal(&self) -> String {
         match self {
-            Value::Null => "null".into(),
+            Value::Null => "NULL".to_owned(),
             Value::Integer(n) => n.to_string(),
-            Value::Real(f) => f.to_string(),
-            Value::Blob(s) => s.to_owned(),
-            Value::Text(s) => s.to_owned(),
+            Value::Real(f) if f.is_nan() => "NULL".to_owned(),
+            // The nearest literal which still parses as infinity
+            Value::Real(f) if f.is_infinite() => {
+                if *f > 0.0 { "9.0e+999" } else { "-9.0e+999" }.to_owned()
+            }
+            Value::Real(f) => format_real(*f),
+            Value::Text(s) => format!("'{}'", s.replace('\'', "''")),
+            Value::Blob(s) => format!(
+                "X'{}'",
+                s.iter().map(|b| format!("{:02X}", b)).collect::<String>()
+            ),
         }
     }
 }
 
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Real(r) => write!(f, "{}", format_real(*r)),
+            Value::Blob(bytes) => write!(f, "{}", String::from_utf8_lossy(bytes)),
+            Value::Text(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// Format a real the way SQLite does (`%!.15g`), always including a decimal point.
+pub fn format_real(f: f64) -> String {
+    if f.is_nan() {
+        return String::new();
+    } else if f.is_infinite() {
+        return if f > 0.0 { "Inf".into() } else { "-Inf".into() };
+    }
+
+    let scientific = format!("{:.14e}", f);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent = exponent.parse::<i32>().unwrap();
+
+    let trim = |s: &str| -> String {
+        let s = s.trim_end_matches('0');
+        if s.ends_with('.') {
+            format!("{}0", s)
+        } else {
+            s.to_owned()
+        }
+    };
+
+    if !(-4..15).contains(&exponent) {
+        format!(
+            "{}e{}{:02}",
+            trim(mantissa),
+            if exponent < 0 { '-' } else { '+' },
+            exponent.abs()
+        )
+    } else {
+        let decimals = (14 - exponent).max(0) as usize;
+        trim(&format!("{:.*}", decimals, f))
+    }
+}
+
 impl ColumnType {
-    #[allow(dead_code)]
     fn size(&self) -> usize {
         match self {
             ColumnType::Null => 0,
@@ -175,135 +234,176 @@ impl TryFrom<i64> for ColumnType {
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub enum RecordType {
-    Table,
-    Index,
+/// A record still in its page, whose columns are decoded when they're asked for.
+#[derive(Debug)]
+pub struct RecordView<'a> {
+    /// The rowid of a table record.
+    pub row_id: Option<i64>,
+    rowid_alias: Option<usize>,
+    /// The type of each column, and its offset in `body`.
+    columns: Vec<(ColumnType, usize)>,
+    body: &'a [u8],
 }
 
-impl Record {
-    pub fn parse<'input>(
-        input: &'input [u8],
-        column_names: &[&str],
-        column_indices: &[usize],
-        record_type: RecordType,
-    ) -> IResult<&'input [u8], Self> {
-        let (input, row_id) = if record_type == RecordType::Table {
-            let (input, row_id) = varint(input)?;
-            (input, Some(row_id))
-        } else {
-            (input, None)
-        };
+/// A value borrowed from a record.
+#[derive(Debug, Clone, Copy)]
+pub enum ValueRef<'a> {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(&'a str),
+    Blob(&'a [u8]),
+}
 
-        let mut header_bytes_read = 0;
+impl ValueRef<'_> {
+    pub fn to_value(self) -> Value {
+        match self {
+            ValueRef::Null => Value::Null,
+            ValueRef::Integer(n) => Value::Integer(n),
+            ValueRef::Real(f) => Value::Real(f),
+            ValueRef::Text(s) => Value::Text(s.to_owned()),
+            ValueRef::Blob(bytes) => Value::Blob(bytes.to_vec()),
+        }
+    }
+}
+
+impl<'a> RecordView<'a> {
+    /// Parse a record's header from a cell's payload, leaving its values to be decoded by
+    /// `column`. Table records have a rowid, which is stored in the cell before the payload.
+    pub fn parse(
+        input: &'a [u8],
+        row_id: Option<i64>,
+        rowid_alias: Option<usize>,
+    ) -> IResult<&'a [u8], Self> {
         let before_input_len = input.len();
-        let (input, header_size) = varint(input)?;
+        let (mut rest, header_size) = varint(input)?;
         let header_size = header_size as usize;
-        header_bytes_read += before_input_len - input.len();
+        let mut header_bytes_read = before_input_len - rest.len();
 
-        let mut rest = input;
-        let mut column_types = Vec::new();
+        let mut columns = Vec::new();
+        let mut body_size = 0;
         while header_bytes_read < header_size {
             let (remainder, column_type) = varint(rest)?;
             header_bytes_read += rest.len() - remainder.len();
             rest = remainder;
-            let column_type = ColumnType::try_from(column_type).expect("invalid column type");
-            column_types.push(column_type);
+            let column_type = ColumnType::try_from(column_type).map_err(|_| {
+                nom::Err::Failure(nom::error::Error::new(input, nom::error::ErrorKind::Verify))
+            })?;
+            let size = column_type.size();
+            columns.push((column_type, body_size));
+            body_size += size;
         }
+        let (rest, body) = take(body_size)(rest)?;
 
-        let mut values = Vec::with_capacity(column_names.len());
-        for (i, column_type) in column_types.iter().enumerate() {
-            let to_include = column_indices.contains(&i);
-            let is_rowid_alias = if to_include {
-                // FIXME: This is only an alias for `rowid` when it has type
-                // "INTEGER PRIMARY KEY" - it's not based on the name
-                column_names[column_indices.iter().position(|j| i == *j).unwrap()] == "id"
-            } else {
-                false
-            };
-
-            match column_type {
-                ColumnType::Null => {
-                    if to_include {
-                        if record_type == RecordType::Table && is_rowid_alias {
-                            values.push(Value::Integer(row_id.unwrap()));
-                        } else {
-                            values.push(Value::Null);
-                        }
-                    }
-                }
-                ColumnType::I8 => {
-                    let (remainder, value) = i8(rest)?;
-                    rest = remainder;
-                    if to_include {
-                        values.push(Value::Integer(value as i64));
-                    }
-                }
-                ColumnType::I16 => {
-                    let (remainder, bytes) = take(2usize)(rest)?;
-                    rest = remainder;
-                    if to_include {
-                        values.push(Value::Integer(
-                            i16::from_be_bytes([bytes[0], bytes[1]]) as i64
-                        ));
-                    }
-                }
-                ColumnType::I24 => {
-                    let (remainder, bytes) = take(3usize)(rest)?;
-                    rest = remainder;
-                    if to_include {
-                        values.push(Value::Integer(i32::from_be_bytes([
-                            0, bytes[0], bytes[1], bytes[2],
-                        ]) as i64));
-                    }
-                }
-                ColumnType::I32 => {
-                    let (remainder, bytes) = take(4usize)(rest)?;
-                    rest = remainder;
-                    if to_include {
-                        values.push(Value::Integer(i32::from_be_bytes([
-                            bytes[0], bytes[1], bytes[2], bytes[3],
-                        ]) as i64));
-                    }
-                }
-                ColumnType::I48 => todo!("i48 column"),
-                ColumnType::I64 => todo!("i64 column"),
-                ColumnType::F64 => todo!("f64 column"),
-                ColumnType::Zero => {
-                    if to_include {
-                        values.push(Value::Integer(0i64));
-                    }
-                }
-                ColumnType::One => {
-                    if to_include {
-                        values.push(Value::Integer(0i64));
-                    }
-                }
-                ColumnType::Blob(size) => {
-                    let (remainder, bytes) = take(*size)(rest)?;
-                    rest = remainder;
-                    if to_include {
-                        values.push(Value::Blob(
-                            std::str::from_utf8(bytes)
-                                .expect("non utf-8 text")
-                                .to_owned(),
-                        ));
-                    }
-                }
-                ColumnType::Text(size) => {
-                    let (remainder, bytes) = take(*size)(rest)?;
-                    rest = remainder;
-                    if to_include {
-                        values.push(Value::Text(
-                            std::str::from_utf8(bytes)
-                                .expect("non utf-8 text")
-                                .to_owned(),
-                        ));
-                    }
-                }
+        Ok((
+            rest,
+            RecordView {
+                row_id,
+                // The rowid alias is only stored in the rowid of table records
+                rowid_alias: rowid_alias.filter(|_| row_id.is_some()),
+                columns,
+                body,
+            },
+        ))
+    }
+
+    /// The number of columns stored in the record.
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Decode a column, which must be stored in the record.
+    pub fn column(&self, i: usize) -> ValueRef<'a> {
+        let (column_type, offset) = &self.columns[i];
+        let bytes = &self.body[*offset..*offset + column_type.size()];
+        let integer = |bytes: &[u8]| {
+            // Sign-extend from the stored width
+            let fill = if bytes[0] & 0x80 != 0 { 0xff } else { 0 };
+            let mut be_bytes = [fill; 8];
+            be_bytes[8 - bytes.len()..].copy_from_slice(bytes);
+            ValueRef::Integer(i64::from_be_bytes(be_bytes))
+        };
+        match column_type {
+            // The rowid alias column is stored as NULL, with its value in the rowid
+            ColumnType::Null if self.rowid_alias == Some(i) => {
+                ValueRef::Integer(self.row_id.unwrap())
             }
+            ColumnType::Null => ValueRef::Null,
+            ColumnType::I8
+            | ColumnType::I16
+            | ColumnType::I24
+            | ColumnType::I32
+            | ColumnType::I48
+            | ColumnType::I64 => integer(bytes),
+            ColumnType::F64 => ValueRef::Real(f64::from_be_bytes(bytes.try_into().unwrap())),
+            ColumnType::Zero => ValueRef::Integer(0),
+            ColumnType::One => ValueRef::Integer(1),
+            ColumnType::Blob(_) => ValueRef::Blob(bytes),
+            // Rather than lose them, the bytes of text which isn't UTF-8 are read as a blob
+            ColumnType::Text(_) => match std::str::from_utf8(bytes) {
+                Ok(text) => ValueRef::Text(text),
+                Err(_) => ValueRef::Blob(bytes),
+            },
+        }
+    }
+
+    /// The number of bytes the given columns' values take up in the record.
+    pub fn size_of(&self, column_indices: &[usize]) -> usize {
+        column_indices
+            .iter()
+            .filter_map(|i| self.columns.get(*i))
+            .map(|(column_type, _)| column_type.size())
+            .sum()
+    }
+
+    /// Decode the given columns into an owned record, in the order they're stored. Columns
+    /// missing from the record are left out.
+    pub fn to_record(&self, column_indices: &[usize]) -> Record {
+        let values = (0..self.len())
+            .filter(|i| column_indices.contains(i))
+            .map(|i| self.column(i).to_value())
+            .collect();
+        Record {
+            row_id: self.row_id,
+            values,
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecordView, Value, ValueRef};
+
+    #[test]
+    fn decode_columns_on_demand() {
+        // A header of 5 bytes: NULL, I24, text of length 2, F64
+        let mut input = vec![5, 0, 3, 17, 7];
+        input.extend([0xff, 0xff, 0xfe]);
+        input.extend(b"hi");
+        input.extend(1.5f64.to_be_bytes());
+        input.push(42);
+
+        let (rest, view) = RecordView::parse(&input, Some(7), Some(0)).unwrap();
+        assert_eq!(rest, [42]);
+        assert_eq!(view.len(), 4);
+        assert!(matches!(view.column(2), ValueRef::Text("hi")));
+        assert!(matches!(view.column(1), ValueRef::Integer(-2)));
+
+        let record = view.to_record(&[0, 1, 3]);
+        assert_eq!(record.row_id, Some(7));
+        assert_eq!(
+            record.values,
+            vec![Value::Integer(7), Value::Integer(-2), Value::Real(1.5)]
+        );
+    }
 
-        Ok((rest, Record { values }))
+    #[test]
+    fn sql_literals() {
+        assert_eq!(Value::Null.to_sql_literal(), "NULL");
+        assert_eq!(Value::Integer(-3).to_sql_literal(), "-3");
+        assert_eq!(Value::Real(2.0).to_sql_literal(), "2.0");
+        assert_eq!(Value::Real(f64::INFINITY).to_sql_literal(), "9.0e+999");
+        assert_eq!(Value::Text("it's".into()).to_sql_literal(), "'it''s'");
+        assert_eq!(Value::Blob(b"hi".to_vec()).to_sql_literal(), "X'6869'");
     }
 }
@@ -0,0 +1,825 @@
+use std::{
+    io::{BufRead, Write},
+    time::Instant,
+};
+
+use anyhow::{bail, Context, Result};
+use rustyline::{error::ReadlineError, DefaultEditor};
+use sqlite_starter_rust::{quote_identifier, Connection, ObjectSchema, Row, Stats};
+
+use crate::output::{sql_literal, Mode, Output};
+
+const PROMPT: &str = "sqlite> ";
+const CONTINUATION_PROMPT: &str = "   ...> ";
+const HISTORY_FILE: &str = ".sqlite_history";
+/// How deeply `.read` can be nested, so a script which reads itself stops.
+const MAX_READ_DEPTH: usize = 25;
+
+/// The dot-commands, as listed by `.help`.
+const HELP: &[(&str, &str)] = &[
+    (".dbinfo", "Show status information about the database"),
+    (".dump ?PATTERN?", "Render database content as SQL"),
+    (".exit", "Exit this program"),
+    (
+        ".fullschema",
+        "Show schema and the content of sqlite_stat tables",
+    ),
+    (".headers on|off", "Turn display of headers on or off"),
+    (".help", "Show this message"),
+    (".indexes ?TABLE?", "Show names of indexes"),
+    (".mode MODE ?TABLE?", "Set output mode"),
+    (".nullvalue STRING", "Use STRING in place of NULL values"),
+    (".quit", "Exit this program"),
+    (".read FILE", "Read input from FILE"),
+    (
+        ".schema ?PATTERN?",
+        "Show the CREATE statements matching PATTERN",
+    ),
+    (
+        ".separator COL ?ROW?",
+        "Change the column and row separators",
+    ),
+    (".stats on|off", "Show stats or turn stats on or off"),
+    (".tables", "List names of tables"),
+    (".timer on|off", "Turn SQL timer on or off"),
+];
+
+/// The tables `ANALYZE` stores statistics in.
+const STAT_TABLES: [&str; 3] = ["sqlite_stat1", "sqlite_stat3", "sqlite_stat4"];
+
+/// Runs dot-commands and SQL statements against one open database.
+pub struct Shell {
+    pub conn: Connection,
+    pub output: Output,
+    /// Whether to print how long each statement took.
+    pub timer: bool,
+    /// Whether to print what reading the database took for each statement.
+    pub stats: bool,
+    /// The last error reported while running a script, which decides its exit code.
+    pub script_error: Option<anyhow::Error>,
+    /// The number of `.read` commands being run.
+    read_depth: usize,
+}
+
+impl Shell {
+    pub fn new(conn: Connection, output: Output) -> Self {
+        Shell {
+            conn,
+            output,
+            timer: false,
+            stats: false,
+            script_error: None,
+            read_depth: 0,
+        }
+    }
+
+    /// Read statements and dot-commands from the terminal until `.quit` or end of input,
+    /// reporting errors and carrying on.
+    pub fn run_interactive(&mut self) -> Result<()> {
+        let mut editor = DefaultEditor::new()?;
+        let history =
+            std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(HISTORY_FILE));
+        if let Some(history) = history.as_ref() {
+            // There's no history on the first run
+            let _ = editor.load_history(history);
+        }
+
+        let mut sql = String::new();
+        loop {
+            let prompt = if sql.is_empty() {
+                PROMPT
+            } else {
+                CONTINUATION_PROMPT
+            };
+            let line = match editor.readline(prompt) {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => {
+                    sql.clear();
+                    continue;
+                }
+                Err(ReadlineError::Eof) => break,
+                Err(err) => return Err(err.into()),
+            };
+            let _ = editor.add_history_entry(line.as_str());
+
+            match self.process_line(&mut sql, &line) {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(err) => eprintln!("Error: {:#}", err),
+            }
+        }
+
+        if let Some(history) = history.as_ref() {
+            let _ = editor.save_history(history);
+        }
+        Ok(())
+    }
+
+    /// Run a script of statements and dot-commands, as from stdin or `.read`. Errors are
+    /// reported and the rest of the script is still run, as sqlite3 does.
+    pub fn run_script(&mut self, script: impl BufRead) -> Result<()> {
+        let mut sql = String::new();
+        for line in script.lines() {
+            match self.process_line(&mut sql, &line?) {
+                Ok(true) => {}
+                Ok(false) => return Ok(()),
+                Err(err) => {
+                    eprintln!("Error: {:#}", err);
+                    self.script_error = Some(err);
+                }
+            }
+        }
+        if !sql.trim().is_empty() {
+            // A final statement doesn't need its `;`
+            self.run_sql(&std::mem::take(&mut sql))?;
+        }
+        Ok(())
+    }
+
+    /// Process a line of input, which is either a dot-command or part of the SQL in `sql`,
+    /// which is run once it's complete. Returns whether to keep reading input.
+    fn process_line(&mut self, sql: &mut String, line: &str) -> Result<bool> {
+        if sql.is_empty() && line.trim_start().starts_with('.') {
+            return self.dot_command(line.trim());
+        }
+
+        sql.push_str(line);
+        sql.push('\n');
+        let (statements, rest) = split_statements(sql);
+        let rest = rest.to_owned();
+        let result = statements
+            .into_iter()
+            .try_for_each(|statement| self.run_sql(statement));
+        // Blank lines and comments between statements aren't the start of another
+        if is_blank(&rest) {
+            sql.clear();
+        } else {
+            *sql = rest;
+        }
+        result.map(|_| true)
+    }
+
+    /// Run a single dot-command or a string of SQL statements, as given on the command line.
+    pub fn run_command(&mut self, command: &str) -> Result<()> {
+        if command.trim_start().starts_with('.') {
+            self.dot_command(command.trim())?;
+            return Ok(());
+        }
+        let (statements, rest) = split_statements(command);
+        for statement in statements {
+            self.run_sql(statement)?;
+        }
+        if !is_blank(rest) {
+            self.run_sql(rest)?;
+        }
+        Ok(())
+    }
+
+    /// Run a dot-command. Returns whether to keep reading input.
+    fn dot_command(&mut self, line: &str) -> Result<bool> {
+        let args = split_args(line);
+        let args = args.iter().map(|a| a.as_str()).collect::<Vec<_>>();
+        match args[0] {
+            ".dbinfo" => self.print_dbinfo(),
+            ".tables" => {
+                let tables = self.conn.schema().table_names();
+                println!("{}", tables.join(" "));
+            }
+            ".schema" => {
+                let (options, pattern) = schema_args(&args[1..], &["--nosys"])?;
+                self.print_schema(pattern, options.contains(&"--nosys"), true);
+            }
+            ".fullschema" => {
+                schema_args(&args[1..], &[])?;
+                self.print_schema(None, true, false);
+                self.print_stat_tables()?;
+            }
+            ".dump" => {
+                let allowed = ["--data-only", "--newlines", "--nosys"];
+                let (options, pattern) = schema_args(&args[1..], &allowed)?;
+                self.dump(&mut std::io::stdout().lock(), pattern, &options)?;
+            }
+            ".indexes" => {
+                if args.len() > 2 {
+                    bail!("usage: .indexes ?TABLE?");
+                }
+                let indexes = self.conn.schema().index_names(args.get(1).copied());
+                println!("{}", indexes.join(" "));
+            }
+            ".read" => {
+                let Some(path) = args.get(1) else {
+                    bail!("usage: .read FILE");
+                };
+                if self.read_depth == MAX_READ_DEPTH {
+                    bail!(
+                        "too many nested .read commands (the limit is {})",
+                        MAX_READ_DEPTH
+                    );
+                }
+                let file = std::fs::File::open(path)
+                    .with_context(|| format!("cannot open \"{}\"", path))?;
+                self.read_depth += 1;
+                let result = self.run_script(std::io::BufReader::new(file));
+                self.read_depth -= 1;
+                result?;
+            }
+            ".quit" | ".exit" => return Ok(false),
+            ".mode" => match args[1..] {
+                [] => println!("current output mode: {}", self.output.mode.name()),
+                [mode] => self.output.set_mode(Mode::parse(mode, None)?),
+                [mode, table] => self.output.set_mode(Mode::parse(mode, Some(table))?),
+                _ => bail!("usage: .mode MODE ?TABLE?"),
+            },
+            ".headers" => match args[1..] {
+                [on_off] => self.output.set_headers(parse_bool(on_off)?),
+                _ => bail!("usage: .headers on|off"),
+            },
+            ".nullvalue" => match args[1..] {
+                [null_value] => self.output.null_value = null_value.to_owned(),
+                _ => bail!("usage: .nullvalue STRING"),
+            },
+            ".separator" => match args[1..] {
+                [column] => self.output.column_separator = column.to_owned(),
+                [column, row] => {
+                    self.output.column_separator = column.to_owned();
+                    self.output.row_separator = row.to_owned();
+                }
+                _ => bail!("usage: .separator COL ?ROW?"),
+            },
+            ".timer" => match args[1..] {
+                [on_off] => self.timer = parse_bool(on_off)?,
+                _ => bail!("usage: .timer on|off"),
+            },
+            ".stats" => match args[1..] {
+                [] => self.print_stats(&self.conn.stats(), None),
+                [on_off] => self.stats = parse_bool(on_off)?,
+                _ => bail!("usage: .stats on|off"),
+            },
+            ".help" => {
+                for (command, description) in HELP {
+                    println!("{:<24} {}", command, description);
+                }
+            }
+            command => bail!(
+                "unknown command or invalid arguments: \"{}\". Enter \".help\" for help",
+                command.trim_start_matches('.')
+            ),
+        }
+        Ok(true)
+    }
+
+    /// Print the header fields and a summary of the schema, as sqlite3's `.dbinfo` does.
+    fn print_dbinfo(&self) {
+        let (header, schema) = (&self.conn.database().header, self.conn.schema());
+        let fields = [
+            ("database page size:", header.page_size.to_string()),
+            ("write format:", (header.write_version as u8).to_string()),
+            ("read format:", (header.read_version as u8).to_string()),
+            (
+                "reserved bytes:",
+                header.end_page_reserved_bytes.to_string(),
+            ),
+            (
+                "file change counter:",
+                header.file_change_counter.to_string(),
+            ),
+            ("database page count:", header.size_in_pages.to_string()),
+            (
+                "freelist page count:",
+                header.num_freelist_pages.to_string(),
+            ),
+            ("schema cookie:", header.schema_cookie.to_string()),
+            ("schema format:", header.schema_format.to_string()),
+            (
+                "default cache size:",
+                header.default_page_cache_size.to_string(),
+            ),
+            (
+                "autovacuum top root:",
+                header.largest_root_btree_page.to_string(),
+            ),
+            (
+                "incremental vacuum:",
+                (header.incremental_vacuum_mode as u8).to_string(),
+            ),
+            (
+                "text encoding:",
+                format!(
+                    "{} ({})",
+                    header.text_encoding as u32,
+                    header.text_encoding.name()
+                ),
+            ),
+            ("user version:", header.user_version.to_string()),
+            ("application id:", header.application_id.to_string()),
+            (
+                "software version:",
+                header.sqlite_version_number.to_string(),
+            ),
+            ("number of tables:", schema.table_count().to_string()),
+            ("number of indexes:", schema.index_count().to_string()),
+            ("number of triggers:", schema.trigger_count().to_string()),
+            ("number of views:", schema.view_count().to_string()),
+            ("schema size:", schema.sql_size().to_string()),
+            // Missing its colon, as in sqlite3. The database is never changed, so this
+            // is always the initial data version.
+            ("data version", "1".to_owned()),
+        ];
+        for (label, value) in fields {
+            println!("{:<20} {}", label, value);
+        }
+    }
+
+    /// Print the statements which created the objects on tables matching `pattern`, or every
+    /// object, optionally leaving out SQLite's own. As in sqlite3, tables with quoted names
+    /// are created `IF NOT EXISTS`, and views can be followed by their column names.
+    fn print_schema(&mut self, pattern: Option<&str>, no_internal: bool, view_columns: bool) {
+        let schema = self.conn.schema();
+        let objects = match pattern {
+            Some(pattern) => schema.objects_on_tables(pattern),
+            None => schema.objects.iter().collect(),
+        };
+        let statements = objects
+            .into_iter()
+            .filter(|o| !(no_internal && o.is_internal()))
+            .filter_map(|o| {
+                let view = o.as_view().filter(|_| view_columns);
+                Some((o.sql()?.to_owned(), view.map(|v| v.name.clone())))
+            })
+            .collect::<Vec<_>>();
+
+        for (sql, view) in statements {
+            match sql.get(..14).and_then(|s| s.strip_prefix("CREATE TABLE ")) {
+                Some("'" | "\"") => println!("CREATE TABLE IF NOT EXISTS {};", &sql[13..]),
+                _ => match view.and_then(|view| self.view_columns(&view).map(|c| (view, c))) {
+                    Some((view, columns)) => println!(
+                        "{}\n/* {}({}) */;",
+                        sql,
+                        quote_identifier(&view),
+                        columns.join(",")
+                    ),
+                    None => println!("{};", sql),
+                },
+            }
+        }
+    }
+
+    /// The quoted names of a view's columns, if it can be queried.
+    fn view_columns(&mut self, view: &str) -> Option<Vec<String>> {
+        let sql = format!("SELECT * FROM {}", quote_identifier(view));
+        let mut statement = self.conn.prepare(&sql).ok()?;
+        let rows = statement.query(&[]).ok()?;
+        let columns = rows.column_names().iter();
+        Some(columns.map(|c| quote_identifier(c).into_owned()).collect())
+    }
+
+    /// Print the contents of the `sqlite_stat` tables as statements which recreate them.
+    fn print_stat_tables(&mut self) -> Result<()> {
+        let stat_tables = STAT_TABLES
+            .iter()
+            .filter(|name| self.conn.schema().table(name).is_some())
+            .collect::<Vec<_>>();
+        if stat_tables.is_empty() {
+            println!("/* No STAT tables available */");
+            return Ok(());
+        }
+
+        println!("ANALYZE sqlite_schema;");
+        for name in stat_tables {
+            let mut statement = self.conn.prepare(&format!("SELECT * FROM {}", name))?;
+            for row in statement.query(&[])? {
+                let values = row?
+                    .values()
+                    .iter()
+                    .map(|v| v.to_sql_literal())
+                    .collect::<Vec<_>>();
+                println!("INSERT INTO {} VALUES({});", name, values.join(","));
+            }
+        }
+        println!("ANALYZE sqlite_schema;");
+        Ok(())
+    }
+
+    /// Write SQL which recreates the objects matching `pattern`, or the whole database, with
+    /// the rows of its tables, as sqlite3's `.dump` does. With `--data-only` only the rows are
+    /// printed, `--nosys` leaves out SQLite's own tables, and `--newlines` writes text with
+    /// control characters as it is rather than with `unistr()` escapes.
+    fn dump(
+        &mut self,
+        out: &mut impl Write,
+        pattern: Option<&str>,
+        options: &[&str],
+    ) -> Result<()> {
+        let data_only = options.contains(&"--data-only");
+        let no_internal = options.contains(&"--nosys");
+        let escape_controls = !options.contains(&"--newlines");
+
+        let schema = self.conn.schema();
+        let objects = match pattern {
+            Some(pattern) => schema.objects_like(pattern),
+            None => schema.objects.iter().collect(),
+        };
+        let objects = objects
+            .into_iter()
+            .filter(|o| o.sql().is_some() && !(no_internal && o.is_internal()));
+        // Each table with the names of the columns its rows are inserted with, which leave
+        // out generated columns, then the other objects' statements. sqlite_sequence comes
+        // last, as inserting rows into the other tables can change it.
+        let mut tables: Vec<(String, String, Vec<String>)> = Vec::new();
+        let mut statements = Vec::new();
+        for object in objects {
+            match object {
+                ObjectSchema::Table(table) => {
+                    let columns = table.columns.iter().filter(|c| c.generated.is_none());
+                    let columns = columns.map(|c| quote_identifier(&c.name).into_owned());
+                    tables.push((table.name.clone(), table.sql.clone(), columns.collect()));
+                }
+                // Views, then triggers, then indexes, in the order sqlite3 dumps them
+                ObjectSchema::View(view) => statements.push((0, view.sql.clone())),
+                ObjectSchema::Trigger(trigger) => statements.push((1, trigger.sql.clone())),
+                ObjectSchema::Index(index) => {
+                    statements.push((2, index.sql.clone().unwrap_or_default()))
+                }
+            }
+        }
+        tables.sort_by_key(|(name, ..)| name.eq_ignore_ascii_case("sqlite_sequence"));
+        statements.sort_by_key(|(order, _)| *order);
+
+        if !data_only {
+            writeln!(out, "PRAGMA foreign_keys=OFF;")?;
+            writeln!(out, "BEGIN TRANSACTION;")?;
+        }
+        let mut writable_schema = false;
+        for (name, sql, columns) in tables {
+            if name.eq_ignore_ascii_case("sqlite_sequence") {
+                // It can only be created along with an AUTOINCREMENT table, or by writing
+                // to the schema
+                writeln!(out, "PRAGMA writable_schema=ON;")?;
+                writeln!(out, "CREATE TABLE IF NOT EXISTS sqlite_sequence(name,seq);")?;
+                writeln!(out, "DELETE FROM sqlite_sequence;")?;
+                writable_schema = true;
+            } else if STAT_TABLES.iter().any(|t| name.eq_ignore_ascii_case(t)) {
+                if !data_only {
+                    writeln!(out, "ANALYZE sqlite_schema;")?;
+                }
+            } else if name.to_ascii_lowercase().starts_with("sqlite_") {
+                continue;
+            } else if !data_only {
+                match sql.strip_prefix("CREATE TABLE ") {
+                    Some(rest) if rest.starts_with(['\'', '"']) => {
+                        writeln!(out, "CREATE TABLE IF NOT EXISTS {};", rest)?
+                    }
+                    _ => writeln!(out, "{};", sql)?,
+                }
+            }
+
+            let table = quote_identifier(&name);
+            let sql = format!("SELECT {} FROM {}", columns.join(", "), table);
+            let mut statement = self.conn.prepare(&sql)?;
+            for row in statement.query(&[])? {
+                let values = row?
+                    .values()
+                    .iter()
+                    .map(|v| sql_literal(v, escape_controls))
+                    .collect::<Vec<_>>();
+                writeln!(out, "INSERT INTO {} VALUES({});", table, values.join(","))?;
+            }
+        }
+        if !data_only {
+            for (_, sql) in statements {
+                writeln!(out, "{};", sql)?;
+            }
+        }
+        if writable_schema {
+            writeln!(out, "PRAGMA writable_schema=OFF;")?;
+        }
+        if !data_only {
+            writeln!(out, "COMMIT;")?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Run one SQL statement and print its result rows.
+    fn run_sql(&mut self, sql: &str) -> Result<()> {
+        let sql = skip_blank(sql).trim_end();
+        if sql.is_empty() || sql == ";" {
+            return Ok(());
+        }
+        let (start, cpu_start, stats) = (Instant::now(), cpu_times(), self.conn.stats());
+        let mut rows_returned = 0;
+        let mut statement = self.conn.prepare(sql)?;
+        let rows = statement.query(&[])?;
+        let column_names = rows.column_names().to_vec();
+        let rows = rows
+            .map(|row| row.map(Row::into_values))
+            .inspect(|_| rows_returned += 1);
+        let mut out = std::io::stdout().lock();
+        self.output.print_rows(&mut out, &column_names, rows)?;
+        out.flush()?;
+
+        if self.stats {
+            self.print_stats(&self.conn.stats().since(&stats), Some(rows_returned));
+        }
+        if self.timer {
+            // As sqlite3 does, CPU time is split into time in user mode and in the kernel
+            let real = start.elapsed().as_secs_f64();
+            let (user, sys) = match (cpu_start, cpu_times()) {
+                (Some((user_start, sys_start)), Some((user, sys))) => {
+                    (user - user_start, sys - sys_start)
+                }
+                _ => (0.0, 0.0),
+            };
+            println!("Run Time: real {:.6} user {:.6} sys {:.6}", real, user, sys);
+        }
+        Ok(())
+    }
+
+    /// Print the work a statement did reading the database, or that everything run so far
+    /// did. The pages read are given for each B-tree.
+    fn print_stats(&self, stats: &Stats, rows_returned: Option<usize>) {
+        if let Some(rows_returned) = rows_returned {
+            println!("{:<36} {}", "Rows returned:", rows_returned);
+        }
+        println!("{:<36} {}", "Rows examined:", stats.rows_examined);
+        println!("{:<36} {}", "Bytes decoded:", stats.bytes_decoded);
+        println!("{:<36} {}", "Page cache hits:", stats.cache_hits);
+        println!("{:<36} {}", "Page cache misses:", stats.cache_misses);
+        println!(
+            "{:<36} {}",
+            "Overflow pages followed:", stats.overflow_pages_read
+        );
+        let schema = self.conn.schema();
+        for (root_page, pages) in &stats.pages_read {
+            let label = match schema.btree_name(*root_page) {
+                Some(name) => format!("Pages read from {}:", name),
+                None => format!("Pages read from page {}:", root_page),
+            };
+            println!("{:<36} {}", label, pages);
+        }
+    }
+}
+
+/// The CPU time the process has spent in user mode and in the kernel, in seconds.
+#[cfg(unix)]
+fn cpu_times() -> Option<(f64, f64)> {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::uninit();
+    // SAFETY: getrusage only writes to the struct it's given
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    // SAFETY: getrusage succeeded, so filled in the struct
+    let usage = unsafe { usage.assume_init() };
+    let seconds = |t: libc::timeval| t.tv_sec as f64 + t.tv_usec as f64 / 1e6;
+    Some((seconds(usage.ru_utime), seconds(usage.ru_stime)))
+}
+
+#[cfg(not(unix))]
+fn cpu_times() -> Option<(f64, f64)> {
+    None
+}
+
+/// Split a dot-command into its arguments, which are separated by whitespace unless they're
+/// quoted with `'...'` or `"..."`. Backslash escapes such as `\t` are resolved in `"..."`.
+fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(c) = chars.next() else {
+            return args;
+        };
+        let mut arg = String::new();
+        if c == '\'' {
+            arg.extend(chars.by_ref().take_while(|&q| q != c));
+        } else if c == '"' {
+            while let Some(c) = chars.next().filter(|&c| c != '"') {
+                let escaped = match c {
+                    '\\' => chars.next(),
+                    c => {
+                        arg.push(c);
+                        continue;
+                    }
+                };
+                arg.push(match escaped {
+                    Some('t') => '\t',
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some(c) => c,
+                    None => '\\',
+                });
+            }
+        } else {
+            arg.push(c);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                arg.push(c);
+            }
+        }
+        args.push(arg);
+    }
+}
+
+/// Parse an on/off argument, as `.headers` takes.
+fn parse_bool(arg: &str) -> Result<bool> {
+    match arg.to_ascii_lowercase().as_str() {
+        "on" | "yes" | "true" | "1" => Ok(true),
+        "off" | "no" | "false" | "0" => Ok(false),
+        _ => bail!("not a boolean value: \"{}\"", arg),
+    }
+}
+
+/// Split the arguments of `.schema`, `.fullschema` or `.dump` into its `--` options, which must be
+/// among `allowed`, and its pattern.
+fn schema_args<'a>(args: &[&'a str], allowed: &[&str]) -> Result<(Vec<&'a str>, Option<&'a str>)> {
+    let (options, patterns): (Vec<_>, Vec<_>) =
+        args.iter().copied().partition(|a| a.starts_with("--"));
+    if let Some(option) = options.iter().find(|o| !allowed.contains(o)) {
+        bail!("unknown option: {}", option);
+    }
+    if patterns.len() > 1 {
+        bail!("too many arguments");
+    }
+    Ok((options, patterns.first().copied()))
+}
+
+/// Split SQL text into its complete statements, each ending with its `;`, and the incomplete
+/// text after the last one. Semicolons in quotes and comments, and between the `BEGIN` and
+/// `END` of a `CREATE TRIGGER`, don't end a statement.
+pub fn split_statements(sql: &str) -> (Vec<&str>, &str) {
+    let bytes = sql.as_bytes();
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'\'' | b'"' | b'`' | b'[') => {
+                let close = if quote == b'[' { b']' } else { quote };
+                i += 1;
+                while i < bytes.len() && bytes[i] != close {
+                    i += 1;
+                }
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < bytes.len() && !(bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/')) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            b';' if !in_trigger_body(&sql[start..i]) => {
+                statements.push(&sql[start..=i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    (statements, &sql[start.min(sql.len())..])
+}
+
+/// Is the end of this statement inside the body of a `CREATE TRIGGER`, where statements are
+/// separated by `;` until its `END`?
+fn in_trigger_body(statement: &str) -> bool {
+    let words = statement
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|w| !w.is_empty())
+        .take(4)
+        .map(|w| w.to_ascii_uppercase())
+        .collect::<Vec<_>>();
+    let is_trigger = match words.as_slice() {
+        [create, trigger, ..] if create == "CREATE" && trigger == "TRIGGER" => true,
+        [create, temp, trigger, ..] => {
+            create == "CREATE" && (temp == "TEMP" || temp == "TEMPORARY") && trigger == "TRIGGER"
+        }
+        _ => false,
+    };
+    is_trigger && !statement.trim_end().to_ascii_uppercase().ends_with("END")
+}
+
+/// Skip the whitespace and comments at the start of some SQL.
+fn skip_blank(sql: &str) -> &str {
+    let mut rest = sql.trim_start();
+    loop {
+        if let Some(comment) = rest.strip_prefix("--") {
+            rest = comment.split_once('\n').map_or("", |(_, r)| r).trim_start();
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.split_once("*/").map_or("", |(_, r)| r).trim_start();
+        } else {
+            return rest;
+        }
+    }
+}
+
+/// Is this text only whitespace and comments?
+fn is_blank(sql: &str) -> bool {
+    skip_blank(sql).is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlite_starter_rust::Connection;
+
+    use super::{is_blank, split_args, split_statements, Shell};
+    use crate::output::Output;
+
+    #[test]
+    fn dot_command_args() {
+        assert_eq!(
+            split_args(".schema  'my table' \"a b\"c"),
+            vec![".schema", "my table", "a b", "c"]
+        );
+        assert_eq!(
+            split_args(".separator \"\\t\" '\\n'"),
+            vec![".separator", "\t", "\\n"]
+        );
+    }
+
+    #[test]
+    fn dump_blobs() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/blobs.db");
+        let mut shell = Shell::new(Connection::open(path).unwrap(), Output::default());
+        let mut out = Vec::new();
+        shell.dump(&mut out, None, &[]).unwrap();
+        // Blobs are written as hex whether or not they're valid UTF-8
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "PRAGMA foreign_keys=OFF;\n\
+             BEGIN TRANSACTION;\n\
+             CREATE TABLE b (id INTEGER PRIMARY KEY, x BLOB);\n\
+             INSERT INTO b VALUES(1,X'00ff10');\n\
+             INSERT INTO b VALUES(2,X'');\n\
+             INSERT INTO b VALUES(3,'text');\n\
+             INSERT INTO b VALUES(4,X'c328');\n\
+             INSERT INTO b VALUES(5,NULL);\n\
+             COMMIT;\n"
+        );
+
+        let mut out = Vec::new();
+        shell.dump(&mut out, Some("b"), &["--data-only"]).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .starts_with("INSERT INTO b VALUES(1,X'00ff10');\n"));
+    }
+
+    #[test]
+    fn dump_overflowing_records() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/overflow.db");
+        let mut shell = Shell::new(Connection::open(path).unwrap(), Output::default());
+        let mut out = Vec::new();
+        shell
+            .dump(&mut out, Some("docs"), &["--data-only"])
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let long = format!(
+            "INSERT INTO docs VALUES(2,'long','{}end');\n",
+            "x".repeat(5000)
+        );
+        assert!(out.contains(&long));
+        assert!(out.contains(&format!("VALUES(3,'blob',X'{}');\n", "00".repeat(3000))));
+    }
+
+    #[test]
+    fn nested_reads() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/sample.db");
+        let mut shell = Shell::new(Connection::open(path).unwrap(), Output::default());
+        let script = std::env::temp_dir().join(format!("read_self_{}.sql", std::process::id()));
+        std::fs::write(&script, format!(".read '{}'\n", script.display())).unwrap();
+        let result = shell.run_script(format!(".read '{}'\n", script.display()).as_bytes());
+        std::fs::remove_file(&script).unwrap();
+
+        result.unwrap();
+        assert_eq!(
+            shell.script_error.unwrap().to_string(),
+            "too many nested .read commands (the limit is 25)"
+        );
+        assert_eq!(shell.read_depth, 0);
+    }
+
+    #[test]
+    fn statements_end_with_semicolons() {
+        assert_eq!(
+            split_statements("SELECT 1; SELECT ';' -- ;\n, 2; SELECT"),
+            (vec!["SELECT 1;", " SELECT ';' -- ;\n, 2;"], " SELECT")
+        );
+        assert_eq!(
+            split_statements("SELECT \"a;\", [b;] /* ; */;"),
+            (vec!["SELECT \"a;\", [b;] /* ; */;"], "")
+        );
+        assert_eq!(split_statements("SELECT 'a;\n"), (vec![], "SELECT 'a;\n"));
+
+        let trigger = "CREATE TRIGGER t AFTER INSERT ON a BEGIN SELECT 1; SELECT 2; END;";
+        assert_eq!(split_statements(trigger), (vec![trigger], ""));
+        assert_eq!(
+            split_statements("CREATE TRIGGER t AFTER INSERT ON a BEGIN SELECT 1;"),
+            (vec![], "CREATE TRIGGER t AFTER INSERT ON a BEGIN SELECT 1;")
+        );
+
+        assert!(is_blank(" -- comment\n/* another */\n"));
+        assert!(!is_blank("-- comment\nSELECT"));
+    }
+}
@@ -0,0 +1,536 @@
+use std::borrow::Cow;
+
+/// Every SQLite keyword, which must be quoted to be used as an identifier.
+const KEYWORDS: &[&str] = &[
+    "ABORT",
+    "ACTION",
+    "ADD",
+    "AFTER",
+    "ALL",
+    "ALTER",
+    "ALWAYS",
+    "ANALYZE",
+    "AND",
+    "AS",
+    "ASC",
+    "ATTACH",
+    "AUTOINCREMENT",
+    "BEFORE",
+    "BEGIN",
+    "BETWEEN",
+    "BY",
+    "CASCADE",
+    "CASE",
+    "CAST",
+    "CHECK",
+    "COLLATE",
+    "COLUMN",
+    "COMMIT",
+    "CONFLICT",
+    "CONSTRAINT",
+    "CREATE",
+    "CROSS",
+    "CURRENT",
+    "CURRENT_DATE",
+    "CURRENT_TIME",
+    "CURRENT_TIMESTAMP",
+    "DATABASE",
+    "DEFAULT",
+    "DEFERRABLE",
+    "DEFERRED",
+    "DELETE",
+    "DESC",
+    "DETACH",
+    "DISTINCT",
+    "DO",
+    "DROP",
+    "EACH",
+    "ELSE",
+    "END",
+    "ESCAPE",
+    "EXCEPT",
+    "EXCLUDE",
+    "EXCLUSIVE",
+    "EXISTS",
+    "EXPLAIN",
+    "FAIL",
+    "FILTER",
+    "FIRST",
+    "FOLLOWING",
+    "FOR",
+    "FOREIGN",
+    "FROM",
+    "FULL",
+    "GENERATED",
+    "GLOB",
+    "GROUP",
+    "GROUPS",
+    "HAVING",
+    "IF",
+    "IGNORE",
+    "IMMEDIATE",
+    "IN",
+    "INDEX",
+    "INDEXED",
+    "INITIALLY",
+    "INNER",
+    "INSERT",
+    "INSTEAD",
+    "INTERSECT",
+    "INTO",
+    "IS",
+    "ISNULL",
+    "JOIN",
+    "KEY",
+    "LAST",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "MATCH",
+    "MATERIALIZED",
+    "NATURAL",
+    "NO",
+    "NOT",
+    "NOTHING",
+    "NOTNULL",
+    "NULL",
+    "NULLS",
+    "OF",
+    "OFFSET",
+    "ON",
+    "OR",
+    "ORDER",
+    "OTHERS",
+    "OUTER",
+    "OVER",
+    "PARTITION",
+    "PLAN",
+    "PRAGMA",
+    "PRECEDING",
+    "PRIMARY",
+    "QUERY",
+    "RAISE",
+    "RANGE",
+    "RECURSIVE",
+    "REFERENCES",
+    "REGEXP",
+    "REINDEX",
+    "RELEASE",
+    "RENAME",
+    "REPLACE",
+    "RESTRICT",
+    "RETURNING",
+    "RIGHT",
+    "ROLLBACK",
+    "ROW",
+    "ROWS",
+    "SAVEPOINT",
+    "SELECT",
+    "SET",
+    "TABLE",
+    "TEMP",
+    "TEMPORARY",
+    "THEN",
+    "TIES",
+    "TO",
+    "TRANSACTION",
+    "TRIGGER",
+    "UNBOUNDED",
+    "UNION",
+    "UNIQUE",
+    "UPDATE",
+    "USING",
+    "VACUUM",
+    "VALUES",
+    "VIEW",
+    "VIRTUAL",
+    "WHEN",
+    "WHERE",
+    "WINDOW",
+    "WITH",
+    "WITHOUT",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    /// A bare identifier or keyword.
+    Identifier(String),
+    /// An identifier quoted with `"..."`, `` `...` `` or `[...]`.
+    QuotedIdentifier(String),
+    /// A string literal quoted with `'...'`.
+    String(String),
+    Integer(i64),
+    Real(f64),
+    /// A blob literal, `X'...'`.
+    Blob(Vec<u8>),
+    /// A parameter, `?`, `?NNN`, `:name`, `@name` or `$name`, including its prefix.
+    Variable(String),
+    LeftParen,
+    RightParen,
+    Comma,
+    Dot,
+    Semicolon,
+    Star,
+    Plus,
+    Minus,
+    Slash,
+    Percent,
+    Concat,
+    Arrow,
+    DoubleArrow,
+    Equals,
+    NotEquals,
+    LessThan,
+    LessThanOrEquals,
+    GreaterThan,
+    GreaterThanOrEquals,
+    BitAnd,
+    BitOr,
+    BitNot,
+    ShiftLeft,
+    ShiftRight,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// The source text of the token, used for error messages.
+    pub text: String,
+    /// Where the token starts in the source, in bytes.
+    pub offset: usize,
+}
+
+impl Token {
+    /// Is this token the given (case-insensitive) keyword?
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.kind, TokenKind::Identifier(s) if s.eq_ignore_ascii_case(keyword))
+    }
+}
+
+pub fn tokenize(input: &str) -> anyhow::Result<Vec<Token>> {
+    let chars = input.char_indices().collect::<Vec<_>>();
+    let byte_offset = |i: usize| chars.get(i).map(|(o, _)| *o).unwrap_or(input.len());
+
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i].1;
+        let start = i;
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        // Comments
+        if c == '-' && chars.get(i + 1).map(|(_, c)| *c) == Some('-') {
+            while i < chars.len() && chars[i].1 != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if c == '/' && chars.get(i + 1).map(|(_, c)| *c) == Some('*') {
+            i += 2;
+            while i < chars.len()
+                && !(chars[i].1 == '*' && chars.get(i + 1).map(|(_, c)| *c) == Some('/'))
+            {
+                i += 1;
+            }
+            i += 2;
+            continue;
+        }
+
+        let kind = if (c == 'x' || c == 'X') && chars.get(i + 1).map(|(_, c)| *c) == Some('\'') {
+            i += 1;
+            let (hex, end) = quoted(&chars, i, '\'')?;
+            i = end;
+            TokenKind::Blob(parse_hex(&hex)?)
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len()
+                && (chars[i].1.is_alphanumeric() || chars[i].1 == '_' || chars[i].1 == '$')
+            {
+                i += 1;
+            }
+            TokenKind::Identifier(input[byte_offset(start)..byte_offset(i)].to_owned())
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(|(_, c)| c.is_ascii_digit()))
+        {
+            let (kind, end) = number(input, &chars, i)?;
+            i = end;
+            kind
+        } else if c == '?' {
+            i += 1;
+            while i < chars.len() && chars[i].1.is_ascii_digit() {
+                i += 1;
+            }
+            TokenKind::Variable(input[byte_offset(start)..byte_offset(i)].to_owned())
+        } else if matches!(c, ':' | '@' | '$')
+            && chars
+                .get(i + 1)
+                .is_some_and(|(_, c)| c.is_alphanumeric() || *c == '_')
+        {
+            i += 1;
+            while i < chars.len() && (chars[i].1.is_alphanumeric() || chars[i].1 == '_') {
+                i += 1;
+            }
+            TokenKind::Variable(input[byte_offset(start)..byte_offset(i)].to_owned())
+        } else {
+            match c {
+                '\'' => {
+                    let (s, end) = quoted(&chars, i, '\'')?;
+                    i = end;
+                    TokenKind::String(s)
+                }
+                '"' | '`' => {
+                    let (s, end) = quoted(&chars, i, c)?;
+                    i = end;
+                    TokenKind::QuotedIdentifier(s)
+                }
+                '[' => {
+                    let end = chars[i..]
+                        .iter()
+                        .position(|(_, c)| *c == ']')
+                        .ok_or_else(|| anyhow::format_err!("unrecognized token: \"[\""))?;
+                    let s = chars[i + 1..i + end].iter().map(|(_, c)| c).collect();
+                    i += end + 1;
+                    TokenKind::QuotedIdentifier(s)
+                }
+                _ => {
+                    let next = chars.get(i + 1).map(|(_, c)| *c);
+                    let next2 = chars.get(i + 2).map(|(_, c)| *c);
+                    let (kind, len) = match (c, next, next2) {
+                        ('-', Some('>'), Some('>')) => (TokenKind::DoubleArrow, 3),
+                        ('-', Some('>'), _) => (TokenKind::Arrow, 2),
+                        ('|', Some('|'), _) => (TokenKind::Concat, 2),
+                        ('=', Some('='), _) => (TokenKind::Equals, 2),
+                        ('!', Some('='), _) => (TokenKind::NotEquals, 2),
+                        ('<', Some('>'), _) => (TokenKind::NotEquals, 2),
+                        ('<', Some('='), _) => (TokenKind::LessThanOrEquals, 2),
+                        ('<', Some('<'), _) => (TokenKind::ShiftLeft, 2),
+                        ('>', Some('='), _) => (TokenKind::GreaterThanOrEquals, 2),
+                        ('>', Some('>'), _) => (TokenKind::ShiftRight, 2),
+                        ('(', _, _) => (TokenKind::LeftParen, 1),
+                        (')', _, _) => (TokenKind::RightParen, 1),
+                        (',', _, _) => (TokenKind::Comma, 1),
+                        ('.', _, _) => (TokenKind::Dot, 1),
+                        (';', _, _) => (TokenKind::Semicolon, 1),
+                        ('*', _, _) => (TokenKind::Star, 1),
+                        ('+', _, _) => (TokenKind::Plus, 1),
+                        ('-', _, _) => (TokenKind::Minus, 1),
+                        ('/', _, _) => (TokenKind::Slash, 1),
+                        ('%', _, _) => (TokenKind::Percent, 1),
+                        ('=', _, _) => (TokenKind::Equals, 1),
+                        ('<', _, _) => (TokenKind::LessThan, 1),
+                        ('>', _, _) => (TokenKind::GreaterThan, 1),
+                        ('&', _, _) => (TokenKind::BitAnd, 1),
+                        ('|', _, _) => (TokenKind::BitOr, 1),
+                        ('~', _, _) => (TokenKind::BitNot, 1),
+                        _ => anyhow::bail!("unrecognized token: \"{}\"", c),
+                    };
+                    i += len;
+                    kind
+                }
+            }
+        };
+
+        tokens.push(Token {
+            kind,
+            text: input[byte_offset(start)..byte_offset(i)].to_owned(),
+            offset: byte_offset(start),
+        });
+    }
+
+    Ok(tokens)
+}
+
+/// Read a quoted string starting at `start`, where doubled quotes are an escaped quote.
+/// Returns the unquoted contents and the index just past the closing quote.
+fn quoted(chars: &[(usize, char)], start: usize, quote: char) -> anyhow::Result<(String, usize)> {
+    let mut s = String::new();
+    let mut i = start + 1;
+    loop {
+        match chars.get(i).map(|(_, c)| *c) {
+            None => anyhow::bail!("unrecognized token: unterminated {} quote", quote),
+            Some(c) if c == quote => {
+                if chars.get(i + 1).map(|(_, c)| *c) == Some(quote) {
+                    s.push(quote);
+                    i += 2;
+                } else {
+                    return Ok((s, i + 1));
+                }
+            }
+            Some(c) => {
+                s.push(c);
+                i += 1;
+            }
+        }
+    }
+}
+
+fn number(
+    input: &str,
+    chars: &[(usize, char)],
+    start: usize,
+) -> anyhow::Result<(TokenKind, usize)> {
+    let byte_offset = |i: usize| chars.get(i).map(|(o, _)| *o).unwrap_or(input.len());
+    let peek = |i: usize| chars.get(i).map(|(_, c)| *c);
+
+    let mut i = start;
+    if peek(i) == Some('0') && matches!(peek(i + 1), Some('x' | 'X')) {
+        i += 2;
+        while peek(i).is_some_and(|c| c.is_ascii_hexdigit()) {
+            i += 1;
+        }
+        let digits = &input[byte_offset(start + 2)..byte_offset(i)];
+        let value = u64::from_str_radix(digits, 16)
+            .map_err(|_| anyhow::format_err!("hex literal too big: {}", digits))?;
+        return Ok((TokenKind::Integer(value as i64), i));
+    }
+
+    let mut is_real = false;
+    while peek(i).is_some_and(|c| c.is_ascii_digit()) {
+        i += 1;
+    }
+    if peek(i) == Some('.') {
+        is_real = true;
+        i += 1;
+        while peek(i).is_some_and(|c| c.is_ascii_digit()) {
+            i += 1;
+        }
+    }
+    if matches!(peek(i), Some('e' | 'E')) {
+        let mut j = i + 1;
+        if matches!(peek(j), Some('+' | '-')) {
+            j += 1;
+        }
+        if peek(j).is_some_and(|c| c.is_ascii_digit()) {
+            is_real = true;
+            i = j;
+            while peek(i).is_some_and(|c| c.is_ascii_digit()) {
+                i += 1;
+            }
+        }
+    }
+
+    let text = &input[byte_offset(start)..byte_offset(i)];
+    if !is_real {
+        if let Ok(n) = text.parse::<i64>() {
+            return Ok((TokenKind::Integer(n), i));
+        }
+    }
+    let f = text
+        .parse::<f64>()
+        .map_err(|_| anyhow::format_err!("unrecognized token: \"{}\"", text))?;
+    Ok((TokenKind::Real(f), i))
+}
+
+fn parse_hex(hex: &str) -> anyhow::Result<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("unrecognized token: \"X'{}'\"", hex);
+    }
+    Ok((0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect())
+}
+
+/// Quote an identifier with `"..."` if it isn't a plain name, or is a keyword.
+pub fn quote_identifier(name: &str) -> Cow<'_, str> {
+    let mut chars = name.chars();
+    let plain = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(name));
+    if plain {
+        Cow::Borrowed(name)
+    } else {
+        Cow::Owned(format!("\"{}\"", name.replace('"', "\"\"")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{quote_identifier, tokenize, TokenKind};
+
+    fn kinds(input: &str) -> Vec<TokenKind> {
+        tokenize(input)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.kind)
+            .collect()
+    }
+
+    #[test]
+    fn select() {
+        assert_eq!(
+            kinds("SELECT name, count(*) FROM \"my table\" WHERE x >= 1.5;"),
+            vec![
+                TokenKind::Identifier("SELECT".into()),
+                TokenKind::Identifier("name".into()),
+                TokenKind::Comma,
+                TokenKind::Identifier("count".into()),
+                TokenKind::LeftParen,
+                TokenKind::Star,
+                TokenKind::RightParen,
+                TokenKind::Identifier("FROM".into()),
+                TokenKind::QuotedIdentifier("my table".into()),
+                TokenKind::Identifier("WHERE".into()),
+                TokenKind::Identifier("x".into()),
+                TokenKind::GreaterThanOrEquals,
+                TokenKind::Real(1.5),
+                TokenKind::Semicolon,
+            ]
+        );
+    }
+
+    #[test]
+    fn strings_and_blobs() {
+        assert_eq!(
+            kinds("'it''s' X'0aFF'"),
+            vec![
+                TokenKind::String("it's".into()),
+                TokenKind::Blob(vec![0x0a, 0xff]),
+            ]
+        );
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(
+            kinds("? ?12 :name @a_1 $b"),
+            vec![
+                TokenKind::Variable("?".into()),
+                TokenKind::Variable("?12".into()),
+                TokenKind::Variable(":name".into()),
+                TokenKind::Variable("@a_1".into()),
+                TokenKind::Variable("$b".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_identifiers() {
+        assert_eq!(quote_identifier("_apples2"), "_apples2");
+        assert_eq!(quote_identifier("select"), "\"select\"");
+        assert_eq!(quote_identifier("my \"table\""), "\"my \"\"table\"\"\"");
+        assert_eq!(quote_identifier("1"), "\"1\"");
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(
+            kinds("a->'$.b' ->> 2 || -- comment\n <> /* block */ 0x10"),
+            vec![
+                TokenKind::Identifier("a".into()),
+                TokenKind::Arrow,
+                TokenKind::String("$.b".into()),
+                TokenKind::DoubleArrow,
+                TokenKind::Integer(2),
+                TokenKind::Concat,
+                TokenKind::NotEquals,
+                TokenKind::Integer(16),
+            ]
+        );
+    }
+}
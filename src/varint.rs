@@ -1,11 +1,21 @@
-use nom::IResult;
+use nom::{error::ErrorKind, IResult};
 
 pub fn varint(input: &[u8]) -> IResult<&[u8], i64> {
+    // Corrupt pages can end part way through a varint
+    let byte = |i: usize| {
+        input
+            .get(i)
+            .copied()
+            .ok_or(nom::Err::Error(nom::error::Error::new(
+                input,
+                ErrorKind::Eof,
+            )))
+    };
     let mut i = 0;
-    let mut value: i64 = (input[i] as i64) & 0x7f;
-    while high_bit(input[i]) && i < 8 {
+    let mut value: i64 = (byte(i)? as i64) & 0x7f;
+    while high_bit(byte(i)?) && i < 8 {
         i += 1;
-        value = (value << 7) | ((input[i] as i64) & 0x7f);
+        value = (value << 7) | ((byte(i)? as i64) & 0x7f);
     }
     Ok((&input[i + 1..], value))
 }
@@ -28,6 +38,12 @@ mod tests {
         assert!(!high_bit(0b01111111));
     }
 
+    #[test]
+    fn truncated() {
+        assert!(varint(&[]).is_err());
+        assert!(varint(&[0x81, 0x80]).is_err());
+    }
+
     #[test]
     fn one_byte() {
         let input = &[0x15];
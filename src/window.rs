@@ -0,0 +1,578 @@
+use std::ops::Range;
+
+use crate::{
+    collation::{Collation, Collations},
+    expr::{to_integer, to_real, EvalContext, Expr, Scope},
+    function::FrameAggregate,
+    query::{compare_keys, compare_sort_keys, rows_equal, OrderingTerm},
+    record::Value,
+};
+
+/// The `OVER (...)` clause of a window function call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Window {
+    pub partition_by: Vec<Expr>,
+    pub order_by: Vec<OrderingTerm>,
+    /// The default frame is every row up to and including the current row's peers if there's
+    /// an `ORDER BY`, or the whole partition if not.
+    pub frame: Option<Frame>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub units: FrameUnits,
+    pub start: FrameBound,
+    pub end: FrameBound,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameUnits {
+    Rows,
+    Range,
+    Groups,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameBound {
+    UnboundedPreceding,
+    Preceding(Expr),
+    CurrentRow,
+    Following(Expr),
+    UnboundedFollowing,
+}
+
+/// Functions that can only be used as window functions.
+const WINDOW_FUNCTIONS: &[&str] = &[
+    "row_number",
+    "rank",
+    "dense_rank",
+    "percent_rank",
+    "cume_dist",
+    "ntile",
+    "lag",
+    "lead",
+    "first_value",
+    "last_value",
+    "nth_value",
+];
+
+pub fn is_window_only(name: &str) -> bool {
+    WINDOW_FUNCTIONS.contains(&name)
+}
+
+impl Window {
+    /// Call `f` on every expression in the window definition.
+    pub fn walk(&self, f: &mut dyn FnMut(&Expr)) {
+        self.partition_by.iter().for_each(|e| e.walk(f));
+        self.order_by.iter().for_each(|o| o.expr.walk(f));
+        if let Some(frame) = self.frame.as_ref() {
+            for bound in [&frame.start, &frame.end] {
+                if let FrameBound::Preceding(e) | FrameBound::Following(e) = bound {
+                    e.walk(f);
+                }
+            }
+        }
+    }
+
+    /// Copy the window definition, replacing expressions as in `Expr::replace`.
+    pub fn replace(&self, f: &mut dyn FnMut(&Expr) -> Option<Expr>) -> Window {
+        let mut bound = |bound: &FrameBound| match bound {
+            FrameBound::Preceding(e) => FrameBound::Preceding(e.replace(f)),
+            FrameBound::Following(e) => FrameBound::Following(e.replace(f)),
+            bound => bound.clone(),
+        };
+        let frame = self.frame.as_ref().map(|frame| Frame {
+            units: frame.units,
+            start: bound(&frame.start),
+            end: bound(&frame.end),
+        });
+        Window {
+            partition_by: self.partition_by.iter().map(|e| e.replace(f)).collect(),
+            order_by: self
+                .order_by
+                .iter()
+                .map(|o| OrderingTerm {
+                    expr: o.expr.replace(f),
+                    descending: o.descending,
+                    nulls_first: o.nulls_first,
+                })
+                .collect(),
+            frame,
+        }
+    }
+
+    /// The partition and sort keys of a row.
+    fn keys(&self, ctx: &EvalContext) -> anyhow::Result<(Vec<Value>, Vec<Value>)> {
+        let partition = self
+            .partition_by
+            .iter()
+            .map(|e| e.eval(ctx))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let order = self
+            .order_by
+            .iter()
+            .map(|o| o.expr.eval(ctx))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok((partition, order))
+    }
+
+    /// Sort row indices into partitions, each sorted by the window's `ORDER BY`.
+    fn partitions(
+        &self,
+        scope: &Scope,
+        rows: &[Vec<Value>],
+        collations: &Collations,
+    ) -> anyhow::Result<Vec<Partition>> {
+        let collation = |e: &Expr| collations.resolve(e.collation(scope).as_deref());
+        let partition_collations = self
+            .partition_by
+            .iter()
+            .map(collation)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let order_collations = self
+            .order_by
+            .iter()
+            .map(|o| collation(&o.expr))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut keyed = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| {
+                let (partition_key, order_key) =
+                    self.keys(&EvalContext::new(scope, row, collations))?;
+                Ok((index, partition_key, order_key))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        // One sort puts the partitions in order, and the rows of each in order after that
+        keyed.sort_by(|(_, a_partition, a_order), (_, b_partition, b_order)| {
+            compare_keys(&partition_collations, a_partition, b_partition).then_with(|| {
+                compare_sort_keys(&self.order_by, &order_collations, a_order, b_order)
+            })
+        });
+
+        let mut partitions = Vec::new();
+        let mut partition_rows = Vec::new();
+        let mut keyed = keyed.into_iter().peekable();
+        while let Some((index, partition_key, order_key)) = keyed.next() {
+            partition_rows.push((index, order_key));
+            let last = keyed.peek().map_or(true, |(_, next, _)| {
+                !rows_equal(next, &partition_key, &partition_collations)
+            });
+            if last {
+                partitions.push(Partition::new(
+                    std::mem::take(&mut partition_rows),
+                    &order_collations,
+                ));
+            }
+        }
+        Ok(partitions)
+    }
+
+    /// The order rows are in after being sorted into partitions, which is the order results
+    /// come out in if the query has no `ORDER BY`.
+    pub fn sorted_order(
+        &self,
+        scope: &Scope,
+        rows: &[Vec<Value>],
+        collations: &Collations,
+    ) -> anyhow::Result<Vec<usize>> {
+        Ok(self
+            .partitions(scope, rows, collations)?
+            .into_iter()
+            .flat_map(|p| p.rows.into_iter().map(|(i, _)| i))
+            .collect())
+    }
+}
+
+/// The rows of one partition, as indices into the input rows paired with their sort keys.
+struct Partition {
+    rows: Vec<(usize, Vec<Value>)>,
+    /// The start position of each peer group, i.e. run of rows with equal sort keys.
+    peer_groups: Vec<usize>,
+    /// The peer group of each row.
+    peer_group: Vec<usize>,
+    /// The positions of the rows whose first sort key is a number, which sort next to each
+    /// other.
+    numeric_keys: Range<usize>,
+}
+
+impl Partition {
+    /// Group sorted rows into their peers, comparing their sort keys with `collations`.
+    fn new(rows: Vec<(usize, Vec<Value>)>, collations: &[Collation]) -> Self {
+        let mut peer_groups = Vec::new();
+        let mut peer_group = Vec::with_capacity(rows.len());
+        for (position, (_, key)) in rows.iter().enumerate() {
+            if position == 0 || !rows_equal(&rows[position - 1].1, key, collations) {
+                peer_groups.push(position);
+            }
+            peer_group.push(peer_groups.len() - 1);
+        }
+
+        let is_numeric = |(_, key): &(usize, Vec<Value>)| {
+            matches!(key.first(), Some(Value::Integer(_) | Value::Real(_)))
+        };
+        let start = rows.iter().position(is_numeric).unwrap_or(rows.len());
+        let end = start + rows[start..].iter().take_while(|r| is_numeric(r)).count();
+        Partition {
+            rows,
+            peer_groups,
+            peer_group,
+            numeric_keys: start..end,
+        }
+    }
+
+    /// The range of positions of the peers of the row at `position`, i.e. the rows with equal
+    /// sort keys.
+    fn peers(&self, position: usize) -> (usize, usize) {
+        let group = self.peer_group[position];
+        let end = self
+            .peer_groups
+            .get(group + 1)
+            .copied()
+            .unwrap_or(self.rows.len());
+        (self.peer_groups[group], end)
+    }
+}
+
+/// Compute a window function for every row, returning its values in the same order as `rows`.
+pub fn evaluate(
+    function: &Expr,
+    window: &Window,
+    scope: &Scope,
+    rows: &[Vec<Value>],
+    collations: &Collations,
+) -> anyhow::Result<Vec<Value>> {
+    let Expr::Function {
+        name,
+        args,
+        distinct,
+    } = function
+    else {
+        unreachable!("window function must be a function call");
+    };
+    if !is_window_only(name) && !crate::function::is_aggregate(name, args.len()) {
+        anyhow::bail!("{}() may not be used as a window function", name);
+    }
+    if let Some(frame) = window.frame.as_ref() {
+        let has_offset =
+            |b: &FrameBound| matches!(b, FrameBound::Preceding(_) | FrameBound::Following(_));
+        if frame.units == FrameUnits::Range
+            && (has_offset(&frame.start) || has_offset(&frame.end))
+            && window.order_by.len() != 1
+        {
+            anyhow::bail!("RANGE with offset PRECEDING/FOLLOWING requires one ORDER BY expression");
+        }
+    }
+
+    let mut values = vec![Value::Null; rows.len()];
+    for partition in window.partitions(scope, rows, collations)? {
+        let count = partition.rows.len();
+        let row_at = |position: usize| rows[partition.rows[position].0].as_slice();
+        let mut aggregate = if is_window_only(name) {
+            None
+        } else {
+            let arg_values = partition
+                .rows
+                .iter()
+                .map(|(index, _)| {
+                    let ctx = EvalContext::new(scope, &rows[*index], collations);
+                    args.iter().map(|a| a.eval(&ctx)).collect()
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let json_args = args.iter().map(Expr::is_json).collect();
+            let arg_collations =
+                EvalContext::new(scope, &[], collations).argument_collations(args)?;
+            Some(FrameAggregate::new(
+                name,
+                arg_values,
+                *distinct,
+                json_args,
+                arg_collations,
+            ))
+        };
+
+        for (position, (index, _)) in partition.rows.iter().enumerate() {
+            let ctx = EvalContext::new(scope, &rows[*index], collations);
+            let (peers_start, peers_end) = partition.peers(position);
+            let arg = |i: usize| -> anyhow::Result<Option<Value>> {
+                args.get(i).map(|a| a.eval(&ctx)).transpose()
+            };
+
+            values[*index] = match name.as_str() {
+                "row_number" => Value::Integer(position as i64 + 1),
+                "rank" => Value::Integer(peers_start as i64 + 1),
+                "dense_rank" => Value::Integer(partition.peer_group[position] as i64 + 1),
+                "percent_rank" => Value::Real(if count > 1 {
+                    peers_start as f64 / (count - 1) as f64
+                } else {
+                    0.0
+                }),
+                "cume_dist" => Value::Real(peers_end as f64 / count as f64),
+                "ntile" => {
+                    let buckets = arg(0)?
+                        .as_ref()
+                        .and_then(to_integer)
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| {
+                            anyhow::format_err!("argument of ntile must be a positive integer")
+                        })? as usize;
+                    // The first `count % buckets` buckets get one extra row
+                    let size = count / buckets;
+                    let larger = count % buckets;
+                    let bucket = if position < larger * (size + 1) {
+                        position / (size + 1)
+                    } else {
+                        larger + (position - larger * (size + 1)) / size.max(1)
+                    };
+                    Value::Integer(bucket as i64 + 1)
+                }
+                "lag" | "lead" => {
+                    let offset = match arg(1)? {
+                        Some(offset) => to_integer(&offset).unwrap_or(0),
+                        None => 1,
+                    };
+                    let target = if name == "lag" {
+                        position as i64 - offset
+                    } else {
+                        position as i64 + offset
+                    };
+                    if (0..count as i64).contains(&target) {
+                        args[0].eval(&EvalContext::new(
+                            scope,
+                            row_at(target as usize),
+                            collations,
+                        ))?
+                    } else {
+                        arg(2)?.unwrap_or(Value::Null)
+                    }
+                }
+                _ => {
+                    let (start, end) = frame_bounds(window, &partition, position, &ctx)?;
+                    let frame_row = |offset: usize| -> anyhow::Result<Value> {
+                        args[0].eval(&EvalContext::new(scope, row_at(start + offset), collations))
+                    };
+                    match name.as_str() {
+                        "first_value" if start < end => frame_row(0)?,
+                        "last_value" if start < end => frame_row(end - start - 1)?,
+                        "nth_value" => {
+                            let n = arg(1)?
+                                .as_ref()
+                                .and_then(to_integer)
+                                .filter(|n| *n > 0)
+                                .ok_or_else(|| {
+                                    anyhow::format_err!(
+                                        "second argument to nth_value must be a positive integer"
+                                    )
+                                })? as usize;
+                            if n <= end - start {
+                                frame_row(n - 1)?
+                            } else {
+                                Value::Null
+                            }
+                        }
+                        "first_value" | "last_value" => Value::Null,
+                        // An aggregate function over the rows in the frame
+                        _ => aggregate
+                            .as_mut()
+                            .expect("aggregate window functions have frame state")
+                            .value(start, end)?,
+                    }
+                }
+            };
+        }
+    }
+    Ok(values)
+}
+
+/// The range of positions in the partition that make up the frame of the row at `position`.
+fn frame_bounds(
+    window: &Window,
+    partition: &Partition,
+    position: usize,
+    ctx: &EvalContext,
+) -> anyhow::Result<(usize, usize)> {
+    let count = partition.rows.len();
+    let (peers_start, peers_end) = partition.peers(position);
+    let Some(frame) = window.frame.as_ref() else {
+        return Ok(if window.order_by.is_empty() {
+            (0, count)
+        } else {
+            (0, peers_end)
+        });
+    };
+
+    let offset = |expr: &Expr, is_start: bool| -> anyhow::Result<f64> {
+        let value = expr.eval(ctx)?;
+        let offset = match (frame.units, &value) {
+            (FrameUnits::Range, Value::Integer(_) | Value::Real(_)) => to_real(&value),
+            (_, Value::Integer(n)) => Some(*n as f64),
+            _ => None,
+        };
+        offset.filter(|n| *n >= 0.0).ok_or_else(|| {
+            anyhow::format_err!(
+                "frame {} offset must be a non-negative integer",
+                if is_start { "starting" } else { "ending" }
+            )
+        })
+    };
+
+    let bound = |bound: &FrameBound, is_start: bool| -> anyhow::Result<usize> {
+        let (offset, preceding) = match bound {
+            FrameBound::UnboundedPreceding => return Ok(0),
+            FrameBound::UnboundedFollowing => return Ok(count),
+            FrameBound::CurrentRow => {
+                return Ok(match (frame.units, is_start) {
+                    (FrameUnits::Rows, true) => position,
+                    (FrameUnits::Rows, false) => position + 1,
+                    (_, true) => peers_start,
+                    (_, false) => peers_end,
+                })
+            }
+            FrameBound::Preceding(expr) => (offset(expr, is_start)?, true),
+            FrameBound::Following(expr) => (offset(expr, is_start)?, false),
+        };
+
+        match frame.units {
+            FrameUnits::Rows => {
+                let offset = offset as usize;
+                let target = if preceding {
+                    position.saturating_sub(offset)
+                } else {
+                    (position + offset).min(count)
+                };
+                Ok(if is_start {
+                    target
+                } else {
+                    (target + 1).min(count)
+                })
+            }
+            FrameUnits::Groups => {
+                let groups = &partition.peer_groups;
+                let group = partition.peer_group[position];
+                let offset = offset as usize;
+                let target = if preceding {
+                    group.checked_sub(offset)
+                } else {
+                    Some(group + offset).filter(|g| *g < groups.len())
+                };
+                Ok(match (target, is_start) {
+                    (Some(target), true) => groups[target],
+                    (Some(target), false) => partition.peers(groups[target]).1,
+                    // Before the first group
+                    (None, _) if preceding => 0,
+                    // After the last group
+                    (None, _) => count,
+                })
+            }
+            FrameUnits::Range => {
+                let term = &window.order_by[0];
+                let current = match partition.rows[position].1[0] {
+                    Value::Integer(n) => n as f64,
+                    Value::Real(f) => f,
+                    // Rows with other keys only have their peers in range
+                    _ => return Ok(if is_start { peers_start } else { peers_end }),
+                };
+                // How far each row's key is past the current row's, in sort order, which only
+                // grows through the rows with numeric keys
+                let distance = |(_, key): &(usize, Vec<Value>)| {
+                    let key = to_real(&key[0]).unwrap_or(0.0);
+                    if term.descending {
+                        current - key
+                    } else {
+                        key - current
+                    }
+                };
+                let limit = if preceding { -offset } else { offset };
+                let numeric = &partition.rows[partition.numeric_keys.clone()];
+                let start = partition.numeric_keys.start;
+                Ok(if is_start {
+                    start + numeric.partition_point(|r| distance(r) < limit)
+                } else {
+                    start + numeric.partition_point(|r| distance(r) <= limit)
+                })
+            }
+        }
+    };
+
+    let start = bound(&frame.start, true)?;
+    let end = bound(&frame.end, false)?;
+    Ok((start, end.max(start)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evaluate, Frame, FrameBound, FrameUnits, Window};
+    use crate::{
+        collation::Collations,
+        expr::{Expr, Scope},
+        query::OrderingTerm,
+        record::Value,
+    };
+
+    fn column(name: &str) -> Expr {
+        Expr::Column {
+            table: None,
+            name: name.into(),
+        }
+    }
+
+    fn function(name: &str, args: Vec<Expr>) -> Expr {
+        Expr::Function {
+            name: name.into(),
+            args,
+            distinct: false,
+        }
+    }
+
+    #[test]
+    fn frames() {
+        let scope = Scope::new(None, &["x"]);
+        let rows = [1, 2, 2, 4, 7]
+            .iter()
+            .map(|n| vec![Value::Integer(*n)])
+            .collect::<Vec<_>>();
+        let window = |frame: Option<Frame>| Window {
+            partition_by: Vec::new(),
+            order_by: vec![OrderingTerm {
+                expr: column("x"),
+                descending: false,
+                nulls_first: None,
+            }],
+            frame,
+        };
+        let sum = |frame: Option<Frame>| {
+            evaluate(
+                &function("sum", vec![column("x")]),
+                &window(frame),
+                &scope,
+                &rows,
+                &Collations::default(),
+            )
+            .unwrap()
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+        };
+
+        // The default frame includes peers of the current row
+        assert_eq!(sum(None), vec!["1", "5", "5", "9", "16"]);
+        let one_each_side = |units| Frame {
+            units,
+            start: FrameBound::Preceding(Expr::Literal(Value::Integer(1))),
+            end: FrameBound::Following(Expr::Literal(Value::Integer(1))),
+        };
+        assert_eq!(
+            sum(Some(one_each_side(FrameUnits::Rows))),
+            vec!["3", "5", "8", "13", "11"]
+        );
+        assert_eq!(
+            sum(Some(one_each_side(FrameUnits::Groups))),
+            vec!["5", "9", "9", "15", "11"]
+        );
+        assert_eq!(
+            sum(Some(one_each_side(FrameUnits::Range))),
+            vec!["5", "5", "5", "4", "7"]
+        );
+    }
+}